@@ -0,0 +1,307 @@
+//! # Difficulty Adjustment
+//!
+//! This module computes the difficulty of the next block from the headers preceding it.
+//!
+//! `DifficultyAdjustment` is the rule a chain uses; `WindowedAverage` is its default implementation.
+//! It averages the difficulty of the last `window` blocks and scales it by the ratio of the expected
+//! to the observed time those blocks took. The result is bounded to a change of `1 / max_change_divisor`
+//! of the parent difficulty per block and never falls below `minimum_difficulty`.
+//!
+//! Observed times are measured between median-time-past values, the median timestamp of the last
+//! `median_span` blocks, so that a single miner cannot skew the difficulty with its timestamp.
+//! For the same reason, a new block must have a timestamp after the median-time-past of its parent.
+//!
+//! Ancestors are always passed oldest first, ending with the parent of the next block.
+
+use core_utils::timestamp::Timestamp;
+use ethereum_types::U512;
+use primvites::{block_header::BlockHeader, U256};
+use serde::{Serialize, Deserialize};
+
+// Represents errors that can occur while adjusting the difficulty.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DifficultyError
+{
+    NoParent,
+    TimestampTooOld { median_time_past: Timestamp, found: Timestamp },
+}
+
+impl std::fmt::Display for DifficultyError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            DifficultyError::NoParent => write!(f, "No parent header given!"),
+            DifficultyError::TimestampTooOld { median_time_past, found } =>
+                write!(f, "Timestamp {} is not after the median time past {}", found, median_time_past),
+        }
+    }
+}
+
+impl std::error::Error for DifficultyError {}
+
+// 'DifficultyAdjustment' defines how the difficulty of the next block is derived from its ancestors.
+pub trait DifficultyAdjustment
+{
+    // The 'required_ancestors' function returns how many ancestors the rule reads at most
+    fn required_ancestors(&self) -> usize;
+
+    // The 'median_span' function returns the number of ancestors the median time past is computed over
+    fn median_span(&self) -> usize;
+
+    // The 'next_difficulty' function returns the difficulty of the child of the last ancestor
+    fn next_difficulty(&self, ancestors: &[BlockHeader]) -> Result<U256, DifficultyError>;
+}
+
+// Difficulty adjustment config
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DifficultyConfig
+{
+    // The block time the difficulty is tuned towards, in seconds.
+    // 15 seconds by default.
+    pub target_block_time: u64,
+    // The number of blocks the difficulty and block time are averaged over.
+    // 60 blocks by default.
+    pub window: usize,
+    // The number of blocks the median time past is computed over.
+    // 11 blocks by default.
+    pub median_span: usize,
+    // Bounds the change per block to `parent_difficulty / max_change_divisor`.
+    // 8 by default.
+    pub max_change_divisor: u64,
+    // The lowest difficulty a block can have.
+    // 131072 by default.
+    #[serde(with = "core_utils::u256_bytes")]
+    pub minimum_difficulty: U256,
+}
+
+impl Default for DifficultyConfig
+{
+    fn default() -> Self
+    {
+        Self {
+            target_block_time: 15,
+            window: 60,
+            median_span: 11,
+            max_change_divisor: 8,
+            minimum_difficulty: U256::from(crate::pow::MINIMUM_DIFFICULTY),
+        }
+    }
+}
+
+// The 'median_time_past' function returns the median timestamp of the last `span` headers
+pub fn median_time_past(headers: &[BlockHeader], span: usize) -> Option<Timestamp>
+{
+    let start = headers.len().saturating_sub(span.max(1));
+    let mut timestamps: Vec<Timestamp> = headers[start..].iter().map(|header| *header.timestamp()).collect();
+    timestamps.sort();
+    timestamps.get(timestamps.len() / 2).copied()
+}
+
+// Adjusts the difficulty from the average difficulty and block time of a window of blocks
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowedAverage
+{
+    config: DifficultyConfig,
+}
+
+impl WindowedAverage
+{
+    // The 'new' function creates the adjustment from a config
+    pub fn new(config: DifficultyConfig) -> Self
+    {
+        WindowedAverage { config }
+    }
+
+    // The 'config' function returns the config of the adjustment
+    pub fn config(&self) -> &DifficultyConfig
+    {
+        &self.config
+    }
+
+    // The 'verify_timestamp' function checks that a timestamp is after the median time past of the ancestors
+    pub fn verify_timestamp(&self, ancestors: &[BlockHeader], timestamp: &Timestamp) -> Result<(), DifficultyError>
+    {
+        let median_time_past = median_time_past(ancestors, self.config.median_span).ok_or(DifficultyError::NoParent)?;
+        if timestamp <= &median_time_past
+        {
+            return Err(DifficultyError::TimestampTooOld { median_time_past, found: *timestamp });
+        }
+        Ok(())
+    }
+
+    // The 'bound' function limits the change from the parent difficulty and applies the minimum difficulty
+    fn bound(&self, parent: &U256, difficulty: U256) -> U256
+    {
+        let max_change = *parent / U256::from(self.config.max_change_divisor.max(1));
+        let lower = *parent - max_change;
+        let upper = parent.saturating_add(max_change);
+        difficulty.clamp(lower, upper).max(self.config.minimum_difficulty)
+    }
+}
+
+impl DifficultyAdjustment for WindowedAverage
+{
+    fn required_ancestors(&self) -> usize
+    {
+        self.config.window + self.config.median_span
+    }
+
+    fn median_span(&self) -> usize
+    {
+        self.config.median_span
+    }
+
+    fn next_difficulty(&self, ancestors: &[BlockHeader]) -> Result<U256, DifficultyError>
+    {
+        let parent = ancestors.last().ok_or(DifficultyError::NoParent)?;
+        let end = ancestors.len() - 1;
+        let start = end.saturating_sub(self.config.window.max(1));
+        let blocks = end - start;
+        if blocks == 0
+        {
+            return Ok(self.bound(parent.difficulty(), *parent.difficulty()));
+        }
+
+        let total = ancestors[start + 1..=end]
+            .iter()
+            .fold(U512::zero(), |total, header| total + U512::from(*header.difficulty()));
+        let average = total / U512::from(blocks);
+
+        let span = self.config.median_span;
+        let end_time = median_time_past(&ancestors[..=end], span).expect("Window is not empty");
+        let start_time = median_time_past(&ancestors[..=start], span).expect("Window is not empty");
+        let observed = end_time.saturating_duration_since(start_time).as_millis().max(1);
+        let expected = u128::from(blocks as u64) * u128::from(self.config.target_block_time) * 1_000;
+
+        let scaled = average * U512::from(expected.max(1)) / U512::from(observed);
+        let difficulty = U256::try_from(scaled).unwrap_or(U256::MAX);
+        Ok(self.bound(parent.difficulty(), difficulty))
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use primvites::block_header::BlockHeaderBuilder;
+    use std::time::Duration;
+
+    fn config() -> DifficultyConfig
+    {
+        DifficultyConfig {
+            target_block_time: 10,
+            window: 10,
+            median_span: 5,
+            max_change_divisor: 8,
+            minimum_difficulty: U256::from(100),
+        }
+    }
+
+    // Builds a chain of headers with a constant difficulty and the given gaps between timestamps
+    fn chain(difficulty: u64, gaps: &[u64]) -> Vec<BlockHeader>
+    {
+        let mut timestamp = Timestamp::from(1_700_000_000);
+        let mut headers = vec![header(0, difficulty, timestamp)];
+        for (index, gap) in gaps.iter().enumerate()
+        {
+            timestamp += Duration::from_secs(*gap);
+            headers.push(header(index as u64 + 1, difficulty, timestamp));
+        }
+        headers
+    }
+
+    fn header(number: u64, difficulty: u64, timestamp: Timestamp) -> BlockHeader
+    {
+        BlockHeaderBuilder::new()
+            .set_block_number(number)
+            .set_block_height(number)
+            .set_difficulty(U256::from(difficulty))
+            .set_timestamp(timestamp)
+            .seal()
+    }
+
+    #[test]
+    fn test_median_time_past()
+    {
+        let headers = chain(1_000, &[10, 10, 100, 1, 10]);
+        assert_eq!(median_time_past(&headers, 3), Some(Timestamp::from(1_700_000_121)));
+        assert_eq!(median_time_past(&headers[..1], 3), Some(Timestamp::from(1_700_000_000)));
+        assert_eq!(median_time_past(&[], 3), None);
+    }
+
+    #[test]
+    fn test_no_parent()
+    {
+        assert_eq!(WindowedAverage::new(config()).next_difficulty(&[]), Err(DifficultyError::NoParent));
+    }
+
+    #[test]
+    fn test_steady_block_time_keeps_difficulty()
+    {
+        let adjustment = WindowedAverage::new(config());
+        let headers = chain(1_000, &[10; 20]);
+        assert_eq!(adjustment.next_difficulty(&headers), Ok(U256::from(1_000)));
+        assert_eq!(adjustment.next_difficulty(&headers[..1]), Ok(U256::from(1_000)));
+    }
+
+    #[test]
+    fn test_adjusts_towards_target()
+    {
+        let adjustment = WindowedAverage::new(config());
+
+        // Blocks 10% faster than the target raise the difficulty.
+        let fast = adjustment.next_difficulty(&chain(1_000, &[9; 20])).unwrap();
+        assert_eq!(fast, U256::from(1_111));
+
+        // Blocks 10% slower than the target lower it.
+        let slow = adjustment.next_difficulty(&chain(1_000, &[11; 20])).unwrap();
+        assert_eq!(slow, U256::from(909));
+    }
+
+    #[test]
+    fn test_change_is_bounded()
+    {
+        let adjustment = WindowedAverage::new(config());
+        assert_eq!(adjustment.next_difficulty(&chain(1_000, &[1; 20])), Ok(U256::from(1_125)));
+        assert_eq!(adjustment.next_difficulty(&chain(1_000, &[100; 20])), Ok(U256::from(875)));
+    }
+
+    #[test]
+    fn test_minimum_difficulty()
+    {
+        let adjustment = WindowedAverage::new(config());
+        assert_eq!(adjustment.next_difficulty(&chain(101, &[100; 20])), Ok(U256::from(100)));
+        assert_eq!(adjustment.next_difficulty(&chain(1, &[10; 20])), Ok(U256::from(100)));
+    }
+
+    #[test]
+    fn test_outlier_timestamp_is_ignored()
+    {
+        let adjustment = WindowedAverage::new(config());
+        let mut gaps = [10; 20];
+        // A single block claims to be far in the future, followed by blocks at the usual pace.
+        gaps[18] = 10_000;
+        let mut headers = chain(1_000, &gaps);
+        let last = headers.len() - 1;
+        headers[last] = header(last as u64, 1_000, *headers[last - 2].timestamp() + Duration::from_secs(20));
+        assert_eq!(adjustment.next_difficulty(&headers), Ok(U256::from(1_000)));
+    }
+
+    #[test]
+    fn test_verify_timestamp()
+    {
+        let adjustment = WindowedAverage::new(config());
+        let headers = chain(1_000, &[10; 20]);
+        let median = median_time_past(&headers, 5).unwrap();
+
+        assert!(adjustment.verify_timestamp(&headers, &(median + Duration::from_millis(1))).is_ok());
+        assert_eq!(
+            adjustment.verify_timestamp(&headers, &median),
+            Err(DifficultyError::TimestampTooOld { median_time_past: median, found: median })
+        );
+        assert_eq!(adjustment.verify_timestamp(&[], &median), Err(DifficultyError::NoParent));
+    }
+}
@@ -0,0 +1,460 @@
+//! # Fork Choice
+//!
+//! This module tracks every known header as a tree rooted at the genesis header and follows the
+//! heaviest chain.
+//!
+//! The canonical head is the header with the highest total difficulty. Among headers of equal total
+//! difficulty, the one with the lowest hash wins, so every node picks the same head. Imported headers
+//! must extend a known header, with a block number one above their parent and a total difficulty equal
+//! to the parent total difficulty plus their own difficulty. Proof of work is not checked here.
+//!
+//! When the head moves to another branch, `TreeRoute` describes the reorg: the common ancestor, the
+//! blocks to revert from the old head down, and the blocks to apply up to the new head. Subscribers
+//! receive a `ChainEvent` for every reverted and applied block, followed by the new head.
+
+use crypto::hash::HashDigest;
+use primvites::{block_header::BlockHeader, BlockNumber, U256};
+use std::{cmp::Ordering, collections::HashMap, sync::mpsc::{channel, Receiver, Sender}};
+
+// Represents errors that can occur while importing headers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainError
+{
+    AlreadyKnown(HashDigest),
+    UnknownParent(HashDigest),
+    UnknownHeader(HashDigest),
+    InvalidHash,
+    InvalidBlockNumber { expected: BlockNumber, found: BlockNumber },
+    InvalidTotalDifficulty { expected: U256, found: U256 },
+}
+
+impl std::fmt::Display for ChainError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            ChainError::AlreadyKnown(hash) => write!(f, "Header already known: {}", hash),
+            ChainError::UnknownParent(hash) => write!(f, "Unknown parent: {}", hash),
+            ChainError::UnknownHeader(hash) => write!(f, "Unknown header: {}", hash),
+            ChainError::InvalidHash => write!(f, "Header hash does not match its contents!"),
+            ChainError::InvalidBlockNumber { expected, found } =>
+                write!(f, "Invalid block number: expected {}, found {}", expected, found),
+            ChainError::InvalidTotalDifficulty { expected, found } =>
+                write!(f, "Invalid total difficulty: expected {}, found {}", expected, found),
+        }
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+// Represents a change of the canonical chain.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainEvent
+{
+    // A block left the canonical chain
+    Reverted(BlockHeader),
+    // A block joined the canonical chain
+    Applied(BlockHeader),
+    // The canonical head changed
+    NewHead(BlockHeader),
+}
+
+// The path between two headers of the tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeRoute
+{
+    common_ancestor: HashDigest,
+    reverted: Vec<BlockHeader>,
+    applied: Vec<BlockHeader>,
+}
+
+impl TreeRoute
+{
+    // The 'common_ancestor' function returns the hash of the last header both ends share
+    pub fn common_ancestor(&self) -> &HashDigest { &self.common_ancestor }
+
+    // The 'reverted' function returns the headers to revert, starting from the old end
+    pub fn reverted(&self) -> &[BlockHeader] { &self.reverted }
+
+    // The 'applied' function returns the headers to apply, ending with the new end
+    pub fn applied(&self) -> &[BlockHeader] { &self.applied }
+
+    // The 'is_reorg' function checks whether any header is reverted
+    pub fn is_reorg(&self) -> bool { !self.reverted.is_empty() }
+}
+
+// Represents the effect of importing a header.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportOutcome
+{
+    // The header became the head by extending it, possibly through an already known branch
+    Extended(TreeRoute),
+    // The header became the head by replacing canonical blocks
+    Reorg(TreeRoute),
+    // The header was stored on a branch lighter than the canonical chain
+    SideChain,
+}
+
+// Tracks the header tree and its canonical chain
+pub struct Blockchain
+{
+    headers: HashMap<HashDigest, BlockHeader>,
+    children: HashMap<HashDigest, Vec<HashDigest>>,
+    // Canonical header hashes, indexed by block number from the genesis number
+    canonical: Vec<HashDigest>,
+    subscribers: Vec<Sender<ChainEvent>>,
+}
+
+impl Blockchain
+{
+    // The 'new' function creates a chain holding only the genesis header
+    pub fn new(genesis: BlockHeader) -> Self
+    {
+        let hash = *genesis.hash();
+        Blockchain {
+            headers: HashMap::from([(hash, genesis)]),
+            children: HashMap::new(),
+            canonical: vec![hash],
+            subscribers: Vec::new(),
+        }
+    }
+
+    // The 'genesis' function returns the genesis header
+    pub fn genesis(&self) -> &BlockHeader
+    {
+        &self.headers[&self.canonical[0]]
+    }
+
+    // The 'head' function returns the canonical head
+    pub fn head(&self) -> &BlockHeader
+    {
+        &self.headers[self.canonical.last().expect("Canonical chain holds the genesis")]
+    }
+
+    // The 'header' function returns a known header by hash
+    pub fn header(&self, hash: &HashDigest) -> Option<&BlockHeader>
+    {
+        self.headers.get(hash)
+    }
+
+    // The 'children' function returns the hashes of the known children of a header
+    pub fn children(&self, hash: &HashDigest) -> &[HashDigest]
+    {
+        self.children.get(hash).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    // The 'contains' function checks whether a header is known
+    pub fn contains(&self, hash: &HashDigest) -> bool
+    {
+        self.headers.contains_key(hash)
+    }
+
+    // The 'canonical_hash' function returns the hash of the canonical header with the given number
+    pub fn canonical_hash(&self, number: BlockNumber) -> Option<&HashDigest>
+    {
+        let index = number.checked_sub(self.genesis().block_number())?;
+        self.canonical.get(usize::try_from(index).ok()?)
+    }
+
+    // The 'is_canonical' function checks whether a known header is on the canonical chain
+    pub fn is_canonical(&self, hash: &HashDigest) -> bool
+    {
+        self.headers
+            .get(hash)
+            .and_then(|header| self.canonical_hash(header.block_number()))
+            .is_some_and(|canonical| canonical == hash)
+    }
+
+    // The 'subscribe' function returns a receiver of every following change of the canonical chain
+    pub fn subscribe(&mut self) -> Receiver<ChainEvent>
+    {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    // The 'import' function adds a header to the tree and moves the head if the header is heavier
+    pub fn import(&mut self, header: BlockHeader) -> Result<ImportOutcome, ChainError>
+    {
+        let hash = *header.hash();
+        if self.contains(&hash)
+        {
+            return Err(ChainError::AlreadyKnown(hash));
+        }
+        if !header.verify_hash()
+        {
+            return Err(ChainError::InvalidHash);
+        }
+
+        let parent = self.headers.get(header.parent_hash()).ok_or(ChainError::UnknownParent(*header.parent_hash()))?;
+        let expected_number = parent.block_number() + 1;
+        if header.block_number() != expected_number
+        {
+            return Err(ChainError::InvalidBlockNumber { expected: expected_number, found: header.block_number() });
+        }
+        let expected_difficulty = parent.total_difficulty().saturating_add(*header.difficulty());
+        if header.total_difficulty() != &expected_difficulty
+        {
+            return Err(ChainError::InvalidTotalDifficulty { expected: expected_difficulty, found: *header.total_difficulty() });
+        }
+
+        let heavier = Self::fork_choice(&header, self.head()) == Ordering::Greater;
+        self.children.entry(*header.parent_hash()).or_default().push(hash);
+        self.headers.insert(hash, header);
+        if !heavier
+        {
+            return Ok(ImportOutcome::SideChain);
+        }
+
+        let route = self.set_head(hash)?;
+        if route.is_reorg()
+        {
+            Ok(ImportOutcome::Reorg(route))
+        } else {
+            Ok(ImportOutcome::Extended(route))
+        }
+    }
+
+    // The 'fork_choice' function orders headers by total difficulty, then by lowest hash
+    pub fn fork_choice(a: &BlockHeader, b: &BlockHeader) -> Ordering
+    {
+        a.total_difficulty()
+            .cmp(b.total_difficulty())
+            .then_with(|| b.hash().as_ref().cmp(a.hash().as_ref()))
+    }
+
+    // The 'tree_route' function returns the path from one known header to another
+    pub fn tree_route(&self, from: &HashDigest, to: &HashDigest) -> Result<TreeRoute, ChainError>
+    {
+        let mut from = self.headers.get(from).ok_or(ChainError::UnknownHeader(*from))?;
+        let mut to = self.headers.get(to).ok_or(ChainError::UnknownHeader(*to))?;
+        let mut reverted = Vec::new();
+        let mut applied = Vec::new();
+
+        while from.hash() != to.hash()
+        {
+            if from.block_number() >= to.block_number()
+            {
+                reverted.push(from.clone());
+                from = self.parent(from)?;
+            } else {
+                applied.push(to.clone());
+                to = self.parent(to)?;
+            }
+        }
+        applied.reverse();
+
+        Ok(TreeRoute { common_ancestor: *from.hash(), reverted, applied })
+    }
+
+    // The 'parent' function returns the parent of a header that is not the genesis
+    fn parent(&self, header: &BlockHeader) -> Result<&BlockHeader, ChainError>
+    {
+        self.headers.get(header.parent_hash()).ok_or(ChainError::UnknownParent(*header.parent_hash()))
+    }
+
+    // The 'set_head' function makes a known header the canonical head and notifies subscribers
+    fn set_head(&mut self, hash: HashDigest) -> Result<TreeRoute, ChainError>
+    {
+        let head = *self.head().hash();
+        let route = self.tree_route(&head, &hash)?;
+
+        let ancestor_number = self.headers[route.common_ancestor()].block_number();
+        self.canonical.truncate((ancestor_number - self.genesis().block_number()) as usize + 1);
+        self.canonical.extend(route.applied().iter().map(|header| *header.hash()));
+
+        let events = route.reverted().iter().cloned().map(ChainEvent::Reverted)
+            .chain(route.applied().iter().cloned().map(ChainEvent::Applied))
+            .chain(std::iter::once(ChainEvent::NewHead(self.head().clone())));
+        for event in events
+        {
+            self.subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        }
+
+        Ok(route)
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use primvites::block_header::BlockHeaderBuilder;
+
+    fn genesis() -> BlockHeader
+    {
+        BlockHeaderBuilder::new()
+            .set_difficulty(U256::from(10))
+            .set_total_difficulty(U256::from(10))
+            .seal()
+    }
+
+    // Builds a child of the parent with the given difficulty, the salt distinguishes siblings
+    fn child(parent: &BlockHeader, difficulty: u64, salt: u64) -> BlockHeader
+    {
+        BlockHeaderBuilder::new()
+            .set_parent_hash(*parent.hash())
+            .set_block_number(parent.block_number() + 1)
+            .set_block_height(parent.block_height() + 1)
+            .set_difficulty(U256::from(difficulty))
+            .set_total_difficulty(*parent.total_difficulty() + difficulty)
+            .set_nonce(U256::from(salt))
+            .seal()
+    }
+
+    #[test]
+    fn test_extend_chain()
+    {
+        let genesis = genesis();
+        let mut chain = Blockchain::new(genesis.clone());
+        let events = chain.subscribe();
+
+        let block1 = child(&genesis, 10, 0);
+        let block2 = child(&block1, 10, 0);
+        assert!(matches!(chain.import(block1.clone()), Ok(ImportOutcome::Extended(_))));
+        assert!(matches!(chain.import(block2.clone()), Ok(ImportOutcome::Extended(_))));
+
+        assert_eq!(chain.head(), &block2);
+        assert_eq!(chain.canonical_hash(1), Some(block1.hash()));
+        assert_eq!(chain.canonical_hash(3), None);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                ChainEvent::Applied(block1.clone()),
+                ChainEvent::NewHead(block1),
+                ChainEvent::Applied(block2.clone()),
+                ChainEvent::NewHead(block2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_rejects_invalid_headers()
+    {
+        let genesis = genesis();
+        let mut chain = Blockchain::new(genesis.clone());
+        let block1 = child(&genesis, 10, 0);
+
+        assert_eq!(chain.import(genesis.clone()), Err(ChainError::AlreadyKnown(*genesis.hash())));
+        assert_eq!(chain.import(child(&block1, 10, 0)), Err(ChainError::UnknownParent(*block1.hash())));
+
+        let mut builder = BlockHeaderBuilder::new();
+        builder.set_parent_hash(*genesis.hash()).set_block_number(1);
+        assert_eq!(chain.import(builder.build()), Err(ChainError::InvalidHash));
+        assert_eq!(
+            chain.import(builder.set_block_number(2).seal()),
+            Err(ChainError::InvalidBlockNumber { expected: 1, found: 2 })
+        );
+        assert_eq!(
+            chain.import(builder.set_block_number(1).set_difficulty(U256::from(5)).seal()),
+            Err(ChainError::InvalidTotalDifficulty { expected: U256::from(15), found: U256::zero() })
+        );
+        assert_eq!(chain.head(), &genesis);
+    }
+
+    #[test]
+    fn test_side_chain_and_reorg()
+    {
+        let genesis = genesis();
+        let mut chain = Blockchain::new(genesis.clone());
+
+        let a1 = child(&genesis, 10, 0);
+        let a2 = child(&a1, 10, 0);
+        chain.import(a1.clone()).unwrap();
+        chain.import(a2.clone()).unwrap();
+
+        // A lighter branch is stored without moving the head.
+        let b1 = child(&genesis, 12, 1);
+        assert_eq!(chain.import(b1.clone()), Ok(ImportOutcome::SideChain));
+        assert_eq!(chain.head(), &a2);
+        assert!(chain.contains(b1.hash()));
+        assert!(!chain.is_canonical(b1.hash()));
+
+        // The branch becomes heavier and replaces the canonical blocks.
+        let events = chain.subscribe();
+        let b2 = child(&b1, 10, 1);
+        let route = match chain.import(b2.clone())
+        {
+            Ok(ImportOutcome::Reorg(route)) => route,
+            outcome => panic!("Expected a reorg, found {:?}", outcome),
+        };
+
+        assert_eq!(route.common_ancestor(), genesis.hash());
+        assert_eq!(route.reverted(), &[a2.clone(), a1.clone()]);
+        assert_eq!(route.applied(), &[b1.clone(), b2.clone()]);
+        assert_eq!(chain.head(), &b2);
+        assert!(chain.is_canonical(b1.hash()));
+        assert!(!chain.is_canonical(a1.hash()));
+        assert_eq!(chain.canonical_hash(1), Some(b1.hash()));
+        assert_eq!(chain.children(genesis.hash()), &[*a1.hash(), *b1.hash()]);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                ChainEvent::Reverted(a2),
+                ChainEvent::Reverted(a1),
+                ChainEvent::Applied(b1),
+                ChainEvent::Applied(b2.clone()),
+                ChainEvent::NewHead(b2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tie_break_on_lowest_hash()
+    {
+        let genesis = genesis();
+        let first = child(&genesis, 10, 0);
+        let second = child(&genesis, 10, 1);
+        let (low, high) = if first.hash().as_ref() < second.hash().as_ref() { (first, second) } else { (second, first) };
+
+        for order in [[&low, &high], [&high, &low]]
+        {
+            let mut chain = Blockchain::new(genesis.clone());
+            for header in order
+            {
+                chain.import(header.clone()).unwrap();
+            }
+            assert_eq!(chain.head(), &low);
+        }
+    }
+
+    #[test]
+    fn test_tree_route()
+    {
+        let genesis = genesis();
+        let mut chain = Blockchain::new(genesis.clone());
+        let a1 = child(&genesis, 10, 0);
+        let a2 = child(&a1, 10, 0);
+        let b2 = child(&a1, 5, 1);
+        for header in [&a1, &a2, &b2]
+        {
+            chain.import(header.clone()).unwrap();
+        }
+
+        let route = chain.tree_route(a2.hash(), b2.hash()).unwrap();
+        assert_eq!(route.common_ancestor(), a1.hash());
+        assert_eq!(route.reverted(), std::slice::from_ref(&a2));
+        assert_eq!(route.applied(), &[b2]);
+
+        let route = chain.tree_route(genesis.hash(), a2.hash()).unwrap();
+        assert!(!route.is_reorg());
+        assert_eq!(route.applied(), &[a1, a2]);
+
+        let unknown = HashDigest::from([1; HashDigest::LENGTH]);
+        assert_eq!(chain.tree_route(&unknown, genesis.hash()), Err(ChainError::UnknownHeader(unknown)));
+    }
+
+    #[test]
+    fn test_dropped_subscriber()
+    {
+        let genesis = genesis();
+        let mut chain = Blockchain::new(genesis.clone());
+        drop(chain.subscribe());
+        let events = chain.subscribe();
+
+        chain.import(child(&genesis, 10, 0)).unwrap();
+        assert_eq!(chain.subscribers.len(), 1);
+        assert_eq!(events.try_iter().count(), 2);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod pow;
+pub mod difficulty;
+pub mod fork_choice;
+pub mod validation;
+pub mod spec;
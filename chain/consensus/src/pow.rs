@@ -0,0 +1,343 @@
+//! # Proof of Work
+//!
+//! This module seals and verifies block headers with proof of work.
+//!
+//! The work of a header is its hash, computed over the header without nonce followed by the nonce,
+//! which is exactly the canonical encoding the header hash commits to. A header is valid when its
+//! hash, read as a big-endian 256 bit number, is at most the boundary `2^256 / difficulty`.
+//!
+//! `PowEngine` verifies sealed headers when importing blocks and enforces a minimum difficulty.
+//! `PowEngine::dev` accepts any non-zero difficulty, so that tests can mine blocks in milliseconds.
+//! `Miner` searches nonces on several threads until a valid one is found or the search is cancelled.
+//!
+//! ## Example
+//!
+//! ```
+//! use consensus::pow::{PowEngine, Miner};
+//! use primvites::{block_header::BlockHeaderBuilder, U256};
+//! use std::sync::atomic::AtomicBool;
+//!
+//! let header = BlockHeaderBuilder::new()
+//!     .set_block_number(1)
+//!     .set_difficulty(U256::from(16))
+//!     .seal();
+//!
+//! let sealed = Miner::new(2).mine(&header, &AtomicBool::new(false)).unwrap();
+//! assert!(PowEngine::dev().verify_header(&sealed).is_ok());
+//! ```
+
+use crypto::hash::{HashDigest, Hasher, Algorithm};
+use ethereum_types::U512;
+use primvites::{block_header::BlockHeader, U256};
+use std::{sync::atomic::{AtomicBool, Ordering}, thread};
+
+// The minimum difficulty accepted outside of dev mode
+pub const MINIMUM_DIFFICULTY: u64 = 131_072;
+
+// The minimum difficulty accepted in dev mode
+pub const DEV_MINIMUM_DIFFICULTY: u64 = 1;
+
+// Represents errors that can occur while sealing or verifying proof of work.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PowError
+{
+    ZeroDifficulty,
+    DifficultyTooLow { minimum: U256, found: U256 },
+    InvalidHash,
+    InsufficientWork { boundary: U256, found: U256 },
+    Cancelled,
+}
+
+impl std::fmt::Display for PowError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            PowError::ZeroDifficulty => write!(f, "Difficulty is zero!"),
+            PowError::DifficultyTooLow { minimum, found } =>
+                write!(f, "Difficulty too low: minimum {}, found {}", minimum, found),
+            PowError::InvalidHash => write!(f, "Header hash does not match its contents!"),
+            PowError::InsufficientWork { boundary, found } =>
+                write!(f, "Insufficient work: boundary {}, found {}", boundary, found),
+            PowError::Cancelled => write!(f, "Mining cancelled!"),
+        }
+    }
+}
+
+impl std::error::Error for PowError {}
+
+// The 'boundary' function returns the largest hash value accepted for a difficulty, `2^256 / difficulty`
+pub fn boundary(difficulty: &U256) -> Result<U256, PowError>
+{
+    if difficulty.is_zero()
+    {
+        return Err(PowError::ZeroDifficulty);
+    }
+    if difficulty == &U256::one()
+    {
+        // 2^256 does not fit, but every hash is at most `U256::MAX`.
+        return Ok(U256::MAX);
+    }
+
+    let quotient = (U512::one() << 256) / U512::from(*difficulty);
+    Ok(U256::try_from(quotient).expect("Quotient fits for a difficulty above one"))
+}
+
+// The 'work' function returns the hash of a header as the number compared against the boundary
+pub fn work(hash: &HashDigest) -> U256
+{
+    U256::from_big_endian(hash.as_ref())
+}
+
+// The 'meets_difficulty' function checks that a hash is within the boundary of a difficulty
+pub fn meets_difficulty(hash: &HashDigest, difficulty: &U256) -> Result<bool, PowError>
+{
+    Ok(work(hash) <= boundary(difficulty)?)
+}
+
+// The 'nonce_hash' function computes the hash of a header prefix followed by a nonce
+fn nonce_hash(prefix: &Hasher, nonce: &U256) -> HashDigest
+{
+    let mut nonce_bytes = [0u8; 32];
+    nonce.to_big_endian(&mut nonce_bytes);
+
+    let mut hasher = prefix.clone();
+    hasher.update(&nonce_bytes);
+    hasher.finalize()
+}
+
+// Verifies the proof of work of sealed headers
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowEngine
+{
+    minimum_difficulty: U256,
+}
+
+impl PowEngine
+{
+    // The 'new' function creates an engine rejecting headers below the given difficulty
+    pub fn new(minimum_difficulty: U256) -> Self
+    {
+        PowEngine { minimum_difficulty }
+    }
+
+    // The 'dev' function creates an engine accepting any non-zero difficulty
+    pub fn dev() -> Self
+    {
+        PowEngine::new(U256::from(DEV_MINIMUM_DIFFICULTY))
+    }
+
+    // The 'minimum_difficulty' function returns the lowest difficulty the engine accepts
+    pub fn minimum_difficulty(&self) -> &U256
+    {
+        &self.minimum_difficulty
+    }
+
+    // The 'verify_header' function checks the difficulty, hash and proof of work of a sealed header
+    pub fn verify_header(&self, header: &BlockHeader) -> Result<(), PowError>
+    {
+        let difficulty = header.difficulty();
+        if difficulty.is_zero()
+        {
+            return Err(PowError::ZeroDifficulty);
+        }
+        if difficulty < &self.minimum_difficulty
+        {
+            return Err(PowError::DifficultyTooLow { minimum: self.minimum_difficulty, found: *difficulty });
+        }
+        if !header.verify_hash()
+        {
+            return Err(PowError::InvalidHash);
+        }
+
+        let boundary = boundary(difficulty)?;
+        let found = work(header.hash());
+        if found > boundary
+        {
+            return Err(PowError::InsufficientWork { boundary, found });
+        }
+        Ok(())
+    }
+}
+
+impl Default for PowEngine
+{
+    fn default() -> Self
+    {
+        PowEngine::new(U256::from(MINIMUM_DIFFICULTY))
+    }
+}
+
+// Searches nonces satisfying the difficulty of a header on several threads
+#[derive(Debug, Clone, PartialEq)]
+pub struct Miner
+{
+    threads: usize,
+}
+
+impl Miner
+{
+    // The 'new' function creates a miner using the given number of threads, at least one
+    pub fn new(threads: usize) -> Self
+    {
+        Miner { threads: threads.max(1) }
+    }
+
+    // The 'threads' function returns the number of threads searching nonces
+    pub fn threads(&self) -> usize
+    {
+        self.threads
+    }
+
+    // The 'mine' function returns the header sealed with a valid nonce, searching from its current nonce.
+    // Setting `cancel` stops the search and returns `PowError::Cancelled`.
+    pub fn mine(&self, header: &BlockHeader, cancel: &AtomicBool) -> Result<BlockHeader, PowError>
+    {
+        let boundary = boundary(header.difficulty())?;
+        let mut prefix = Hasher::new(Algorithm::SHA256);
+        prefix.update(&header.encode_without_nonce());
+
+        let found = AtomicBool::new(false);
+        let start = *header.nonce();
+        let step = U256::from(self.threads);
+
+        let nonce = thread::scope(|scope| {
+            let searches: Vec<_> = (0..self.threads)
+                .map(|index| {
+                    let (prefix, found) = (&prefix, &found);
+                    scope.spawn(move || {
+                        let mut nonce = start.overflowing_add(U256::from(index)).0;
+                        while !cancel.load(Ordering::Relaxed) && !found.load(Ordering::Relaxed)
+                        {
+                            if work(&nonce_hash(prefix, &nonce)) <= boundary
+                            {
+                                found.store(true, Ordering::Relaxed);
+                                return Some(nonce);
+                            }
+                            nonce = nonce.overflowing_add(step).0;
+                        }
+                        None
+                    })
+                })
+                .collect();
+
+            searches
+                .into_iter()
+                .filter_map(|search| search.join().expect("Miner thread panicked"))
+                .next()
+        });
+
+        nonce.map(|nonce| header.with_nonce(nonce)).ok_or(PowError::Cancelled)
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use primvites::block_header::BlockHeaderBuilder;
+    use std::{sync::Arc, time::Duration};
+
+    fn header(difficulty: u64) -> BlockHeader
+    {
+        BlockHeaderBuilder::new()
+            .set_protocol_version(1)
+            .set_parent_hash(HashDigest::from([7; HashDigest::LENGTH]))
+            .set_block_number(1)
+            .set_block_height(1)
+            .set_difficulty(U256::from(difficulty))
+            .seal()
+    }
+
+    #[test]
+    fn test_boundary()
+    {
+        assert_eq!(boundary(&U256::zero()), Err(PowError::ZeroDifficulty));
+        assert_eq!(boundary(&U256::one()), Ok(U256::MAX));
+        assert_eq!(boundary(&U256::from(2)), Ok(U256::one() << 255));
+        assert_eq!(boundary(&U256::from(3)), Ok(U256::MAX / 3));
+        assert_eq!(boundary(&U256::MAX), Ok(U256::one()));
+    }
+
+    #[test]
+    fn test_nonce_hash_matches_header_hash()
+    {
+        let header = header(16).with_nonce(U256::from(99));
+        let mut prefix = Hasher::new(Algorithm::SHA256);
+        prefix.update(&header.encode_without_nonce());
+        assert_eq!(&nonce_hash(&prefix, header.nonce()), header.hash());
+    }
+
+    #[test]
+    fn test_mine_and_verify()
+    {
+        let cancel = AtomicBool::new(false);
+        for threads in [1, 4]
+        {
+            let sealed = Miner::new(threads).mine(&header(1_000), &cancel).unwrap();
+            assert!(PowEngine::dev().verify_header(&sealed).is_ok());
+            assert_eq!(meets_difficulty(sealed.hash(), sealed.difficulty()), Ok(true));
+        }
+    }
+
+    #[test]
+    fn test_verify_rejects_invalid_headers()
+    {
+        let engine = PowEngine::dev();
+        let sealed = Miner::new(2).mine(&header(1_000), &AtomicBool::new(false)).unwrap();
+
+        assert_eq!(engine.verify_header(&header(0)), Err(PowError::ZeroDifficulty));
+
+        let mut tampered = BlockHeaderBuilder::new();
+        tampered
+            .set_protocol_version(1)
+            .set_parent_hash(HashDigest::from([7; HashDigest::LENGTH]))
+            .set_block_number(1)
+            .set_block_height(1)
+            .set_difficulty(U256::from(1_000))
+            .set_nonce(*sealed.nonce() + 1)
+            .set_hash(*sealed.hash());
+        assert_eq!(engine.verify_header(&tampered.build()), Err(PowError::InvalidHash));
+
+        // Find a nonce whose hash misses the boundary.
+        let unsealed = (0u64..)
+            .map(|nonce| header(1_000).with_nonce(U256::from(nonce)))
+            .find(|header| meets_difficulty(header.hash(), header.difficulty()) == Ok(false))
+            .unwrap();
+        assert!(matches!(engine.verify_header(&unsealed), Err(PowError::InsufficientWork { .. })));
+    }
+
+    #[test]
+    fn test_verify_enforces_minimum_difficulty()
+    {
+        let sealed = Miner::new(1).mine(&header(16), &AtomicBool::new(false)).unwrap();
+        assert_eq!(
+            PowEngine::default().verify_header(&sealed),
+            Err(PowError::DifficultyTooLow { minimum: U256::from(MINIMUM_DIFFICULTY), found: U256::from(16) })
+        );
+        assert!(PowEngine::dev().verify_header(&sealed).is_ok());
+    }
+
+    #[test]
+    fn test_mine_cancellation()
+    {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let canceller = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                cancel.store(true, Ordering::Relaxed);
+            })
+        };
+
+        assert_eq!(Miner::new(2).mine(&header(u64::MAX), &cancel), Err(PowError::Cancelled));
+        canceller.join().unwrap();
+    }
+
+    #[test]
+    fn test_mine_zero_difficulty()
+    {
+        assert_eq!(Miner::new(1).mine(&header(0), &AtomicBool::new(false)), Err(PowError::ZeroDifficulty));
+    }
+}
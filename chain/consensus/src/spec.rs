@@ -0,0 +1,477 @@
+//! # Chain Specification
+//!
+//! This module defines `ChainSpec`, the JSON file describing how a Frenyum chain starts: its chain
+//! identifier, the genesis block parameters, the pre-funded accounts and the protocol parameters.
+//! Amounts, such as balances and the block reward, are written as decimal strings of base units,
+//! other numbers of 256 bits, such as difficulties, as `0x` prefixed hexadecimal strings, and
+//! addresses in their checksummed text encoding.
+//!
+//! The genesis block is built deterministically from the spec. Its header commits to the state of the
+//! pre-funded accounts, but headers do not commit to the chain identifier or the protocol parameters,
+//! so the genesis header also takes the spec hash, the digest of the canonical encoding of the spec
+//! with accounts sorted by address, as its parent hash. Nodes loading the same
+//! spec compute the same genesis hash, and any change to the spec changes it. The genesis base fee is
+//! the initial base fee of the fee market parameters.
+//!
+//! ## Example
+//!
+//! ```
+//! use consensus::spec::ChainSpec;
+//!
+//! let spec = ChainSpec::dev();
+//! let json = spec.to_json().unwrap();
+//! let genesis = ChainSpec::from_json(&json).unwrap().genesis().unwrap();
+//! assert_eq!(genesis.block().header().hash(), spec.genesis().unwrap().block().header().hash());
+//! ```
+
+use core_utils::{amount::Amount, gas::Gas, timestamp::Timestamp};
+use crypto::hash::{HashDigest, Algorithm, serialized_digest};
+use execution::{
+    fee_market::{FeeMarket, FeeMarketConfig},
+    gas_schedule::GasSchedule,
+    state::{MemoryState, State},
+};
+use primvites::{
+    Address, U256,
+    account::Account,
+    block::{Block, BlockBody},
+    block_header::BlockHeaderBuilder,
+    bloom::Bloom,
+    receipt::receipts_root,
+};
+use serde::{Serialize, Deserialize};
+use std::{collections::BTreeSet, path::Path};
+use txpool::pool::PoolConfig;
+use crate::{
+    difficulty::{DifficultyConfig, WindowedAverage},
+    pow::{PowEngine, DEV_MINIMUM_DIFFICULTY},
+    validation::ValidatorConfig,
+};
+
+// Represents errors that can occur while loading a chain spec or building its genesis.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpecError
+{
+    Io(String),
+    Json(String),
+    DuplicateAccount(Address),
+    ZeroDifficulty,
+    SerializationError,
+}
+
+impl std::fmt::Display for SpecError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            SpecError::Io(e) => write!(f, "Failed to read chain spec: {}", e),
+            SpecError::Json(e) => write!(f, "Invalid chain spec: {}", e),
+            SpecError::DuplicateAccount(address) => write!(f, "Duplicate genesis account: {}", address),
+            SpecError::ZeroDifficulty => write!(f, "Genesis difficulty is zero!"),
+            SpecError::SerializationError => write!(f, "Serialization failed!"),
+        }
+    }
+}
+
+impl std::error::Error for SpecError {}
+
+// Serializes addresses in their checksummed text encoding
+mod address_text
+{
+    use primvites::Address;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(address: &Address, serializer: S) -> Result<S::Ok, S::Error>
+    {
+        serializer.collect_str(address)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Address, D::Error>
+    {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(|e| D::Error::custom(format!("Invalid address {}: {}", text, e)))
+    }
+}
+
+// An account funded at genesis
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GenesisAccount
+{
+    #[serde(with = "address_text")]
+    pub address: Address,
+    pub balance: Amount,
+    #[serde(default, with = "core_utils::u256_bytes")]
+    pub nonce: U256,
+}
+
+// The parameters of the genesis block
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GenesisSpec
+{
+    // Genesis timestamp, in seconds since the Unix epoch
+    pub timestamp: u64,
+    // Difficulty of the genesis block
+    #[serde(with = "core_utils::u256_bytes")]
+    pub difficulty: U256,
+    // Gas limit of the genesis block
+    pub gas_limit: Gas,
+    // Protocol version of the genesis header
+    #[serde(default = "default_protocol_version")]
+    pub protocol_version: u32,
+    // Pre-funded accounts
+    #[serde(default)]
+    pub accounts: Vec<GenesisAccount>,
+}
+
+fn default_protocol_version() -> u32
+{
+    1
+}
+
+// Protocol parameters
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProtocolParams
+{
+    // The amount credited to the coinbase for every block.
+    // Zero by default.
+    pub block_reward: Amount,
+    // The number of seconds a timestamp can be ahead of the local clock.
+    // 15 seconds by default.
+    pub max_future_drift: u64,
+    // The difficulty adjustment rule.
+    pub difficulty: DifficultyConfig,
+    // The base fee adjustment rule, including the base fee of the genesis block.
+    pub fee_market: FeeMarketConfig,
+    // The gas costs of execution.
+    pub gas_schedule: GasSchedule,
+}
+
+impl Default for ProtocolParams
+{
+    fn default() -> Self
+    {
+        let validator = ValidatorConfig::default();
+        Self {
+            block_reward: Amount::from(validator.block_reward),
+            max_future_drift: validator.max_future_drift,
+            difficulty: DifficultyConfig::default(),
+            fee_market: validator.fee_market,
+            gas_schedule: validator.gas_schedule,
+        }
+    }
+}
+
+// Describes how a chain starts
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChainSpec
+{
+    // Human readable name of the chain
+    pub name: String,
+    // Chain identifier transactions must carry
+    pub chain_id: u32,
+    // Genesis block parameters
+    pub genesis: GenesisSpec,
+    // Protocol parameters
+    #[serde(default)]
+    pub params: ProtocolParams,
+}
+
+// The genesis block of a chain along with its initial state
+#[derive(Clone, Debug, PartialEq)]
+pub struct Genesis
+{
+    block: Block,
+    state: MemoryState,
+}
+
+impl Genesis
+{
+    // The 'block' function returns the genesis block
+    pub fn block(&self) -> &Block { &self.block }
+
+    // The 'state' function returns the state after the genesis block
+    pub fn state(&self) -> &MemoryState { &self.state }
+
+    // The 'into_parts' function returns the genesis block and state
+    pub fn into_parts(self) -> (Block, MemoryState) { (self.block, self.state) }
+}
+
+impl ChainSpec
+{
+    // The 'dev' function returns a spec for local development, with a difficulty low enough to mine
+    // blocks in milliseconds
+    pub fn dev() -> Self
+    {
+        ChainSpec {
+            name: "frenyum-dev".to_string(),
+            chain_id: 1337,
+            genesis: GenesisSpec {
+                timestamp: 0,
+                difficulty: U256::from(DEV_MINIMUM_DIFFICULTY),
+                gas_limit: Gas::new(30_000_000),
+                protocol_version: default_protocol_version(),
+                accounts: Vec::new(),
+            },
+            params: ProtocolParams {
+                difficulty: DifficultyConfig {
+                    minimum_difficulty: U256::from(DEV_MINIMUM_DIFFICULTY),
+                    ..DifficultyConfig::default()
+                },
+                ..ProtocolParams::default()
+            },
+        }
+    }
+
+    // The 'from_json' function parses a spec
+    pub fn from_json(json: &str) -> Result<ChainSpec, SpecError>
+    {
+        serde_json::from_str(json).map_err(|e| SpecError::Json(e.to_string()))
+    }
+
+    // The 'to_json' function writes the spec as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String, SpecError>
+    {
+        serde_json::to_string_pretty(self).map_err(|e| SpecError::Json(e.to_string()))
+    }
+
+    // The 'load' function reads a spec from a JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ChainSpec, SpecError>
+    {
+        let json = std::fs::read_to_string(path).map_err(|e| SpecError::Io(e.to_string()))?;
+        ChainSpec::from_json(&json)
+    }
+
+    // The 'canonical' function returns the spec with accounts sorted by address, rejecting duplicates
+    pub fn canonical(&self) -> Result<ChainSpec, SpecError>
+    {
+        let mut spec = self.clone();
+        spec.genesis.accounts.sort_by_key(|account| account.address);
+        let mut seen = BTreeSet::new();
+        for account in &spec.genesis.accounts
+        {
+            if !seen.insert(account.address)
+            {
+                return Err(SpecError::DuplicateAccount(account.address));
+            }
+        }
+        Ok(spec)
+    }
+
+    // The 'hash' function returns the digest of the canonical encoding of the spec
+    pub fn hash(&self) -> Result<HashDigest, SpecError>
+    {
+        serialized_digest(Algorithm::SHA256, &self.canonical()?).map_err(|_| SpecError::SerializationError)
+    }
+
+    // The 'genesis' function builds the genesis block and the initial state
+    pub fn genesis(&self) -> Result<Genesis, SpecError>
+    {
+        let spec = self.canonical()?;
+        let genesis = &spec.genesis;
+        if genesis.difficulty.is_zero()
+        {
+            return Err(SpecError::ZeroDifficulty);
+        }
+
+        let mut state = MemoryState::new();
+        for account in &genesis.accounts
+        {
+            state.set_account(account.address, Account::new(account.balance.base_units(), account.nonce, None));
+        }
+
+        let body = BlockBody::new(Vec::new());
+        let header = BlockHeaderBuilder::new()
+            .set_protocol_version(genesis.protocol_version)
+            .set_parent_hash(spec.hash()?)
+            .set_block_number(0)
+            .set_block_height(0)
+            .set_difficulty(genesis.difficulty)
+            .set_timestamp(Timestamp::from(genesis.timestamp))
+            .set_total_difficulty(genesis.difficulty)
+            .set_base_fee(spec.params.fee_market.initial_base_fee)
+            .set_gas_limit(genesis.gas_limit)
+            .set_state_root(state.state_root())
+            .set_transaction_root(body.compute_transaction_root())
+            .set_receipts_root(receipts_root(&[]).map_err(|_| SpecError::SerializationError)?)
+            .set_logs_bloom(Bloom::new())
+            .seal();
+
+        Ok(Genesis { block: Block::new(header, body), state })
+    }
+
+    // The 'pow_engine' function returns the proof of work engine of the chain
+    pub fn pow_engine(&self) -> PowEngine
+    {
+        PowEngine::new(self.params.difficulty.minimum_difficulty)
+    }
+
+    // The 'difficulty_adjustment' function returns the difficulty adjustment rule of the chain
+    pub fn difficulty_adjustment(&self) -> WindowedAverage
+    {
+        WindowedAverage::new(self.params.difficulty.clone())
+    }
+
+    // The 'fee_market' function returns the base fee adjustment rule of the chain
+    pub fn fee_market(&self) -> FeeMarket
+    {
+        FeeMarket::new(self.params.fee_market.clone())
+    }
+
+    // The 'validator_config' function returns the block validation config of the chain
+    pub fn validator_config(&self) -> ValidatorConfig
+    {
+        ValidatorConfig {
+            chain_id: self.chain_id,
+            max_future_drift: self.params.max_future_drift,
+            block_reward: self.params.block_reward.base_units(),
+            fee_market: self.params.fee_market.clone(),
+            gas_schedule: self.params.gas_schedule.clone(),
+        }
+    }
+
+    // The 'pool_config' function returns a transaction pool config admitting transactions of the chain
+    pub fn pool_config(&self) -> PoolConfig
+    {
+        PoolConfig { chain_id: self.chain_id, gas_schedule: self.params.gas_schedule.clone(), ..PoolConfig::default() }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    const SPEC: &str = r#"{
+        "name": "frenyum-testnet",
+        "chain_id": 7,
+        "genesis": {
+            "timestamp": 1700000000,
+            "difficulty": "0x3e8",
+            "gas_limit": 8000000,
+            "accounts": [
+                { "address": "ADDRESS_B", "balance": "100" },
+                { "address": "ADDRESS_A", "balance": "1000000000000000000", "nonce": "0x2" }
+            ]
+        },
+        "params": {
+            "block_reward": "5000",
+            "difficulty": { "target_block_time": 10 },
+            "fee_market": { "initial_base_fee": 1000 },
+            "gas_schedule": { "data_byte": 8 }
+        }
+    }"#;
+
+    fn spec_json() -> String
+    {
+        SPEC.replace("ADDRESS_A", &Address::from([1; 20]).to_string())
+            .replace("ADDRESS_B", &Address::from([2; 20]).to_string())
+    }
+
+    #[test]
+    fn test_parse_spec()
+    {
+        let spec = ChainSpec::from_json(&spec_json()).unwrap();
+        assert_eq!(spec.chain_id, 7);
+        assert_eq!(spec.genesis.difficulty, U256::from(1_000));
+        assert_eq!(spec.genesis.gas_limit, Gas::new(8_000_000));
+        assert_eq!(spec.genesis.protocol_version, 1);
+        assert_eq!(spec.genesis.accounts[1].nonce, U256::from(2));
+        assert_eq!(spec.genesis.accounts[0].nonce, U256::zero());
+        assert_eq!(spec.params.block_reward, Amount::from_base(5_000u64));
+        assert_eq!(spec.params.difficulty.target_block_time, 10);
+        assert_eq!(spec.params.difficulty.window, DifficultyConfig::default().window);
+        assert_eq!(spec.params.max_future_drift, 15);
+        assert_eq!(spec.params.fee_market.initial_base_fee, Gas::new(1_000));
+        assert_eq!(spec.params.fee_market.change_denominator, FeeMarketConfig::default().change_denominator);
+        assert_eq!(spec.params.gas_schedule.data_byte, Gas::new(8));
+        assert_eq!(spec.validator_config().gas_schedule, spec.params.gas_schedule);
+
+        assert_eq!(ChainSpec::from_json(&spec.to_json().unwrap()), Ok(spec));
+        assert!(matches!(ChainSpec::from_json(&SPEC.replace("ADDRESS_A", "fr00")), Err(SpecError::Json(_))));
+    }
+
+    #[test]
+    fn test_genesis()
+    {
+        let spec = ChainSpec::from_json(&spec_json()).unwrap();
+        let genesis = spec.genesis().unwrap();
+        let header = genesis.block().header();
+
+        assert!(header.verify_hash());
+        assert_eq!(header.parent_hash(), &spec.hash().unwrap());
+        assert_eq!(header.block_number(), 0);
+        assert_eq!(header.difficulty(), &U256::from(1_000));
+        assert_eq!(header.total_difficulty(), &U256::from(1_000));
+        assert_eq!(header.timestamp(), &Timestamp::from(1_700_000_000));
+        assert_eq!(header.base_fee(), &Gas::new(1_000));
+        assert!(genesis.block().verify_transaction_root());
+        assert_eq!(header.gas_limit(), &Gas::new(8_000_000));
+        assert_eq!(header.gas_used(), &Gas::new(0));
+
+        let state = genesis.state();
+        assert_eq!(header.state_root(), &state.state_root());
+        assert_eq!(state.balance(&Address::from([1; 20])), U256::from(1_000_000_000_000_000_000u64));
+        assert_eq!(state.nonce(&Address::from([1; 20])), U256::from(2));
+        assert_eq!(state.balance(&Address::from([2; 20])), U256::from(100));
+    }
+
+    #[test]
+    fn test_genesis_hash_is_deterministic()
+    {
+        let spec = ChainSpec::from_json(&spec_json()).unwrap();
+        let hash = *spec.genesis().unwrap().block().header().hash();
+
+        // The order of the accounts in the file does not matter.
+        let mut reordered = spec.clone();
+        reordered.genesis.accounts.reverse();
+        assert_eq!(reordered.genesis().unwrap().block().header().hash(), &hash);
+
+        // Any other change does.
+        let mut other_chain = spec.clone();
+        other_chain.chain_id = 8;
+        let mut other_balance = spec.clone();
+        other_balance.genesis.accounts[0].balance = Amount::from_base(101u64);
+        let mut other_params = spec.clone();
+        other_params.params.block_reward = Amount::ZERO;
+        for other in [other_chain, other_balance, other_params]
+        {
+            assert_ne!(other.genesis().unwrap().block().header().hash(), &hash);
+        }
+    }
+
+    #[test]
+    fn test_invalid_genesis()
+    {
+        let mut spec = ChainSpec::from_json(&spec_json()).unwrap();
+        let duplicate = spec.genesis.accounts[0].clone();
+        spec.genesis.accounts.push(duplicate.clone());
+        assert_eq!(spec.genesis(), Err(SpecError::DuplicateAccount(duplicate.address)));
+
+        let mut spec = ChainSpec::dev();
+        spec.genesis.difficulty = U256::zero();
+        assert_eq!(spec.genesis(), Err(SpecError::ZeroDifficulty));
+    }
+
+    #[test]
+    fn test_load_spec()
+    {
+        let path = std::env::temp_dir().join(format!("frenyum-spec-{}.json", std::process::id()));
+        std::fs::write(&path, spec_json()).unwrap();
+        assert_eq!(ChainSpec::load(&path), ChainSpec::from_json(&spec_json()));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(ChainSpec::load(&path), Err(SpecError::Io(_))));
+    }
+
+    #[test]
+    fn test_dev_spec()
+    {
+        let spec = ChainSpec::dev();
+        assert_eq!(spec.pow_engine(), PowEngine::dev());
+        assert_eq!(spec.validator_config().chain_id, 1337);
+        assert_eq!(spec.pool_config().chain_id, 1337);
+        assert!(spec.genesis().is_ok());
+    }
+}
@@ -0,0 +1,758 @@
+//! # Block Validation
+//!
+//! This module checks a `Block` before it is imported, in four stages:
+//!
+//! - the header: linkage to its parent, timestamp bounds, difficulty per the adjustment rule, total
+//!   difficulty, gas limit within the bounds set by the parent, and proof of work;
+//! - the base fee: adjusted from the base fee and gas used of the parent block per the fee market;
+//! - the body: transaction root, gas used of the header against its gas limit, and the signature
+//!   and chain identifier of every transaction;
+//! - the execution: the transactions are applied under the base fee to a copy of the state, which
+//!   must accept every one of them and reproduce the gas used, receipts root, logs bloom and state
+//!   root of the block. The fees and the block reward go to the coinbase recorded in the header.
+//!
+//! A timestamp must be after the median time past of the ancestors, hence after old blocks, and at
+//! most `max_future_drift` seconds ahead of the local clock. The local clock is the system clock unless
+//! another `Clock` is set with `with_clock`.
+//!
+//! Every failure maps to a distinct `BlockError` variant, so that peers sending bad blocks can be
+//! penalized precisely. The state is only updated once the whole block is valid.
+
+use core_utils::{gas::{Gas, GasMeter}, timestamp::{Clock, SystemClock, Timestamp}};
+use crypto::hash::HashDigest;
+use execution::{
+    executor::{Executor, ExecutionError},
+    fee_market::{FeeMarket, FeeMarketConfig},
+    gas_limit::gas_limit_bounds,
+    gas_schedule::GasSchedule,
+    state::State,
+};
+use primvites::{
+    BlockHeight, BlockNumber, U256,
+    block::Block,
+    block_header::BlockHeader,
+    bloom::Bloom,
+    receipt::{Receipt, receipts_root},
+    transaction::TransactionError,
+};
+use serde::{Serialize, Deserialize};
+use std::{sync::Arc, time::Duration};
+use crate::{
+    difficulty::{DifficultyAdjustment, DifficultyError, WindowedAverage, median_time_past},
+    pow::{PowEngine, PowError},
+};
+
+// Represents the reasons a block is invalid.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockError
+{
+    MissingParent,
+    ParentHashMismatch { expected: HashDigest, found: HashDigest },
+    InvalidBlockNumber { expected: BlockNumber, found: BlockNumber },
+    InvalidBlockHeight { expected: BlockHeight, found: BlockHeight },
+    TimestampTooOld { median_time_past: Timestamp, found: Timestamp },
+    TimestampInFuture { max: Timestamp, found: Timestamp },
+    InvalidDifficulty { expected: U256, found: U256 },
+    InvalidTotalDifficulty { expected: U256, found: U256 },
+    InvalidGasLimit { min: Gas, max: Gas, found: Gas },
+    InvalidSeal(PowError),
+    InvalidBaseFee { expected: Gas, found: Gas },
+    TransactionRootMismatch,
+    GasUsedExceedsLimit { gas_used: Gas, gas_limit: Gas },
+    InvalidTransaction { index: usize, error: TransactionError },
+    WrongChainId { index: usize, expected: u32, found: u32 },
+    TransactionRejected { index: usize, error: ExecutionError },
+    GasUsedMismatch { expected: Gas, found: Gas },
+    RewardOverflow,
+    ReceiptsRootMismatch,
+    LogsBloomMismatch,
+    StateRootMismatch { expected: HashDigest, found: HashDigest },
+}
+
+impl std::fmt::Display for BlockError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            BlockError::MissingParent => write!(f, "No parent header given!"),
+            BlockError::ParentHashMismatch { expected, found } =>
+                write!(f, "Invalid parent hash: expected {}, found {}", expected, found),
+            BlockError::InvalidBlockNumber { expected, found } =>
+                write!(f, "Invalid block number: expected {}, found {}", expected, found),
+            BlockError::InvalidBlockHeight { expected, found } =>
+                write!(f, "Invalid block height: expected {}, found {}", expected, found),
+            BlockError::TimestampTooOld { median_time_past, found } =>
+                write!(f, "Timestamp {} is not after the median time past {}", found, median_time_past),
+            BlockError::TimestampInFuture { max, found } =>
+                write!(f, "Timestamp {} is after the allowed {}", found, max),
+            BlockError::InvalidDifficulty { expected, found } =>
+                write!(f, "Invalid difficulty: expected {}, found {}", expected, found),
+            BlockError::InvalidTotalDifficulty { expected, found } =>
+                write!(f, "Invalid total difficulty: expected {}, found {}", expected, found),
+            BlockError::InvalidGasLimit { min, max, found } =>
+                write!(f, "Gas limit {} is outside of the bounds {} to {}", found, min, max),
+            BlockError::InvalidSeal(e) => write!(f, "Invalid seal: {}", e),
+            BlockError::InvalidBaseFee { expected, found } =>
+                write!(f, "Invalid base fee: expected {}, found {}", expected, found),
+            BlockError::TransactionRootMismatch => write!(f, "Transaction root does not match the body!"),
+            BlockError::GasUsedExceedsLimit { gas_used, gas_limit } =>
+                write!(f, "Gas used {} exceeds the gas limit {}", gas_used, gas_limit),
+            BlockError::InvalidTransaction { index, error } =>
+                write!(f, "Invalid transaction {}: {}", index, error),
+            BlockError::WrongChainId { index, expected, found } =>
+                write!(f, "Invalid chain id of transaction {}: expected {}, found {}", index, expected, found),
+            BlockError::TransactionRejected { index, error } =>
+                write!(f, "Transaction {} rejected: {}", index, error),
+            BlockError::GasUsedMismatch { expected, found } =>
+                write!(f, "Invalid gas used: expected {}, found {}", expected, found),
+            BlockError::RewardOverflow => write!(f, "The block reward overflows the coinbase balance!"),
+            BlockError::ReceiptsRootMismatch => write!(f, "Receipts root does not match the execution!"),
+            BlockError::LogsBloomMismatch => write!(f, "Logs bloom does not match the execution!"),
+            BlockError::StateRootMismatch { expected, found } =>
+                write!(f, "Invalid state root: expected {}, found {}", expected, found),
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+// Block validation config
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValidatorConfig
+{
+    // The chain identifier transactions must carry.
+    // 1 by default.
+    pub chain_id: u32,
+    // The number of seconds a timestamp can be ahead of the local clock.
+    // 15 seconds by default.
+    pub max_future_drift: u64,
+    // The amount credited to the coinbase for every block.
+    // Zero by default.
+    #[serde(with = "core_utils::u256_bytes")]
+    pub block_reward: U256,
+    // The rule adjusting the base fee between blocks.
+    // The default fee market by default.
+    pub fee_market: FeeMarketConfig,
+    // The gas costs of execution.
+    // The default schedule by default.
+    pub gas_schedule: GasSchedule,
+}
+
+impl Default for ValidatorConfig
+{
+    fn default() -> Self
+    {
+        Self {
+            chain_id: 1,
+            max_future_drift: 15,
+            block_reward: U256::zero(),
+            fee_market: FeeMarketConfig::default(),
+            gas_schedule: GasSchedule::default(),
+        }
+    }
+}
+
+// Checks blocks against their ancestors and the state of their parent
+pub struct BlockValidator<A: DifficultyAdjustment = WindowedAverage>
+{
+    config: ValidatorConfig,
+    engine: PowEngine,
+    adjustment: A,
+    fee_market: FeeMarket,
+    clock: Arc<dyn Clock>,
+}
+
+impl<A: DifficultyAdjustment> BlockValidator<A>
+{
+    // The 'new' function creates a validator from a config, a proof of work engine and a difficulty rule
+    pub fn new(config: ValidatorConfig, engine: PowEngine, adjustment: A) -> Self
+    {
+        let fee_market = FeeMarket::new(config.fee_market.clone());
+        BlockValidator { config, engine, adjustment, fee_market, clock: Arc::new(SystemClock) }
+    }
+
+    // The 'with_clock' function sets the local clock timestamps are checked against
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self
+    {
+        self.clock = clock;
+        self
+    }
+
+    // The 'config' function returns the config of the validator
+    pub fn config(&self) -> &ValidatorConfig
+    {
+        &self.config
+    }
+
+    // The 'validate' function checks a block and applies it to the state of its parent.
+    // `ancestors` are passed oldest first and end with the parent.
+    pub fn validate<S: State + Clone>(
+        &self,
+        block: &Block,
+        ancestors: &[BlockHeader],
+        state: &mut S,
+    ) -> Result<Vec<Receipt>, BlockError> {
+        self.validate_header(block.header(), ancestors, self.clock.now())?;
+        let parent = ancestors.last().ok_or(BlockError::MissingParent)?;
+        self.validate_base_fee(block.header(), parent)?;
+        self.validate_body(block)?;
+        self.validate_execution(block, state)
+    }
+
+    // The 'validate_header' function checks a header against its ancestors and the local clock
+    pub fn validate_header(&self, header: &BlockHeader, ancestors: &[BlockHeader], now: Timestamp) -> Result<(), BlockError>
+    {
+        let parent = ancestors.last().ok_or(BlockError::MissingParent)?;
+        if header.parent_hash() != parent.hash()
+        {
+            return Err(BlockError::ParentHashMismatch { expected: *parent.hash(), found: *header.parent_hash() });
+        }
+        let expected_number = parent.block_number() + 1;
+        if header.block_number() != expected_number
+        {
+            return Err(BlockError::InvalidBlockNumber { expected: expected_number, found: header.block_number() });
+        }
+        let expected_height = parent.block_height() + 1;
+        if header.block_height() != expected_height
+        {
+            return Err(BlockError::InvalidBlockHeight { expected: expected_height, found: header.block_height() });
+        }
+
+        let median_time_past = median_time_past(ancestors, self.adjustment.median_span()).ok_or(BlockError::MissingParent)?;
+        if header.timestamp() <= &median_time_past
+        {
+            return Err(BlockError::TimestampTooOld { median_time_past, found: *header.timestamp() });
+        }
+        let max = now.saturating_add(Duration::from_secs(self.config.max_future_drift));
+        if header.timestamp() > &max
+        {
+            return Err(BlockError::TimestampInFuture { max, found: *header.timestamp() });
+        }
+
+        let expected_difficulty = self.adjustment.next_difficulty(ancestors).map_err(|e| match e
+        {
+            DifficultyError::NoParent => BlockError::MissingParent,
+            DifficultyError::TimestampTooOld { median_time_past, found } => BlockError::TimestampTooOld { median_time_past, found },
+        })?;
+        if header.difficulty() != &expected_difficulty
+        {
+            return Err(BlockError::InvalidDifficulty { expected: expected_difficulty, found: *header.difficulty() });
+        }
+        let expected_total = parent.total_difficulty().saturating_add(*header.difficulty());
+        if header.total_difficulty() != &expected_total
+        {
+            return Err(BlockError::InvalidTotalDifficulty { expected: expected_total, found: *header.total_difficulty() });
+        }
+        let (min, max) = gas_limit_bounds(*parent.gas_limit());
+        if header.gas_limit() < &min || header.gas_limit() > &max
+        {
+            return Err(BlockError::InvalidGasLimit { min, max, found: *header.gas_limit() });
+        }
+
+        self.engine.verify_header(header).map_err(BlockError::InvalidSeal)
+    }
+
+    // The 'validate_base_fee' function checks the base fee of a header against its parent header
+    pub fn validate_base_fee(&self, header: &BlockHeader, parent: &BlockHeader) -> Result<(), BlockError>
+    {
+        let expected = self.fee_market.next_base_fee(parent);
+        if header.base_fee() != &expected
+        {
+            return Err(BlockError::InvalidBaseFee { expected, found: *header.base_fee() });
+        }
+        Ok(())
+    }
+
+    // The 'validate_body' function checks that the body matches its header and its transactions are signed
+    pub fn validate_body(&self, block: &Block) -> Result<(), BlockError>
+    {
+        if !block.verify_transaction_root()
+        {
+            return Err(BlockError::TransactionRootMismatch);
+        }
+        let header = block.header();
+        if header.gas_used() > header.gas_limit()
+        {
+            return Err(BlockError::GasUsedExceedsLimit { gas_used: *header.gas_used(), gas_limit: *header.gas_limit() });
+        }
+
+        for (index, transaction) in block.body().transaction().iter().enumerate()
+        {
+            transaction.verify_for_chain(self.config.chain_id).map_err(|error| match error
+            {
+                TransactionError::WrongChainId { expected, found } => BlockError::WrongChainId { index, expected, found },
+                error => BlockError::InvalidTransaction { index, error },
+            })?;
+        }
+        Ok(())
+    }
+
+    // The 'validate_execution' function applies the transactions under the base fee of the block and the
+    // block reward to a copy of the state, checks the result against the block and commits it to the state
+    pub fn validate_execution<S: State + Clone>(
+        &self,
+        block: &Block,
+        state: &mut S,
+    ) -> Result<Vec<Receipt>, BlockError> {
+        let coinbase = *block.header().coinbase();
+        let executor = Executor::new(coinbase)
+            .with_base_fee(*block.header().base_fee())
+            .with_schedule(self.config.gas_schedule.clone());
+        let mut post_state = state.clone();
+        let mut meter = GasMeter::new(*block.header().gas_limit());
+        let mut receipts = Vec::new();
+
+        for (index, transaction) in block.body().transaction().iter().enumerate()
+        {
+            let outcome = executor
+                .execute(&mut post_state, transaction)
+                .map_err(|error| BlockError::TransactionRejected { index, error })?;
+            meter.charge(*outcome.gas_used()).map_err(|e| BlockError::GasUsedExceedsLimit {
+                gas_used: e.used.saturating_add(e.cost),
+                gas_limit: e.limit,
+            })?;
+            receipts.push(Receipt::from_outcome(outcome, meter.used()));
+        }
+
+        let mut beneficiary = post_state.account_or_default(&coinbase);
+        let balance = beneficiary.balance().checked_add(self.config.block_reward).ok_or(BlockError::RewardOverflow)?;
+        beneficiary.set_balance(balance);
+        post_state.set_account(coinbase, beneficiary);
+
+        let header = block.header();
+        if header.gas_used() != &meter.used()
+        {
+            return Err(BlockError::GasUsedMismatch { expected: meter.used(), found: *header.gas_used() });
+        }
+        if receipts_root(&receipts).ok().as_ref() != Some(header.receipts_root())
+        {
+            return Err(BlockError::ReceiptsRootMismatch);
+        }
+        if &Bloom::from_receipts(&receipts) != header.logs_bloom()
+        {
+            return Err(BlockError::LogsBloomMismatch);
+        }
+        let state_root = post_state.state_root();
+        if &state_root != header.state_root()
+        {
+            return Err(BlockError::StateRootMismatch { expected: state_root, found: *header.state_root() });
+        }
+
+        *state = post_state;
+        Ok(receipts)
+    }
+
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::{difficulty::DifficultyConfig, pow::Miner};
+    use core_utils::timestamp::MockClock;
+    use crypto::ed25519::PrivateKey;
+    use execution::{
+        gas_schedule::TRANSFER_GAS,
+        producer::{BlockProducer, ProducerConfig},
+        state::MemoryState,
+    };
+    use primvites::{
+        Address,
+        account::Account,
+        block::BlockBody,
+        block_header::BlockHeaderBuilder,
+        transaction::{Action, RawTransaction, SignedTransaction, TransferAction},
+        Bytes,
+    };
+    use rand::rngs::OsRng;
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    const COINBASE: [u8; 20] = [0xCB; 20];
+    const GENESIS_TIME: u64 = 1_700_000_000;
+
+    struct Fixture
+    {
+        validator: BlockValidator,
+        genesis: BlockHeader,
+        state: MemoryState,
+        private_key: PrivateKey,
+    }
+
+    impl Fixture
+    {
+        fn new() -> Self
+        {
+            let config = ValidatorConfig { block_reward: U256::from(5_000), ..ValidatorConfig::default() };
+            let adjustment = WindowedAverage::new(DifficultyConfig { minimum_difficulty: U256::one(), ..DifficultyConfig::default() });
+            let genesis = BlockHeaderBuilder::new()
+                .set_difficulty(U256::from(1_000))
+                .set_total_difficulty(U256::from(1_000))
+                .set_timestamp(Timestamp::from(GENESIS_TIME))
+                .set_base_fee(Gas::new(8))
+                .set_gas_limit(Gas::new(30_000_000))
+                .seal();
+
+            let mut csprng: OsRng = OsRng;
+            let private_key = PrivateKey::generate(&mut csprng);
+            let mut state = MemoryState::new();
+            state.set_account(private_key.to_public_key().to_address(), Account::with_balance(U256::from(1_000_000)));
+
+            let validator = BlockValidator::new(config, PowEngine::dev(), adjustment);
+            Fixture { validator, genesis, state, private_key }
+        }
+
+        fn transfer(&self, chain_id: u32, nonce: u64) -> Arc<SignedTransaction>
+        {
+            let raw = RawTransaction::new(
+                chain_id,
+                U256::from(nonce),
+                Action::Transfer(TransferAction { to: Address::from([7; 20]), amount: U256::from(10) }),
+                Gas::from(10),
+                Gas::from(TRANSFER_GAS),
+                U256::from(10),
+                Bytes::new(),
+            );
+            Arc::new(raw.sign(&self.private_key))
+        }
+
+        // Produces and mines a valid child of the genesis block, whose base fee is 7
+        fn block(&self, transactions: Vec<Arc<SignedTransaction>>) -> Block
+        {
+            self.block_for(Address::from(COINBASE), transactions)
+        }
+
+        // Produces and mines a valid child of the genesis block for the given coinbase
+        fn block_for(&self, coinbase: Address, transactions: Vec<Arc<SignedTransaction>>) -> Block
+        {
+            let producer = BlockProducer::new(ProducerConfig {
+                coinbase,
+                block_reward: U256::from(5_000),
+                fee_market: self.validator.config.fee_market.clone(),
+                ..ProducerConfig::default()
+            });
+            let mut state = self.state.clone();
+            let block = producer
+                .produce(&self.genesis, U256::from(1_000), Timestamp::from(GENESIS_TIME + 15), &transactions, &mut state)
+                .unwrap()
+                .into_block();
+            Block::new(mine(block.header()), block.body().clone())
+        }
+
+        fn validate(&mut self, block: &Block) -> Result<Vec<Receipt>, BlockError>
+        {
+            let ancestors = [self.genesis.clone()];
+            self.validator.validate_header(block.header(), &ancestors, Timestamp::from(GENESIS_TIME + 15))?;
+            self.validator.validate_base_fee(block.header(), &self.genesis)?;
+            self.validator.validate_body(block)?;
+            self.validator.validate_execution(block, &mut self.state)
+        }
+    }
+
+    fn mine(header: &BlockHeader) -> BlockHeader
+    {
+        Miner::new(2).mine(header, &AtomicBool::new(false)).unwrap()
+    }
+
+    // Builds and mines a child of the genesis header, letting the test change the builder
+    fn header_with(fixture: &Fixture, change: impl FnOnce(&mut BlockHeaderBuilder)) -> BlockHeader
+    {
+        let block = fixture.block(Vec::new());
+        let header = block.header();
+        let mut builder = BlockHeaderBuilder::new();
+        builder
+            .set_parent_hash(*header.parent_hash())
+            .set_block_number(header.block_number())
+            .set_block_height(header.block_height())
+            .set_difficulty(*header.difficulty())
+            .set_timestamp(*header.timestamp())
+            .set_total_difficulty(*header.total_difficulty())
+            .set_base_fee(*header.base_fee())
+            .set_gas_limit(*header.gas_limit())
+            .set_gas_used(*header.gas_used())
+            .set_coinbase(*header.coinbase())
+            .set_state_root(*header.state_root())
+            .set_protocol_version(header.protocol_version())
+            .set_transaction_root(*header.transaction_root())
+            .set_receipts_root(*header.receipts_root())
+            .set_logs_bloom(*header.logs_bloom());
+        change(&mut builder);
+        mine(&builder.seal())
+    }
+
+    #[test]
+    fn test_valid_block()
+    {
+        let mut fixture = Fixture::new();
+        let block = fixture.block(vec![fixture.transfer(1, 0), fixture.transfer(1, 1)]);
+
+        let receipts = fixture.validate(&block).unwrap();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(fixture.state.nonce(&fixture.private_key.to_public_key().to_address()), U256::from(2));
+        // The coinbase receives the tips above the base fee of 7.
+        assert_eq!(fixture.state.balance(&Address::from(COINBASE)), U256::from(2 * 3 * TRANSFER_GAS + 5_000));
+    }
+
+    #[test]
+    fn test_validate_reads_clock()
+    {
+        let mut fixture = Fixture::new();
+        let block = fixture.block(Vec::new());
+        let clock = MockClock::new(Timestamp::from(GENESIS_TIME - 1));
+        let validator = BlockValidator::new(fixture.validator.config().clone(), PowEngine::dev(), fixture.validator.adjustment.clone())
+            .with_clock(Arc::new(clock.clone()));
+        let ancestors = [fixture.genesis.clone()];
+
+        assert_eq!(
+            validator.validate(&block, &ancestors, &mut fixture.state),
+            Err(BlockError::TimestampInFuture { max: Timestamp::from(GENESIS_TIME + 14), found: Timestamp::from(GENESIS_TIME + 15) })
+        );
+        clock.advance(Duration::from_secs(1));
+        validator.validate(&block, &ancestors, &mut fixture.state).unwrap();
+    }
+
+    #[test]
+    fn test_block_pays_header_coinbase()
+    {
+        let mut fixture = Fixture::new();
+        let other = Address::from([0xAB; 20]);
+        let block = fixture.block_for(other, vec![fixture.transfer(1, 0)]);
+
+        fixture.validate(&block).unwrap();
+        assert_eq!(fixture.state.balance(&other), U256::from(3 * TRANSFER_GAS + 5_000));
+        assert_eq!(fixture.state.balance(&Address::from(COINBASE)), U256::zero());
+        assert_eq!(&fixture.state.state_root(), block.header().state_root());
+    }
+
+    #[test]
+    fn test_base_fee()
+    {
+        let mut fixture = Fixture::new();
+        let block = fixture.block(Vec::new());
+        assert_eq!(block.header().base_fee(), &Gas::new(7));
+
+        let header = header_with(&fixture, |builder| { builder.set_base_fee(Gas::new(8)); });
+        assert_eq!(
+            fixture.validator.validate_base_fee(&header, &fixture.genesis),
+            Err(BlockError::InvalidBaseFee { expected: Gas::new(7), found: Gas::new(8) })
+        );
+
+        // A full parent raises the base fee.
+        let full = BlockHeaderBuilder::new()
+            .set_base_fee(Gas::new(8))
+            .set_gas_limit(Gas::new(30_000_000))
+            .set_gas_used(Gas::new(30_000_000))
+            .seal();
+        assert_eq!(
+            fixture.validator.validate_base_fee(&header, &full),
+            Err(BlockError::InvalidBaseFee { expected: Gas::new(9), found: Gas::new(8) })
+        );
+
+        // The execution runs under the base fee of the header.
+        let transactions = vec![fixture.transfer(1, 0)];
+        let block = fixture.block(transactions.clone());
+        let header = header_with(&fixture, |builder| {
+            builder
+                .set_base_fee(Gas::new(11))
+                .set_transaction_root(*block.header().transaction_root())
+                .set_receipts_root(*block.header().receipts_root())
+                .set_logs_bloom(*block.header().logs_bloom());
+        });
+        assert_eq!(
+            fixture.validator.validate_execution(&Block::new(header, block.body().clone()), &mut fixture.state),
+            Err(BlockError::TransactionRejected {
+                index: 0,
+                error: ExecutionError::FeeCapBelowBaseFee { max_fee_per_gas: Gas::new(10), base_fee: Gas::new(11) },
+            })
+        );
+    }
+
+    #[test]
+    fn test_header_linkage()
+    {
+        let fixture = Fixture::new();
+        let ancestors = [fixture.genesis.clone()];
+        let now = Timestamp::from(GENESIS_TIME + 15);
+        let validator = &fixture.validator;
+
+        assert_eq!(validator.validate_header(fixture.block(Vec::new()).header(), &[], now), Err(BlockError::MissingParent));
+
+        let header = header_with(&fixture, |builder| { builder.set_parent_hash(HashDigest::default()); });
+        assert_eq!(
+            validator.validate_header(&header, &ancestors, now),
+            Err(BlockError::ParentHashMismatch { expected: *fixture.genesis.hash(), found: HashDigest::default() })
+        );
+
+        let header = header_with(&fixture, |builder| { builder.set_block_number(2); });
+        assert_eq!(validator.validate_header(&header, &ancestors, now), Err(BlockError::InvalidBlockNumber { expected: 1, found: 2 }));
+
+        let header = header_with(&fixture, |builder| { builder.set_block_height(3); });
+        assert_eq!(validator.validate_header(&header, &ancestors, now), Err(BlockError::InvalidBlockHeight { expected: 1, found: 3 }));
+    }
+
+    #[test]
+    fn test_header_timestamp()
+    {
+        let fixture = Fixture::new();
+        let ancestors = [fixture.genesis.clone()];
+        let validator = &fixture.validator;
+
+        let header = header_with(&fixture, |builder| { builder.set_timestamp(Timestamp::from(GENESIS_TIME)); });
+        assert_eq!(
+            validator.validate_header(&header, &ancestors, Timestamp::from(GENESIS_TIME)),
+            Err(BlockError::TimestampTooOld { median_time_past: Timestamp::from(GENESIS_TIME), found: Timestamp::from(GENESIS_TIME) })
+        );
+
+        let header = fixture.block(Vec::new()).header().clone();
+        assert_eq!(
+            validator.validate_header(&header, &ancestors, Timestamp::from(GENESIS_TIME - 1)),
+            Err(BlockError::TimestampInFuture { max: Timestamp::from(GENESIS_TIME + 14), found: Timestamp::from(GENESIS_TIME + 15) })
+        );
+        assert!(validator.validate_header(&header, &ancestors, Timestamp::from(GENESIS_TIME)).is_ok());
+    }
+
+    #[test]
+    fn test_header_difficulty_and_seal()
+    {
+        let fixture = Fixture::new();
+        let ancestors = [fixture.genesis.clone()];
+        let now = Timestamp::from(GENESIS_TIME + 15);
+        let validator = &fixture.validator;
+
+        let header = header_with(&fixture, |builder| { builder.set_difficulty(U256::from(999)).set_total_difficulty(U256::from(1_999)); });
+        assert_eq!(
+            validator.validate_header(&header, &ancestors, now),
+            Err(BlockError::InvalidDifficulty { expected: U256::from(1_000), found: U256::from(999) })
+        );
+
+        let header = header_with(&fixture, |builder| { builder.set_total_difficulty(U256::from(1_000)); });
+        assert_eq!(
+            validator.validate_header(&header, &ancestors, now),
+            Err(BlockError::InvalidTotalDifficulty { expected: U256::from(2_000), found: U256::from(1_000) })
+        );
+
+        let header = header_with(&fixture, |builder| { builder.set_gas_limit(Gas::new(30_029_297)); });
+        assert_eq!(
+            validator.validate_header(&header, &ancestors, now),
+            Err(BlockError::InvalidGasLimit { min: Gas::new(29_970_704), max: Gas::new(30_029_296), found: Gas::new(30_029_297) })
+        );
+        let header = header_with(&fixture, |builder| { builder.set_gas_limit(Gas::new(29_970_704)); });
+        assert!(validator.validate_header(&header, &ancestors, now).is_ok());
+
+        let header = fixture.block(Vec::new()).header().clone();
+        let unsealed = (0u64..)
+            .map(|nonce| header.with_nonce(U256::from(nonce)))
+            .find(|header| PowEngine::dev().verify_header(header).is_err())
+            .unwrap();
+        assert!(matches!(
+            validator.validate_header(&unsealed, &ancestors, now),
+            Err(BlockError::InvalidSeal(PowError::InsufficientWork { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_body()
+    {
+        let fixture = Fixture::new();
+        let validator = &fixture.validator;
+        let block = fixture.block(vec![fixture.transfer(1, 0)]);
+        let transactions = block.body().transaction().to_vec();
+
+        let swapped = Block::new(block.header().clone(), BlockBody::new(vec![fixture.transfer(1, 1)]));
+        assert_eq!(validator.validate_body(&swapped), Err(BlockError::TransactionRootMismatch));
+
+        let header = header_with(&fixture, |builder| {
+            builder
+                .set_transaction_root(*block.header().transaction_root())
+                .set_gas_used(Gas::new(TRANSFER_GAS))
+                .set_gas_limit(Gas::new(TRANSFER_GAS - 1));
+        });
+        let overfull = Block::new(header, block.body().clone());
+        assert_eq!(
+            validator.validate_body(&overfull),
+            Err(BlockError::GasUsedExceedsLimit { gas_used: Gas::new(TRANSFER_GAS), gas_limit: Gas::new(TRANSFER_GAS - 1) })
+        );
+
+        let block = fixture.block(vec![fixture.transfer(1, 0), fixture.transfer(2, 1)]);
+        assert_eq!(validator.validate_body(&block), Err(BlockError::WrongChainId { index: 1, expected: 1, found: 2 }));
+
+        // A transaction whose signature does not cover its contents.
+        let other = fixture.transfer(1, 1);
+        let forged = Arc::new(SignedTransaction::new(
+            transactions[0].raw_transaction().clone(),
+            transactions[0].public_key().clone(),
+            other.signature().clone(),
+            *transactions[0].get_hash(),
+        ));
+        let block = fixture.block(vec![forged]);
+        assert_eq!(
+            validator.validate_body(&block),
+            Err(BlockError::InvalidTransaction { index: 0, error: TransactionError::InvalidSignature })
+        );
+    }
+
+    #[test]
+    fn test_execution()
+    {
+        let mut fixture = Fixture::new();
+        let before = fixture.state.clone();
+
+        let block = fixture.block(vec![fixture.transfer(1, 0)]);
+        let with_header = |change: fn(&mut BlockHeaderBuilder)| {
+            let header = header_with(&fixture, |builder| {
+                builder
+                    .set_transaction_root(*block.header().transaction_root())
+                    .set_receipts_root(*block.header().receipts_root())
+                    .set_logs_bloom(*block.header().logs_bloom())
+                    .set_state_root(*block.header().state_root())
+                    .set_gas_used(*block.header().gas_used());
+                change(builder);
+            });
+            Block::new(header, block.body().clone())
+        };
+        let wrong_gas = with_header(|builder| { builder.set_gas_used(Gas::new(1)); });
+        let overfull = with_header(|builder| { builder.set_gas_limit(Gas::new(TRANSFER_GAS - 1)); });
+        let wrong_state = with_header(|builder| { builder.set_state_root(HashDigest::from([1; HashDigest::LENGTH])); });
+        let other_coinbase = with_header(|builder| { builder.set_coinbase(Address::from([0xAB; 20])); });
+        assert_eq!(
+            fixture.validator.validate_execution(&wrong_gas, &mut fixture.state),
+            Err(BlockError::GasUsedMismatch { expected: Gas::new(TRANSFER_GAS), found: Gas::new(1) })
+        );
+        assert_eq!(
+            fixture.validator.validate_execution(&overfull, &mut fixture.state),
+            Err(BlockError::GasUsedExceedsLimit { gas_used: Gas::new(TRANSFER_GAS), gas_limit: Gas::new(TRANSFER_GAS - 1) })
+        );
+
+        let wrong_receipts = Block::new(header_with(&fixture, |builder| { builder.set_receipts_root(HashDigest::from([1; HashDigest::LENGTH])); }), BlockBody::new(Vec::new()));
+        assert_eq!(fixture.validate(&wrong_receipts), Err(BlockError::ReceiptsRootMismatch));
+
+        let wrong_bloom = Block::new(header_with(&fixture, |builder| { builder.set_logs_bloom(Bloom::from([1; Bloom::LENGTH])); }), BlockBody::new(Vec::new()));
+        assert_eq!(fixture.validate(&wrong_bloom), Err(BlockError::LogsBloomMismatch));
+
+        assert_eq!(
+            fixture.validate(&wrong_state),
+            Err(BlockError::StateRootMismatch { expected: *block.header().state_root(), found: HashDigest::from([1; HashDigest::LENGTH]) })
+        );
+        // The fees and the reward cannot be redirected without changing the state root.
+        assert!(matches!(fixture.validate(&other_coinbase), Err(BlockError::StateRootMismatch { .. })));
+
+        let mut rich_coinbase = fixture.state.clone();
+        rich_coinbase.set_account(Address::from(COINBASE), Account::with_balance(U256::MAX));
+        assert_eq!(
+            fixture.validator.validate_execution(&fixture.block(Vec::new()), &mut rich_coinbase),
+            Err(BlockError::RewardOverflow)
+        );
+
+        assert_eq!(fixture.state, before);
+
+        // Applying the block twice reuses the nonce of its transaction.
+        fixture.validate(&block).unwrap();
+        let after = fixture.state.clone();
+        assert_eq!(
+            fixture.validate(&block),
+            Err(BlockError::TransactionRejected { index: 0, error: ExecutionError::NonceMismatch { expected: U256::one(), found: U256::zero() } })
+        );
+        assert_eq!(fixture.state, after);
+    }
+}
@@ -0,0 +1,485 @@
+//! # Executor
+//!
+//! This module applies `SignedTransaction`s to a `State`.
+//!
+//! A transaction is first checked against the base fee of its block and the state of its sender: its
+//! fee cap must cover the base fee, its tip must not exceed its fee cap, the nonce must be the
+//! sender's next nonce and the balance must cover `value + gas * max_fee_per_gas`. Transactions
+//! failing these checks, or whose tip does not fit in the coinbase balance, are rejected with an
+//! `ExecutionError` and leave the state untouched.
+//!
+//! The intrinsic gas of the transaction, as priced by the `GasSchedule`, is charged to a `GasMeter`
+//! bounded by its gas limit; a gas limit below the intrinsic gas rejects the transaction.
+//!
+//! Accepted transactions always increment the sender nonce and pay `gas_used` times their effective
+//! gas price: the base fee part is burned and the tip part goes to the coinbase. Their action is then
+//! applied; if it cannot be applied, it is reverted and the outcome has the `Failure` status.
+//! Signatures are not checked here and must be verified beforehand.
+//!
+//! A successful transfer emits a log from the sender with the transfer topic and the recipient
+//! address as topics, and the amount as 32 big-endian bytes of data.
+
+use core_utils::gas::{Gas, GasMeter};
+use crypto::hash::{HashDigest, Algorithm};
+use primvites::{
+    Address, U256,
+    receipt::{Log, address_topic},
+    transaction::{SignedTransaction, Action, ExecutionOutcome, ExecutionStatus},
+};
+use crate::{gas_schedule::GasSchedule, state::State};
+
+// The 'transfer_topic' function returns the first topic of transfer logs
+pub fn transfer_topic() -> HashDigest
+{
+    HashDigest::calculate(b"Transfer", Algorithm::Keccak).expect("Topic is not empty")
+}
+
+// The 'transfer_log' function creates the log emitted by a transfer
+pub fn transfer_log(from: &Address, to: &Address, amount: &U256) -> Log
+{
+    let mut data = vec![0u8; 32];
+    amount.to_big_endian(&mut data);
+    Log::new(*from, vec![transfer_topic(), address_topic(to)], data)
+}
+
+// Represents the reasons a transaction cannot be executed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecutionError
+{
+    NonceMismatch { expected: U256, found: U256 },
+    InsufficientBalance { required: U256, available: U256 },
+    IntrinsicGasTooLow { required: Gas, provided: Gas },
+    ValueMismatch { value: U256, amount: U256 },
+    FeeCapBelowBaseFee { max_fee_per_gas: Gas, base_fee: Gas },
+    TipAboveFeeCap { max_priority_fee_per_gas: Gas, max_fee_per_gas: Gas },
+    Overflow,
+}
+
+impl std::fmt::Display for ExecutionError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            ExecutionError::NonceMismatch { expected, found } =>
+                write!(f, "Invalid nonce: expected {}, found {}", expected, found),
+            ExecutionError::InsufficientBalance { required, available } =>
+                write!(f, "Insufficient balance: required {}, available {}", required, available),
+            ExecutionError::IntrinsicGasTooLow { required, provided } =>
+                write!(f, "Intrinsic gas too low: required {}, provided {}", required, provided),
+            ExecutionError::ValueMismatch { value, amount } =>
+                write!(f, "Transaction value {} does not match transfer amount {}", value, amount),
+            ExecutionError::FeeCapBelowBaseFee { max_fee_per_gas, base_fee } =>
+                write!(f, "Max fee per gas {} is below the base fee {}", max_fee_per_gas, base_fee),
+            ExecutionError::TipAboveFeeCap { max_priority_fee_per_gas, max_fee_per_gas } =>
+                write!(f, "Max priority fee per gas {} exceeds the max fee per gas {}", max_priority_fee_per_gas, max_fee_per_gas),
+            ExecutionError::Overflow => write!(f, "Arithmetic overflow!"),
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
+
+// Applies transactions to a state, burning the base fee and paying tips to a coinbase address
+pub struct Executor
+{
+    coinbase: Address,
+    base_fee: Gas,
+    schedule: GasSchedule,
+}
+
+impl Executor
+{
+    // The 'new' function creates an executor paying fees to the given coinbase, with a zero base fee
+    // and the default gas schedule
+    pub fn new(coinbase: Address) -> Self
+    {
+        Executor { coinbase, base_fee: Gas::new(0), schedule: GasSchedule::default() }
+    }
+
+    // The 'with_schedule' function sets the gas costs of execution
+    pub fn with_schedule(mut self, schedule: GasSchedule) -> Self
+    {
+        self.schedule = schedule;
+        self
+    }
+
+    // The 'with_base_fee' function sets the base fee of the block the transactions are executed in
+    pub fn with_base_fee(mut self, base_fee: Gas) -> Self
+    {
+        self.base_fee = base_fee;
+        self
+    }
+
+    // The 'coinbase' function returns the address receiving the tips
+    pub fn coinbase(&self) -> &Address
+    {
+        &self.coinbase
+    }
+
+    // The 'base_fee' function returns the base fee burned per unit of gas
+    pub fn base_fee(&self) -> &Gas
+    {
+        &self.base_fee
+    }
+
+    // The 'schedule' function returns the gas costs of execution
+    pub fn schedule(&self) -> &GasSchedule
+    {
+        &self.schedule
+    }
+
+    // The 'intrinsic_gas' function returns the gas consumed by a transaction before its action runs
+    pub fn intrinsic_gas(&self, transaction: &SignedTransaction) -> Result<Gas, ExecutionError>
+    {
+        self.schedule.intrinsic_gas(transaction.raw_transaction()).ok_or(ExecutionError::Overflow)
+    }
+
+    // The 'execute' function applies a transaction to the state
+    pub fn execute<S: State>(
+        &self,
+        state: &mut S,
+        transaction: &SignedTransaction,
+    ) -> Result<ExecutionOutcome, ExecutionError> {
+        let raw = transaction.raw_transaction();
+        let max_fee_per_gas = raw.max_fee_per_gas();
+        let max_priority_fee_per_gas = raw.max_priority_fee_per_gas();
+        if max_priority_fee_per_gas.amount() > max_fee_per_gas.amount()
+        {
+            return Err(ExecutionError::TipAboveFeeCap { max_priority_fee_per_gas, max_fee_per_gas });
+        }
+        let gas_price = raw.fee().effective_gas_price(self.base_fee).ok_or(ExecutionError::FeeCapBelowBaseFee {
+            max_fee_per_gas,
+            base_fee: self.base_fee,
+        })?;
+
+        let sender = transaction.sender();
+        let mut sender_account = state.account_or_default(&sender);
+
+        if sender_account.nonce() != raw.nonce()
+        {
+            return Err(ExecutionError::NonceMismatch {
+                expected: *sender_account.nonce(),
+                found: *raw.nonce(),
+            });
+        }
+
+        let Action::Transfer(transfer) = raw.action();
+        if &transfer.amount != raw.value()
+        {
+            return Err(ExecutionError::ValueMismatch { value: *raw.value(), amount: transfer.amount });
+        }
+
+        let intrinsic_gas = self.intrinsic_gas(transaction)?;
+        let mut meter = GasMeter::new(*raw.gas());
+        meter
+            .charge(intrinsic_gas)
+            .map_err(|_| ExecutionError::IntrinsicGasTooLow { required: intrinsic_gas, provided: *raw.gas() })?;
+        let gas_used = meter.settle(self.schedule.max_refund_quotient);
+
+        let required = (U256::from(raw.gas().amount()) * U256::from(max_fee_per_gas.amount()))
+            .checked_add(*raw.value())
+            .ok_or(ExecutionError::Overflow)?;
+        if sender_account.balance() < &required
+        {
+            return Err(ExecutionError::InsufficientBalance {
+                required,
+                available: *sender_account.balance(),
+            });
+        }
+
+        // The transaction is valid: charge the fee and consume the nonce. The accounts it touches are
+        // kept to restore them if the tip cannot be credited.
+        let touched = [sender, transfer.to].map(|address| (address, state.account(&address)));
+        let fee = U256::from(gas_used.amount()) * U256::from(gas_price.amount());
+        let tip = U256::from(gas_used.amount()) * U256::from(gas_price.amount() - self.base_fee.amount());
+        let nonce = sender_account.nonce().checked_add(U256::one()).ok_or(ExecutionError::Overflow)?;
+        sender_account.set_balance(*sender_account.balance() - fee);
+        sender_account.set_nonce(nonce);
+        state.set_account(sender, sender_account);
+
+        let (status, logs) = match Self::transfer(state, &sender, &transfer.to, transfer.amount)
+        {
+            Ok(()) => (ExecutionStatus::Success, vec![transfer_log(&sender, &transfer.to, &transfer.amount)]),
+            Err(_) => (ExecutionStatus::Failure, Vec::new()),
+        };
+
+        let mut coinbase_account = state.account_or_default(&self.coinbase);
+        let coinbase_balance = match coinbase_account.balance().checked_add(tip)
+        {
+            Some(balance) => balance,
+            None =>
+            {
+                for (address, account) in touched
+                {
+                    match account
+                    {
+                        Some(account) => state.set_account(address, account),
+                        None => state.remove_account(&address),
+                    }
+                }
+                return Err(ExecutionError::Overflow);
+            }
+        };
+        coinbase_account.set_balance(coinbase_balance);
+        state.set_account(self.coinbase, coinbase_account);
+
+        Ok(ExecutionOutcome::new(*transaction.get_hash(), status, gas_used, logs))
+    }
+
+    // The 'transfer' function moves an amount between two accounts, leaving the state untouched on error
+    fn transfer<S: State>(
+        state: &mut S,
+        from: &Address,
+        to: &Address,
+        amount: U256,
+    ) -> Result<(), ExecutionError> {
+        let mut from_account = state.account_or_default(from);
+        let available = *from_account.balance();
+        let from_balance = available
+            .checked_sub(amount)
+            .ok_or(ExecutionError::InsufficientBalance { required: amount, available })?;
+        from_account.set_balance(from_balance);
+
+        if from == to
+        {
+            return Ok(());
+        }
+
+        let mut to_account = state.account_or_default(to);
+        let to_balance = to_account.balance().checked_add(amount).ok_or(ExecutionError::Overflow)?;
+        to_account.set_balance(to_balance);
+
+        state.set_account(*from, from_account);
+        state.set_account(*to, to_account);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::{gas_schedule::TRANSFER_GAS, state::MemoryState};
+    use crypto::ed25519::PrivateKey;
+    use primvites::{account::Account, transaction::{RawTransaction, TransactionFee, TransferAction}, Bytes};
+    use rand::rngs::OsRng;
+
+    const COINBASE: [u8; 20] = [0xCB; 20];
+
+    fn transfer(private_key: PrivateKey, nonce: u64, to: Address, amount: u64, gas: u64) -> SignedTransaction
+    {
+        RawTransaction::new(
+            1,
+            U256::from(nonce),
+            Action::Transfer(TransferAction { to, amount: U256::from(amount) }),
+            Gas::from(2),
+            Gas::from(gas),
+            U256::from(amount),
+            Bytes::new(),
+        )
+        .sign(&private_key)
+    }
+
+    fn funded_sender(state: &mut MemoryState, balance: u64) -> (PrivateKey, Address)
+    {
+        let mut csprng: OsRng = OsRng;
+        let private_key = PrivateKey::generate(&mut csprng);
+        let address = private_key.to_public_key().to_address();
+        state.set_account(address, Account::with_balance(U256::from(balance)));
+        (private_key, address)
+    }
+
+    #[test]
+    fn test_execute_transfer()
+    {
+        let mut state = MemoryState::new();
+        let (private_key, sender) = funded_sender(&mut state, 100_000);
+        let recipient = Address::from([7; 20]);
+        let executor = Executor::new(Address::from(COINBASE));
+
+        let transaction = transfer(private_key, 0, recipient, 1_000, 30_000);
+        let outcome = executor.execute(&mut state, &transaction).unwrap();
+
+        assert!(outcome.is_success());
+        assert_eq!(outcome.gas_used(), &Gas::new(TRANSFER_GAS));
+        assert_eq!(outcome.transaction_hash(), transaction.get_hash());
+        assert_eq!(state.balance(&sender), U256::from(100_000 - 1_000 - 2 * TRANSFER_GAS));
+        assert_eq!(state.nonce(&sender), U256::from(1));
+        assert_eq!(state.balance(&recipient), U256::from(1_000));
+        assert_eq!(state.balance(&Address::from(COINBASE)), U256::from(2 * TRANSFER_GAS));
+        assert_eq!(outcome.logs(), &[transfer_log(&sender, &recipient, &U256::from(1_000))]);
+    }
+
+    #[test]
+    fn test_execute_rejects_wrong_nonce()
+    {
+        let mut state = MemoryState::new();
+        let (private_key, _) = funded_sender(&mut state, 100_000);
+        let executor = Executor::new(Address::from(COINBASE));
+        let before = state.clone();
+
+        let transaction = transfer(private_key, 1, Address::from([7; 20]), 1_000, 30_000);
+        assert_eq!(
+            executor.execute(&mut state, &transaction),
+            Err(ExecutionError::NonceMismatch { expected: U256::zero(), found: U256::from(1) })
+        );
+        assert_eq!(state, before);
+    }
+
+    #[test]
+    fn test_execute_rejects_insufficient_balance()
+    {
+        let mut state = MemoryState::new();
+        let (private_key, _) = funded_sender(&mut state, 60_000);
+        let executor = Executor::new(Address::from(COINBASE));
+        let before = state.clone();
+
+        // The full gas limit must be covered, even if less is used.
+        let transaction = transfer(private_key, 0, Address::from([7; 20]), 1_000, 30_000);
+        assert_eq!(
+            executor.execute(&mut state, &transaction),
+            Err(ExecutionError::InsufficientBalance { required: U256::from(61_000), available: U256::from(60_000) })
+        );
+        assert_eq!(state, before);
+    }
+
+    #[test]
+    fn test_execute_rejects_coinbase_overflow()
+    {
+        let mut state = MemoryState::new();
+        let (private_key, _) = funded_sender(&mut state, 100_000);
+        state.set_account(Address::from(COINBASE), Account::with_balance(U256::MAX));
+        let executor = Executor::new(Address::from(COINBASE));
+        let before = state.clone();
+
+        let transaction = transfer(private_key, 0, Address::from([7; 20]), 1_000, 30_000);
+        assert_eq!(executor.execute(&mut state, &transaction), Err(ExecutionError::Overflow));
+        assert_eq!(state, before);
+    }
+
+    #[test]
+    fn test_execute_rejects_low_gas()
+    {
+        let mut state = MemoryState::new();
+        let (private_key, _) = funded_sender(&mut state, 100_000);
+        let executor = Executor::new(Address::from(COINBASE));
+
+        let transaction = transfer(private_key, 0, Address::from([7; 20]), 1_000, TRANSFER_GAS - 1);
+        assert_eq!(
+            executor.execute(&mut state, &transaction),
+            Err(ExecutionError::IntrinsicGasTooLow { required: Gas::new(TRANSFER_GAS), provided: Gas::new(TRANSFER_GAS - 1) })
+        );
+    }
+
+    #[test]
+    fn test_failed_transfer_still_charges_fee()
+    {
+        let mut state = MemoryState::new();
+        let (private_key, sender) = funded_sender(&mut state, 100_000);
+        let recipient = Address::from([7; 20]);
+        state.set_account(recipient, Account::with_balance(U256::MAX));
+        let executor = Executor::new(Address::from(COINBASE));
+
+        let transaction = transfer(private_key, 0, recipient, 1_000, 30_000);
+        let outcome = executor.execute(&mut state, &transaction).unwrap();
+
+        assert_eq!(outcome.status(), ExecutionStatus::Failure);
+        assert!(outcome.logs().is_empty());
+        assert_eq!(state.balance(&sender), U256::from(100_000 - 2 * TRANSFER_GAS));
+        assert_eq!(state.nonce(&sender), U256::from(1));
+        assert_eq!(state.balance(&recipient), U256::MAX);
+    }
+
+    #[test]
+    fn test_self_transfer()
+    {
+        let mut state = MemoryState::new();
+        let (private_key, sender) = funded_sender(&mut state, 100_000);
+        let executor = Executor::new(Address::from(COINBASE));
+
+        let transaction = transfer(private_key, 0, sender, 1_000, 30_000);
+        assert!(executor.execute(&mut state, &transaction).unwrap().is_success());
+        assert_eq!(state.balance(&sender), U256::from(100_000 - 2 * TRANSFER_GAS));
+    }
+
+    #[test]
+    fn test_data_is_charged()
+    {
+        let mut state = MemoryState::new();
+        let (private_key, sender) = funded_sender(&mut state, 100_000);
+        let executor = Executor::new(Address::from(COINBASE));
+        let raw = RawTransaction::new(
+            1,
+            U256::zero(),
+            Action::Transfer(TransferAction { to: Address::from([7; 20]), amount: U256::from(1_000) }),
+            Gas::from(2),
+            Gas::from(TRANSFER_GAS + 16),
+            U256::from(1_000),
+            vec![0xAB; 2],
+        );
+
+        let transaction = raw.sign(&private_key);
+        assert_eq!(executor.intrinsic_gas(&transaction), Ok(Gas::new(TRANSFER_GAS + 32)));
+        assert_eq!(
+            executor.execute(&mut state, &transaction),
+            Err(ExecutionError::IntrinsicGasTooLow { required: Gas::new(TRANSFER_GAS + 32), provided: Gas::new(TRANSFER_GAS + 16) })
+        );
+
+        let executor = executor.with_schedule(GasSchedule { data_byte: Gas::new(8), ..GasSchedule::default() });
+        let outcome = executor.execute(&mut state, &transaction).unwrap();
+        assert_eq!(outcome.gas_used(), &Gas::new(TRANSFER_GAS + 16));
+        assert_eq!(state.balance(&sender), U256::from(100_000 - 1_000 - 2 * (TRANSFER_GAS + 16)));
+    }
+
+    fn dynamic_transfer(private_key: PrivateKey, max_fee_per_gas: u64, max_priority_fee_per_gas: u64) -> SignedTransaction
+    {
+        RawTransaction::with_fee(
+            1,
+            U256::zero(),
+            Action::Transfer(TransferAction { to: Address::from([7; 20]), amount: U256::from(1_000) }),
+            TransactionFee::Dynamic {
+                max_fee_per_gas: Gas::from(max_fee_per_gas),
+                max_priority_fee_per_gas: Gas::from(max_priority_fee_per_gas),
+            },
+            Gas::from(30_000),
+            U256::from(1_000),
+            Bytes::new(),
+        )
+        .sign(&private_key)
+    }
+
+    #[test]
+    fn test_base_fee_is_burned()
+    {
+        let mut state = MemoryState::new();
+        let (private_key, sender) = funded_sender(&mut state, 1_000_000);
+        let executor = Executor::new(Address::from(COINBASE)).with_base_fee(Gas::new(10));
+
+        // The tip is capped at 15 - 10 = 5 per gas.
+        let transaction = dynamic_transfer(private_key, 15, 8);
+        assert!(executor.execute(&mut state, &transaction).unwrap().is_success());
+        assert_eq!(state.balance(&sender), U256::from(1_000_000 - 1_000 - 15 * TRANSFER_GAS));
+        assert_eq!(state.balance(&Address::from(COINBASE)), U256::from(5 * TRANSFER_GAS));
+    }
+
+    #[test]
+    fn test_execute_rejects_invalid_fees()
+    {
+        let mut state = MemoryState::new();
+        let (private_key, _) = funded_sender(&mut state, 1_000_000);
+        let (other_key, _) = funded_sender(&mut state, 1_000_000);
+        let executor = Executor::new(Address::from(COINBASE)).with_base_fee(Gas::new(10));
+        let before = state.clone();
+
+        assert_eq!(
+            executor.execute(&mut state, &dynamic_transfer(private_key, 9, 1)),
+            Err(ExecutionError::FeeCapBelowBaseFee { max_fee_per_gas: Gas::new(9), base_fee: Gas::new(10) })
+        );
+        assert_eq!(
+            executor.execute(&mut state, &dynamic_transfer(other_key, 20, 21)),
+            Err(ExecutionError::TipAboveFeeCap { max_priority_fee_per_gas: Gas::new(21), max_fee_per_gas: Gas::new(20) })
+        );
+        assert_eq!(state, before);
+    }
+}
@@ -0,0 +1,153 @@
+//! # Fee Market
+//!
+//! This module adjusts the base fee of blocks, following the rules of EIP-1559.
+//!
+//! Every block targets a gas usage of `gas_limit / elasticity_multiplier`. The base fee of a block
+//! is the base fee of its parent, raised when the parent used more gas than the target and lowered
+//! when it used less, by at most `1 / change_denominator` of the parent base fee per block. A
+//! congested parent always raises the base fee by at least one, and the base fee never drops below
+//! `minimum_base_fee`.
+//!
+//! The base fee is burned: transactions pay it for every unit of gas they use, and only the tip above
+//! it goes to the coinbase.
+
+use core_utils::gas::Gas;
+use primvites::block_header::BlockHeader;
+use serde::{Serialize, Deserialize};
+
+// Base fee config
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeeMarketConfig
+{
+    // The ratio between the gas limit of a block and its gas target.
+    // 2 by default.
+    pub elasticity_multiplier: u64,
+    // The inverse of the largest change of the base fee between two blocks.
+    // 8 by default.
+    pub change_denominator: u64,
+    // The base fee of the genesis block.
+    // 1 billion by default.
+    pub initial_base_fee: Gas,
+    // The lowest base fee of a block.
+    // Zero by default.
+    pub minimum_base_fee: Gas,
+}
+
+impl Default for FeeMarketConfig
+{
+    fn default() -> Self
+    {
+        Self {
+            elasticity_multiplier: 2,
+            change_denominator: 8,
+            initial_base_fee: Gas::new(1_000_000_000),
+            minimum_base_fee: Gas::new(0),
+        }
+    }
+}
+
+// Computes the base fee of blocks from their parent
+#[derive(Clone, Debug, Default)]
+pub struct FeeMarket
+{
+    config: FeeMarketConfig,
+}
+
+impl FeeMarket
+{
+    // The 'new' function creates a fee market from a config
+    pub fn new(config: FeeMarketConfig) -> Self
+    {
+        FeeMarket { config }
+    }
+
+    // The 'config' function returns the config of the fee market
+    pub fn config(&self) -> &FeeMarketConfig
+    {
+        &self.config
+    }
+
+    // The 'gas_target' function returns the gas usage a block with the given gas limit targets
+    pub fn gas_target(&self, gas_limit: Gas) -> Gas
+    {
+        Gas::new(gas_limit.amount() / self.config.elasticity_multiplier.max(1))
+    }
+
+    // The 'next_base_fee' function returns the base fee of the child of a block
+    pub fn next_base_fee(&self, parent: &BlockHeader) -> Gas
+    {
+        self.base_fee_after(*parent.base_fee(), *parent.gas_used(), *parent.gas_limit())
+    }
+
+    // The 'base_fee_after' function returns the base fee following a block with the given base fee,
+    // gas used and gas limit
+    pub fn base_fee_after(&self, base_fee: Gas, gas_used: Gas, gas_limit: Gas) -> Gas
+    {
+        let target = self.gas_target(gas_limit).amount();
+        let base_fee = base_fee.amount();
+        let gas_used = gas_used.amount();
+        if target == 0 || gas_used == target
+        {
+            return Gas::new(base_fee.max(self.config.minimum_base_fee.amount()));
+        }
+
+        // Computed in 128 bits, as base fee times gas can exceed 64 bits.
+        let denominator = u128::from(self.config.change_denominator.max(1));
+        let delta = |difference: u64| u128::from(base_fee) * u128::from(difference) / u128::from(target) / denominator;
+        let next = if gas_used > target
+        {
+            let delta = delta(gas_used - target).max(1);
+            u64::try_from(u128::from(base_fee) + delta).unwrap_or(u64::MAX)
+        } else {
+            // The delta is below the base fee, as the difference is below the target.
+            base_fee - delta(target - gas_used) as u64
+        };
+        Gas::new(next.max(self.config.minimum_base_fee.amount()))
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    const GAS_LIMIT: u64 = 30_000_000;
+
+    fn next(base_fee: u64, gas_used: u64) -> u64
+    {
+        FeeMarket::default().base_fee_after(Gas::new(base_fee), Gas::new(gas_used), Gas::new(GAS_LIMIT)).amount()
+    }
+
+    #[test]
+    fn test_base_fee_at_target()
+    {
+        assert_eq!(next(1_000_000_000, GAS_LIMIT / 2), 1_000_000_000);
+    }
+
+    #[test]
+    fn test_base_fee_bounds()
+    {
+        // A full block raises the base fee by an eighth, an empty one lowers it by an eighth.
+        assert_eq!(next(1_000_000_000, GAS_LIMIT), 1_125_000_000);
+        assert_eq!(next(1_000_000_000, 0), 875_000_000);
+        assert_eq!(next(1_000_000_000, 3 * GAS_LIMIT / 4), 1_062_500_000);
+        assert_eq!(next(u64::MAX, GAS_LIMIT), u64::MAX);
+    }
+
+    #[test]
+    fn test_base_fee_congestion_always_raises()
+    {
+        assert_eq!(next(0, GAS_LIMIT), 1);
+        assert_eq!(next(1, GAS_LIMIT / 2 + 1), 2);
+        assert_eq!(next(1, 0), 1);
+    }
+
+    #[test]
+    fn test_minimum_base_fee()
+    {
+        let market = FeeMarket::new(FeeMarketConfig { minimum_base_fee: Gas::new(100), ..FeeMarketConfig::default() });
+        assert_eq!(market.base_fee_after(Gas::new(105), Gas::new(0), Gas::new(GAS_LIMIT)), Gas::new(100));
+        assert_eq!(market.base_fee_after(Gas::new(0), Gas::new(GAS_LIMIT / 2), Gas::new(GAS_LIMIT)), Gas::new(100));
+    }
+}
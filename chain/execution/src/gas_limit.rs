@@ -0,0 +1,59 @@
+//! # Gas Limit
+//!
+//! This module bounds the gas limit of blocks by the gas limit of their parent.
+//!
+//! The gas limit of a block can differ from the gas limit of its parent by at most
+//! `parent_gas_limit / GAS_LIMIT_BOUND_DIVISOR`, and is never below `MINIMUM_GAS_LIMIT`. Producers
+//! move the gas limit toward the one they are configured with, one bounded step per block, so that a
+//! chain changes its capacity gradually rather than at the will of a single producer.
+
+use core_utils::gas::Gas;
+
+// The inverse of the largest change of the gas limit between two blocks
+pub const GAS_LIMIT_BOUND_DIVISOR: u64 = 1024;
+
+// The lowest gas limit of a block
+pub const MINIMUM_GAS_LIMIT: u64 = 5_000;
+
+// The 'gas_limit_bounds' function returns the lowest and the highest gas limit of the child of a block
+// with the given gas limit
+pub fn gas_limit_bounds(parent_gas_limit: Gas) -> (Gas, Gas)
+{
+    let parent = parent_gas_limit.amount();
+    let delta = parent / GAS_LIMIT_BOUND_DIVISOR;
+    let min = parent.saturating_sub(delta).max(MINIMUM_GAS_LIMIT);
+    let max = parent.saturating_add(delta).max(MINIMUM_GAS_LIMIT);
+    (Gas::new(min), Gas::new(max))
+}
+
+// The 'next_gas_limit' function returns the gas limit closest to `desired` that the child of a block
+// with the given gas limit can have
+pub fn next_gas_limit(parent_gas_limit: Gas, desired: Gas) -> Gas
+{
+    let (min, max) = gas_limit_bounds(parent_gas_limit);
+    desired.clamp(min, max)
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_gas_limit_bounds()
+    {
+        assert_eq!(gas_limit_bounds(Gas::new(30_720_000)), (Gas::new(30_690_000), Gas::new(30_750_000)));
+        // Small limits cannot move below the minimum.
+        assert_eq!(gas_limit_bounds(Gas::new(0)), (Gas::new(MINIMUM_GAS_LIMIT), Gas::new(MINIMUM_GAS_LIMIT)));
+        assert_eq!(gas_limit_bounds(Gas::MAX).1, Gas::MAX);
+    }
+
+    #[test]
+    fn test_next_gas_limit()
+    {
+        let parent = Gas::new(30_720_000);
+        assert_eq!(next_gas_limit(parent, Gas::new(30_700_000)), Gas::new(30_700_000));
+        assert_eq!(next_gas_limit(parent, Gas::new(60_000_000)), Gas::new(30_750_000));
+        assert_eq!(next_gas_limit(parent, Gas::new(0)), Gas::new(30_690_000));
+    }
+}
@@ -0,0 +1,103 @@
+//! # Gas Schedule
+//!
+//! This module defines `GasSchedule`, the gas cost of the operations a transaction performs.
+//!
+//! The intrinsic gas of a transaction, charged before its action runs, is the cost of its action
+//! plus a cost for every byte of its data payload. Refunds are capped to the gas used divided by
+//! `max_refund_quotient`.
+
+use core_utils::gas::Gas;
+use primvites::transaction::{Action, RawTransaction};
+use serde::{Serialize, Deserialize};
+
+// Gas consumed by a transfer
+pub const TRANSFER_GAS: u64 = 21_000;
+
+// Gas costs of execution
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GasSchedule
+{
+    // The gas consumed by a transfer.
+    // 21 000 by default.
+    pub transfer: Gas,
+    // The gas consumed by every byte of the data payload.
+    // 16 by default.
+    pub data_byte: Gas,
+    // The divisor of the gas used giving the largest refund.
+    // 5 by default.
+    pub max_refund_quotient: u64,
+}
+
+impl Default for GasSchedule
+{
+    fn default() -> Self
+    {
+        Self {
+            transfer: Gas::new(TRANSFER_GAS),
+            data_byte: Gas::new(16),
+            max_refund_quotient: 5,
+        }
+    }
+}
+
+impl GasSchedule
+{
+    // The 'action_cost' function returns the gas consumed by an action
+    pub fn action_cost(&self, action: &Action) -> Gas
+    {
+        match action
+        {
+            Action::Transfer(_) => self.transfer,
+        }
+    }
+
+    // The 'data_cost' function returns the gas consumed by a data payload, or None on overflow
+    pub fn data_cost(&self, data: &[u8]) -> Option<Gas>
+    {
+        self.data_byte.checked_mul(u64::try_from(data.len()).ok()?)
+    }
+
+    // The 'intrinsic_gas' function returns the gas consumed by a transaction before its action runs,
+    // or None on overflow
+    pub fn intrinsic_gas(&self, transaction: &RawTransaction) -> Option<Gas>
+    {
+        self.action_cost(transaction.action()).checked_add(self.data_cost(transaction.data())?)
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use primvites::{Address, Bytes, U256, transaction::TransferAction};
+
+    fn transfer(data: Bytes) -> RawTransaction
+    {
+        RawTransaction::new(
+            1,
+            U256::zero(),
+            Action::Transfer(TransferAction { to: Address::from([7; 20]), amount: U256::one() }),
+            Gas::from(1),
+            Gas::from(TRANSFER_GAS),
+            U256::one(),
+            data,
+        )
+    }
+
+    #[test]
+    fn test_intrinsic_gas()
+    {
+        let schedule = GasSchedule::default();
+        assert_eq!(schedule.intrinsic_gas(&transfer(Bytes::new())), Some(Gas::new(TRANSFER_GAS)));
+        assert_eq!(schedule.intrinsic_gas(&transfer(Bytes::from(&[0, 1, 2]))), Some(Gas::new(TRANSFER_GAS + 3 * 16)));
+    }
+
+    #[test]
+    fn test_intrinsic_gas_overflow()
+    {
+        let schedule = GasSchedule { data_byte: Gas::MAX, ..GasSchedule::default() };
+        assert_eq!(schedule.data_cost(&[1, 2]), None);
+        assert_eq!(schedule.intrinsic_gas(&transfer(Bytes::from(&[1]))), None);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod state;
+pub mod executor;
+pub mod producer;
+pub mod fee_market;
+pub mod gas_limit;
+pub mod gas_schedule;
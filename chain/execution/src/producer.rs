@@ -0,0 +1,425 @@
+//! # Block Producer
+//!
+//! This module assembles the next `Block` on top of a parent header.
+//!
+//! Transactions are pulled from a `TransactionSource` in inclusion order and executed against a
+//! copy of the state under the base fee of the block, which the fee market derives from the parent.
+//! A transaction whose gas limit does not fit in the gas left in the block is skipped, as is one the
+//! executor rejects; the block is full once no transaction fits anymore. Once every transaction ran,
+//! the block reward is credited to the coinbase, which also receives the tips. The copy replaces the
+//! state only once the block is complete, so a failed production leaves the state untouched.
+//!
+//! The gas limit of the block moves from the gas limit of the parent toward the configured one,
+//! within the bounds of the `gas_limit` module. The header commits to the transactions, receipts and
+//! logs of the block and to the state after the block reward, records its coinbase, base fee, gas
+//! limit and gas used, and has the total difficulty of the parent plus its own difficulty.
+//!
+//! It is sealed with a zero nonce: proof of work is added afterwards by mining it.
+
+use core_utils::{gas::{Gas, GasMeter}, timestamp::Timestamp};
+use crypto::hash::{HashDigest, HashError};
+use primvites::{
+    Address, U256,
+    block::{Block, BlockBody},
+    block_header::{BlockHeader, BlockHeaderBuilder},
+    bloom::Bloom,
+    receipt::{Receipt, receipts_root},
+    transaction::SignedTransaction,
+};
+use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+use crate::{
+    executor::{Executor, ExecutionError},
+    fee_market::{FeeMarket, FeeMarketConfig},
+    gas_limit::next_gas_limit,
+    gas_schedule::GasSchedule,
+    state::State,
+};
+
+// 'TransactionSource' defines where a block producer pulls transactions from.
+pub trait TransactionSource
+{
+    // The 'best_transactions' function returns candidate transactions in inclusion order,
+    // whose gas limits add up to at most the given limit and which can pay the given base fee
+    fn best_transactions(&self, gas_limit: Gas, base_fee: Gas) -> Vec<Arc<SignedTransaction>>;
+}
+
+impl TransactionSource for Vec<Arc<SignedTransaction>>
+{
+    fn best_transactions(&self, _gas_limit: Gas, _base_fee: Gas) -> Vec<Arc<SignedTransaction>>
+    {
+        self.clone()
+    }
+}
+
+// Represents errors that can occur while producing a block.
+#[derive(Debug, PartialEq)]
+pub enum ProducerError
+{
+    ReceiptsRoot(HashError),
+    RewardOverflow,
+}
+
+impl std::fmt::Display for ProducerError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            ProducerError::ReceiptsRoot(e) => write!(f, "Failed to compute the receipts root: {}", e),
+            ProducerError::RewardOverflow => write!(f, "The block reward overflows the coinbase balance!"),
+        }
+    }
+}
+
+impl std::error::Error for ProducerError {}
+
+// Block producer config
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProducerConfig
+{
+    // The address receiving the tips and the block reward.
+    // The zero address by default.
+    pub coinbase: Address,
+    // The amount credited to the coinbase for every produced block.
+    // Zero by default.
+    #[serde(with = "core_utils::u256_bytes")]
+    pub block_reward: U256,
+    // The gas limit produced blocks move toward, within the bounds set by the gas limit of their parent.
+    // 30 million by default.
+    pub gas_limit: Gas,
+    // The protocol version of produced headers.
+    // 1 by default.
+    pub protocol_version: u32,
+    // The rule deriving the base fee of produced blocks from their parent.
+    // The default fee market by default.
+    pub fee_market: FeeMarketConfig,
+    // The gas costs of execution.
+    // The default schedule by default.
+    pub gas_schedule: GasSchedule,
+}
+
+impl Default for ProducerConfig
+{
+    fn default() -> Self
+    {
+        Self {
+            coinbase: Address::default(),
+            block_reward: U256::zero(),
+            gas_limit: Gas::new(30_000_000),
+            protocol_version: 1,
+            fee_market: FeeMarketConfig::default(),
+            gas_schedule: GasSchedule::default(),
+        }
+    }
+}
+
+// A block assembled by the producer, along with what its execution produced
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProducedBlock
+{
+    block: Block,
+    receipts: Vec<Receipt>,
+    rejected: Vec<(HashDigest, ExecutionError)>,
+}
+
+impl ProducedBlock
+{
+    // The 'block' function returns the produced block
+    pub fn block(&self) -> &Block { &self.block }
+
+    // The 'receipts' function returns the receipts of the included transactions, in block order
+    pub fn receipts(&self) -> &[Receipt] { &self.receipts }
+
+    // The 'rejected' function returns the hashes of the transactions the executor rejected, with the reason
+    pub fn rejected(&self) -> &[(HashDigest, ExecutionError)] { &self.rejected }
+
+    // The 'into_block' function returns the produced block, dropping the receipts
+    pub fn into_block(self) -> Block { self.block }
+}
+
+// Assembles blocks from a transaction source
+pub struct BlockProducer
+{
+    config: ProducerConfig,
+    fee_market: FeeMarket,
+}
+
+impl BlockProducer
+{
+    // The 'new' function creates a producer from a config
+    pub fn new(config: ProducerConfig) -> Self
+    {
+        let fee_market = FeeMarket::new(config.fee_market.clone());
+        BlockProducer { config, fee_market }
+    }
+
+    // The 'config' function returns the config of the producer
+    pub fn config(&self) -> &ProducerConfig
+    {
+        &self.config
+    }
+
+    // The 'produce' function builds the child of `parent` from the transactions of `source`, applying
+    // them under the base fee following `parent` and the block reward to a copy of `state`, which
+    // replaces `state` once the block is complete
+    pub fn produce<S: State + Clone, T: TransactionSource>(
+        &self,
+        parent: &BlockHeader,
+        difficulty: U256,
+        timestamp: Timestamp,
+        source: &T,
+        state: &mut S,
+    ) -> Result<ProducedBlock, ProducerError> {
+        let base_fee = self.fee_market.next_base_fee(parent);
+        let mut post_state = state.clone();
+        let executor = Executor::new(self.config.coinbase)
+            .with_base_fee(base_fee)
+            .with_schedule(self.config.gas_schedule.clone());
+        let gas_limit = next_gas_limit(*parent.gas_limit(), self.config.gas_limit);
+        let mut meter = GasMeter::new(gas_limit);
+        let mut transactions = Vec::new();
+        let mut receipts = Vec::new();
+        let mut rejected = Vec::new();
+
+        for transaction in source.best_transactions(gas_limit, base_fee)
+        {
+            if transaction.raw_transaction().gas() > &meter.remaining()
+            {
+                continue;
+            }
+
+            match executor.execute(&mut post_state, &transaction)
+            {
+                Ok(outcome) => {
+                    meter.charge(*outcome.gas_used()).expect("Transactions use at most their gas limit");
+                    receipts.push(Receipt::from_outcome(outcome, meter.used()));
+                    transactions.push(transaction);
+                }
+                Err(e) => rejected.push((*transaction.get_hash(), e)),
+            }
+        }
+
+        let mut coinbase = post_state.account_or_default(&self.config.coinbase);
+        let balance = coinbase.balance().checked_add(self.config.block_reward).ok_or(ProducerError::RewardOverflow)?;
+        coinbase.set_balance(balance);
+        post_state.set_account(self.config.coinbase, coinbase);
+
+        let body = BlockBody::new(transactions);
+        let header = BlockHeaderBuilder::new()
+            .set_protocol_version(self.config.protocol_version)
+            .set_parent_hash(*parent.hash())
+            .set_block_number(parent.block_number() + 1)
+            .set_block_height(parent.block_height() + 1)
+            .set_difficulty(difficulty)
+            .set_timestamp(timestamp)
+            .set_total_difficulty(parent.total_difficulty().saturating_add(difficulty))
+            .set_base_fee(base_fee)
+            .set_gas_limit(gas_limit)
+            .set_gas_used(meter.used())
+            .set_coinbase(self.config.coinbase)
+            .set_state_root(post_state.state_root())
+            .set_transaction_root(body.compute_transaction_root())
+            .set_receipts_root(receipts_root(&receipts).map_err(ProducerError::ReceiptsRoot)?)
+            .set_logs_bloom(Bloom::from_receipts(&receipts))
+            .seal();
+
+        *state = post_state;
+        Ok(ProducedBlock { block: Block::new(header, body), receipts, rejected })
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::{gas_schedule::TRANSFER_GAS, state::MemoryState};
+    use crypto::ed25519::PrivateKey;
+    use primvites::{account::Account, transaction::{RawTransaction, Action, TransferAction}, Bytes};
+    use rand::rngs::OsRng;
+
+    const COINBASE: [u8; 20] = [0xCB; 20];
+
+    fn config() -> ProducerConfig
+    {
+        ProducerConfig {
+            coinbase: Address::from(COINBASE),
+            block_reward: U256::from(5_000),
+            gas_limit: Gas::new(2 * TRANSFER_GAS),
+            ..ProducerConfig::default()
+        }
+    }
+
+    fn parent() -> BlockHeader
+    {
+        parent_with_base_fee(Gas::new(0))
+    }
+
+    // A parent using its gas target, so that its child keeps the same base fee
+    fn parent_with_base_fee(base_fee: Gas) -> BlockHeader
+    {
+        BlockHeaderBuilder::new()
+            .set_block_number(7)
+            .set_block_height(7)
+            .set_difficulty(U256::from(100))
+            .set_total_difficulty(U256::from(700))
+            .set_base_fee(base_fee)
+            .set_gas_limit(Gas::new(2 * TRANSFER_GAS))
+            .set_gas_used(Gas::new(TRANSFER_GAS))
+            .seal()
+    }
+
+    fn transfer(private_key: &PrivateKey, nonce: u64, gas: u64) -> Arc<SignedTransaction>
+    {
+        let raw = RawTransaction::new(
+            1,
+            U256::from(nonce),
+            Action::Transfer(TransferAction { to: Address::from([7; 20]), amount: U256::from(10) }),
+            Gas::from(3),
+            Gas::from(gas),
+            U256::from(10),
+            Bytes::new(),
+        );
+        Arc::new(raw.sign(private_key))
+    }
+
+    fn funded_sender(state: &mut MemoryState) -> PrivateKey
+    {
+        let mut csprng: OsRng = OsRng;
+        let private_key = PrivateKey::generate(&mut csprng);
+        state.set_account(private_key.to_public_key().to_address(), Account::with_balance(U256::from(1_000_000)));
+        private_key
+    }
+
+    #[test]
+    fn test_produce_block()
+    {
+        let mut state = MemoryState::new();
+        let private_key = funded_sender(&mut state);
+        let source = vec![transfer(&private_key, 0, TRANSFER_GAS), transfer(&private_key, 1, TRANSFER_GAS)];
+        let parent = parent();
+
+        let produced = BlockProducer::new(config())
+            .produce(&parent, U256::from(120), Timestamp::from(1_700_000_000), &source, &mut state)
+            .unwrap();
+        let block = produced.block();
+        let header = block.header();
+
+        assert_eq!(block.body().transaction(), &source[..]);
+        assert_eq!(header.gas_used(), &Gas::new(2 * TRANSFER_GAS));
+        assert_eq!(header.gas_limit(), &Gas::new(2 * TRANSFER_GAS));
+        assert!(block.verify_transaction_root());
+        assert!(header.verify_hash());
+        assert_eq!(header.parent_hash(), parent.hash());
+        assert_eq!(header.block_number(), 8);
+        assert_eq!(header.block_height(), 8);
+        assert_eq!(header.difficulty(), &U256::from(120));
+        assert_eq!(header.total_difficulty(), &U256::from(820));
+        assert_eq!(header.timestamp(), &Timestamp::from(1_700_000_000));
+        assert_eq!(header.base_fee(), &Gas::new(0));
+        assert_eq!(header.receipts_root(), &receipts_root(produced.receipts()).unwrap());
+        assert_eq!(header.logs_bloom(), &Bloom::from_receipts(produced.receipts()));
+        assert_eq!(header.coinbase(), &Address::from(COINBASE));
+        assert_eq!(produced.receipts()[1].cumulative_gas_used(), &Gas::new(2 * TRANSFER_GAS));
+
+        // The coinbase receives the fees and the block reward.
+        assert_eq!(state.balance(&Address::from(COINBASE)), U256::from(6 * TRANSFER_GAS + 5_000));
+        assert_eq!(header.state_root(), &state.state_root());
+    }
+
+    #[test]
+    fn test_produce_stops_at_gas_limit()
+    {
+        let mut state = MemoryState::new();
+        let private_key = funded_sender(&mut state);
+        let other_key = funded_sender(&mut state);
+        let source = vec![
+            transfer(&private_key, 0, TRANSFER_GAS),
+            transfer(&private_key, 1, 2 * TRANSFER_GAS),
+            transfer(&other_key, 0, TRANSFER_GAS),
+        ];
+
+        let produced = BlockProducer::new(config())
+            .produce(&parent(), U256::from(100), Timestamp::from(1_700_000_000), &source, &mut state)
+            .unwrap();
+        assert_eq!(produced.block().body().transaction(), &[source[0].clone(), source[2].clone()]);
+        assert!(produced.rejected().is_empty());
+    }
+
+    #[test]
+    fn test_produce_skips_rejected_transactions()
+    {
+        let mut state = MemoryState::new();
+        let private_key = funded_sender(&mut state);
+        let source = vec![transfer(&private_key, 1, TRANSFER_GAS), transfer(&private_key, 0, TRANSFER_GAS)];
+
+        let produced = BlockProducer::new(config())
+            .produce(&parent(), U256::from(100), Timestamp::from(1_700_000_000), &source, &mut state)
+            .unwrap();
+        assert_eq!(produced.block().body().transaction(), &[source[1].clone()]);
+        assert_eq!(
+            produced.rejected(),
+            &[(*source[0].get_hash(), ExecutionError::NonceMismatch { expected: U256::zero(), found: U256::one() })]
+        );
+    }
+
+    #[test]
+    fn test_produce_empty_block()
+    {
+        let mut state = MemoryState::new();
+        let produced = BlockProducer::new(config())
+            .produce(&parent(), U256::from(100), Timestamp::from(1_700_000_000), &Vec::new(), &mut state)
+            .unwrap();
+
+        assert!(produced.block().body().transaction().is_empty());
+        assert_eq!(produced.block().header().gas_used(), &Gas::new(0));
+        assert_eq!(state.balance(&Address::from(COINBASE)), U256::from(5_000));
+    }
+
+    #[test]
+    fn test_produce_rejects_reward_overflow()
+    {
+        let mut state = MemoryState::new();
+        let private_key = funded_sender(&mut state);
+        // The coinbase can take the tip of the transfer, but not the block reward on top of it.
+        state.set_account(Address::from(COINBASE), Account::with_balance(U256::MAX - U256::from(3 * TRANSFER_GAS)));
+        let source = vec![transfer(&private_key, 0, TRANSFER_GAS)];
+        let before = state.clone();
+
+        assert_eq!(
+            BlockProducer::new(config())
+                .produce(&parent(), U256::from(100), Timestamp::from(1_700_000_000), &source, &mut state),
+            Err(ProducerError::RewardOverflow)
+        );
+        // The transactions executed before the failure are not applied.
+        assert_eq!(state, before);
+    }
+
+    #[test]
+    fn test_produce_burns_base_fee()
+    {
+        let mut state = MemoryState::new();
+        let private_key = funded_sender(&mut state);
+        let source = vec![transfer(&private_key, 0, TRANSFER_GAS)];
+
+        let parent = parent_with_base_fee(Gas::new(2));
+        let produced = BlockProducer::new(config())
+            .produce(&parent, U256::from(100), Timestamp::from(1_700_000_000), &source, &mut state)
+            .unwrap();
+        assert_eq!(produced.block().header().base_fee(), &Gas::new(2));
+        assert_eq!(produced.block().body().transaction(), &source[..]);
+
+        // Only the tip above the base fee reaches the coinbase.
+        assert_eq!(state.balance(&Address::from(COINBASE)), U256::from(TRANSFER_GAS + 5_000));
+
+        // A transaction whose price is below the base fee is rejected.
+        let source = vec![transfer(&private_key, 1, TRANSFER_GAS)];
+        let parent = parent_with_base_fee(Gas::new(4));
+        let produced = BlockProducer::new(config())
+            .produce(&parent, U256::from(100), Timestamp::from(1_700_000_000), &source, &mut state)
+            .unwrap();
+        assert!(produced.block().body().transaction().is_empty());
+        assert_eq!(produced.rejected().len(), 1);
+    }
+}
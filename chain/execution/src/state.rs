@@ -0,0 +1,174 @@
+//! # Account State
+//!
+//! This module defines the `State` trait, the view of account state the executor reads and writes,
+//! and `MemoryState`, an in-memory implementation. Accounts that were never written are treated as
+//! empty accounts.
+//!
+//! The state root commits to the state: it is the Merkle root of the non-empty accounts in address
+//! order, each leaf hashing the address followed by the canonical encoding of the account. Empty
+//! accounts are left out, so that writing an empty account does not change the root.
+
+use crypto::{hash::{Algorithm, HashDigest, Hasher}, merkle::merkle_root};
+use primvites::{account::Account, codec::Codec, Address, U256};
+use std::collections::BTreeMap;
+
+// 'State' defines read and write access to accounts.
+pub trait State
+{
+    // The 'account' function returns the account stored at the given address
+    fn account(&self, address: &Address) -> Option<Account>;
+
+    // The 'set_account' function stores an account at the given address
+    fn set_account(&mut self, address: Address, account: Account);
+
+    // The 'remove_account' function deletes the account at the given address
+    fn remove_account(&mut self, address: &Address);
+
+    // The 'state_root' function returns the Merkle root committing to every non-empty account
+    fn state_root(&self) -> HashDigest;
+
+    // The 'account_or_default' function returns the account at the given address, or an empty account
+    fn account_or_default(&self, address: &Address) -> Account
+    {
+        self.account(address).unwrap_or_default()
+    }
+
+    // The 'balance' function returns the balance of the account at the given address
+    fn balance(&self, address: &Address) -> U256
+    {
+        *self.account_or_default(address).balance()
+    }
+
+    // The 'nonce' function returns the nonce of the account at the given address
+    fn nonce(&self, address: &Address) -> U256
+    {
+        *self.account_or_default(address).nonce()
+    }
+}
+
+// The 'account_leaf' function hashes an account along with its address, as a leaf of the state root
+pub fn account_leaf(address: &Address, account: &Account) -> HashDigest
+{
+    let mut hasher = Hasher::new(Algorithm::SHA256);
+    hasher
+        .update(address.as_ref())
+        .update(&account.encode().expect("Accounts fit in the encoding limit"));
+    hasher.finalize()
+}
+
+// An in-memory account state
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryState
+{
+    accounts: BTreeMap<Address, Account>,
+}
+
+impl MemoryState
+{
+    // The 'new' function creates an empty state
+    pub fn new() -> Self
+    {
+        MemoryState { accounts: BTreeMap::new() }
+    }
+
+    // The 'len' function returns the number of stored accounts
+    pub fn len(&self) -> usize
+    {
+        self.accounts.len()
+    }
+
+    // The 'is_empty' function checks if no account is stored
+    pub fn is_empty(&self) -> bool
+    {
+        self.accounts.is_empty()
+    }
+
+    // The 'iter' function iterates over the stored accounts in address order
+    pub fn iter(&self) -> impl Iterator<Item = (&Address, &Account)>
+    {
+        self.accounts.iter()
+    }
+}
+
+impl State for MemoryState
+{
+    fn account(&self, address: &Address) -> Option<Account>
+    {
+        self.accounts.get(address).cloned()
+    }
+
+    fn set_account(&mut self, address: Address, account: Account)
+    {
+        self.accounts.insert(address, account);
+    }
+
+    fn remove_account(&mut self, address: &Address)
+    {
+        self.accounts.remove(address);
+    }
+
+    fn state_root(&self) -> HashDigest
+    {
+        let leaves: Vec<HashDigest> = self.accounts
+            .iter()
+            .filter(|(_, account)| !account.is_empty())
+            .map(|(address, account)| account_leaf(address, account))
+            .collect();
+        merkle_root(&leaves)
+    }
+}
+
+impl FromIterator<(Address, Account)> for MemoryState
+{
+    fn from_iter<I: IntoIterator<Item = (Address, Account)>>(iter: I) -> Self
+    {
+        MemoryState { accounts: iter.into_iter().collect() }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_missing_account_is_empty()
+    {
+        let state = MemoryState::new();
+        let address = Address::from([1; 20]);
+
+        assert_eq!(state.account(&address), None);
+        assert_eq!(state.balance(&address), U256::zero());
+        assert_eq!(state.nonce(&address), U256::zero());
+    }
+
+    #[test]
+    fn test_set_and_remove_account()
+    {
+        let mut state = MemoryState::new();
+        let address = Address::from([1; 20]);
+
+        state.set_account(address, Account::with_balance(U256::from(10)));
+        assert_eq!(state.balance(&address), U256::from(10));
+        assert_eq!(state.len(), 1);
+
+        state.remove_account(&address);
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn test_state_root()
+    {
+        let mut state = MemoryState::new();
+        let empty_root = state.state_root();
+        state.set_account(Address::from([1; 20]), Account::with_balance(U256::from(10)));
+        let root = state.state_root();
+        assert_ne!(root, empty_root);
+
+        // Empty accounts do not change the root, any other change does.
+        state.set_account(Address::from([2; 20]), Account::default());
+        assert_eq!(state.state_root(), root);
+        state.set_account(Address::from([1; 20]), Account::with_balance(U256::from(11)));
+        assert_ne!(state.state_root(), root);
+    }
+}
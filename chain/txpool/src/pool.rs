@@ -0,0 +1,909 @@
+//! # Transaction Pool
+//!
+//! This module holds `SignedTransaction`s between their receipt and their inclusion in a block.
+//!
+//! Incoming transactions are checked for their chain identifier, signature, fees and intrinsic gas,
+//! and against the state of their sender: the nonce must not be used yet and the balance must cover
+//! `value + gas * max_fee_per_gas`. Transactions are queued per sender in nonce order. The ones whose
+//! nonces follow the sender nonce without gap are pending and can be included in the next block; the
+//! others are future transactions waiting for the gap to be filled.
+//!
+//! A transaction with the nonce of a queued one replaces it if both its fee cap and its tip are higher
+//! by at least `price_bump` percent. The size of a transaction is computed from its contents when it
+//! is admitted. When the pool exceeds `max_size` bytes, the transaction at the end of a sender queue
+//! tipping the least under the current base fee is evicted, so that no gap is created. A transaction that would be evicted itself is refused with `PoolFull` before anything is
+//! evicted, leaving the pool unchanged. Transactions older than `max_age` seconds are evicted by
+//! `evict_expired`. The age of transactions is read from the `Clock` of the pool, the system clock
+//! unless another one is set with `with_clock`.
+//!
+//! `best_transactions` returns pending transactions by decreasing effective tip under a base fee, in
+//! nonce order per sender, fitting a gas limit; a sender is skipped from its first transaction whose
+//! fee cap is below the base fee. It is what block producers pull from as a `TransactionSource`. Once a
+//! block is imported, `prune` drops the transactions it included and `set_base_fee` records the base
+//! fee of the next block.
+
+use core_utils::{gas::Gas, timestamp::{Clock, SystemClock, Timestamp}};
+use crypto::hash::HashDigest;
+use execution::{gas_schedule::GasSchedule, producer::TransactionSource, state::State};
+use primvites::{
+    Address, U256,
+    transaction::{SignedTransaction, TransactionError},
+};
+use serde::{Serialize, Deserialize};
+use std::{cmp::Reverse, collections::{BTreeMap, BinaryHeap, HashMap}, sync::Arc, time::Duration};
+
+// Represents the reasons a transaction is not accepted by the pool.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PoolError
+{
+    InvalidTransaction(TransactionError),
+    AlreadyKnown(HashDigest),
+    WrongChainId { expected: u32, found: u32 },
+    GasPriceTooLow { minimum: Gas, found: Gas },
+    TipAboveFeeCap { max_priority_fee_per_gas: Gas, max_fee_per_gas: Gas },
+    IntrinsicGasTooLow { required: Gas, provided: Gas },
+    NonceTooLow { expected: U256, found: U256 },
+    InsufficientBalance { required: U256, available: U256 },
+    ReplacementUnderpriced { minimum: Gas, found: Gas },
+    PoolFull,
+}
+
+impl std::fmt::Display for PoolError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            PoolError::InvalidTransaction(e) => write!(f, "Invalid transaction: {}", e),
+            PoolError::AlreadyKnown(hash) => write!(f, "Transaction already known: {}", hash),
+            PoolError::WrongChainId { expected, found } =>
+                write!(f, "Invalid chain id: expected {}, found {}", expected, found),
+            PoolError::GasPriceTooLow { minimum, found } =>
+                write!(f, "Gas price too low: minimum {}, found {}", minimum, found),
+            PoolError::TipAboveFeeCap { max_priority_fee_per_gas, max_fee_per_gas } =>
+                write!(f, "Max priority fee per gas {} exceeds the max fee per gas {}", max_priority_fee_per_gas, max_fee_per_gas),
+            PoolError::IntrinsicGasTooLow { required, provided } =>
+                write!(f, "Intrinsic gas too low: required {}, provided {}", required, provided),
+            PoolError::NonceTooLow { expected, found } =>
+                write!(f, "Nonce too low: expected at least {}, found {}", expected, found),
+            PoolError::InsufficientBalance { required, available } =>
+                write!(f, "Insufficient balance: required {}, available {}", required, available),
+            PoolError::ReplacementUnderpriced { minimum, found } =>
+                write!(f, "Replacement underpriced: minimum fee per gas {}, found {}", minimum, found),
+            PoolError::PoolFull => write!(f, "Transaction pool is full!"),
+        }
+    }
+}
+
+impl std::error::Error for PoolError {}
+
+// Transaction pool config
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoolConfig
+{
+    // The chain identifier accepted transactions must carry.
+    // 1 by default.
+    pub chain_id: u32,
+    // The lowest tip per gas accepted, which is the gas price of legacy transactions.
+    // 1 by default.
+    pub min_gas_price: u64,
+    // The percentage a replacement must raise the fee cap and tip by.
+    // 10 percent by default.
+    pub price_bump: u64,
+    // The total size of the pooled transactions in bytes.
+    // 32 MB by default.
+    pub max_size: usize,
+    // The number of seconds a transaction is kept.
+    // 3 hours by default.
+    pub max_age: u64,
+    // The gas costs the intrinsic gas of transactions is checked against.
+    // The default schedule by default.
+    pub gas_schedule: GasSchedule,
+}
+
+impl Default for PoolConfig
+{
+    fn default() -> Self
+    {
+        Self {
+            chain_id: 1,
+            min_gas_price: 1,
+            price_bump: 10,
+            // 32 MB in bytes
+            max_size: 32 * 1024 * 1024,
+            // 3 hours in seconds
+            max_age: 3 * 60 * 60,
+            gas_schedule: GasSchedule::default(),
+        }
+    }
+}
+
+// A pooled transaction
+#[derive(Clone, Debug)]
+struct PoolEntry
+{
+    transaction: Arc<SignedTransaction>,
+    inserted: Timestamp,
+    // Size of the transaction, computed from its contents when it was admitted
+    size: usize,
+    // Insertion order, breaking ties between equal tips
+    sequence: u64,
+}
+
+impl PoolEntry
+{
+    // The 'effective_tip' function returns the tip per gas paid under a base fee, if the fee cap covers it
+    fn effective_tip(&self, base_fee: Gas) -> Option<u64>
+    {
+        self.transaction.raw_transaction().fee().effective_tip(base_fee).map(|tip| tip.amount())
+    }
+}
+
+// The 'bumped' function returns the lowest price a replacement must offer over `price`
+fn bumped(price: Gas, price_bump: u64) -> Gas
+{
+    let price = price.amount();
+    Gas::new(price.saturating_add(price.saturating_mul(price_bump) / 100).max(price.saturating_add(1)))
+}
+
+// The transactions of a sender, by nonce
+#[derive(Clone, Debug, Default)]
+struct SenderQueue
+{
+    // The nonce of the sender in the state
+    state_nonce: U256,
+    transactions: BTreeMap<U256, PoolEntry>,
+}
+
+impl SenderQueue
+{
+    // The 'pending' function iterates over the transactions following the state nonce without gap
+    fn pending(&self) -> impl Iterator<Item = &PoolEntry>
+    {
+        let mut expected = self.state_nonce;
+        self.transactions.range(self.state_nonce..).map_while(move |(nonce, entry)| {
+            if *nonce != expected
+            {
+                return None;
+            }
+            expected = expected.saturating_add(U256::one());
+            Some(entry)
+        })
+    }
+}
+
+// Holds validated transactions until they are included in a block
+pub struct TransactionPool
+{
+    config: PoolConfig,
+    senders: HashMap<Address, SenderQueue>,
+    by_hash: HashMap<HashDigest, (Address, U256)>,
+    size: usize,
+    sequence: u64,
+    base_fee: Gas,
+    clock: Arc<dyn Clock>,
+}
+
+impl TransactionPool
+{
+    // The 'new' function creates an empty pool
+    pub fn new(config: PoolConfig) -> Self
+    {
+        TransactionPool {
+            config,
+            senders: HashMap::new(),
+            by_hash: HashMap::new(),
+            size: 0,
+            sequence: 0,
+            base_fee: Gas::new(0),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    // The 'with_clock' function sets the clock transactions are timestamped with
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self
+    {
+        self.clock = clock;
+        self
+    }
+
+    // The 'config' function returns the config of the pool
+    pub fn config(&self) -> &PoolConfig
+    {
+        &self.config
+    }
+
+    // The 'base_fee' function returns the base fee eviction ranks transactions under
+    pub fn base_fee(&self) -> &Gas
+    {
+        &self.base_fee
+    }
+
+    // The 'set_base_fee' function sets the base fee of the next block
+    pub fn set_base_fee(&mut self, base_fee: Gas)
+    {
+        self.base_fee = base_fee;
+    }
+
+    // The 'len' function returns the number of pooled transactions
+    pub fn len(&self) -> usize
+    {
+        self.by_hash.len()
+    }
+
+    // The 'is_empty' function checks if no transaction is pooled
+    pub fn is_empty(&self) -> bool
+    {
+        self.by_hash.is_empty()
+    }
+
+    // The 'size' function returns the total size of the pooled transactions in bytes
+    pub fn size(&self) -> usize
+    {
+        self.size
+    }
+
+    // The 'contains' function checks whether a transaction is pooled
+    pub fn contains(&self, hash: &HashDigest) -> bool
+    {
+        self.by_hash.contains_key(hash)
+    }
+
+    // The 'get' function returns a pooled transaction by hash
+    pub fn get(&self, hash: &HashDigest) -> Option<Arc<SignedTransaction>>
+    {
+        let (sender, nonce) = self.by_hash.get(hash)?;
+        Some(self.senders[sender].transactions[nonce].transaction.clone())
+    }
+
+    // The 'pending' function returns the transactions that can be included in the next block
+    pub fn pending(&self) -> Vec<Arc<SignedTransaction>>
+    {
+        self.senders
+            .values()
+            .flat_map(|queue| queue.pending().map(|entry| entry.transaction.clone()))
+            .collect()
+    }
+
+    // The 'future' function returns the transactions waiting for a nonce gap to be filled
+    pub fn future(&self) -> Vec<Arc<SignedTransaction>>
+    {
+        self.senders
+            .values()
+            .flat_map(|queue| {
+                let pending = queue.pending().count();
+                queue.transactions
+                    .range(queue.state_nonce..)
+                    .skip(pending)
+                    .map(|(_, entry)| entry.transaction.clone())
+            })
+            .collect()
+    }
+
+    // The 'add' function validates a transaction against the state and pools it.
+    // Returns the transaction it replaced, if any.
+    pub fn add<S: State>(
+        &mut self,
+        transaction: SignedTransaction,
+        state: &S,
+    ) -> Result<Option<Arc<SignedTransaction>>, PoolError> {
+        let hash = *transaction.get_hash();
+        if self.contains(&hash)
+        {
+            return Err(PoolError::AlreadyKnown(hash));
+        }
+        self.validate(&transaction, state)?;
+
+        let sender = transaction.sender();
+        let raw = transaction.raw_transaction();
+        let nonce = *raw.nonce();
+        let offered = [raw.max_fee_per_gas(), raw.max_priority_fee_per_gas()];
+
+        let queue = self.senders.entry(sender).or_default();
+        queue.state_nonce = state.nonce(&sender);
+        if let Some(existing) = queue.transactions.get(&nonce)
+        {
+            let existing = existing.transaction.raw_transaction();
+            let current = [existing.max_fee_per_gas(), existing.max_priority_fee_per_gas()];
+            for (found, price) in offered.into_iter().zip(current)
+            {
+                let minimum = bumped(price, self.config.price_bump);
+                if found.amount() < minimum.amount()
+                {
+                    return Err(PoolError::ReplacementUnderpriced { minimum, found });
+                }
+            }
+        }
+
+        let entry = PoolEntry {
+            size: transaction.get_size() as usize,
+            transaction: Arc::new(transaction),
+            inserted: self.clock.now(),
+            sequence: self.sequence,
+        };
+        self.sequence += 1;
+        self.size += entry.size;
+        self.by_hash.insert(hash, (sender, nonce));
+        let replaced = queue.transactions.insert(nonce, entry);
+        if let Some(replaced) = &replaced
+        {
+            self.size -= replaced.size;
+            self.by_hash.remove(replaced.transaction.get_hash());
+        }
+
+        let evicted = self.eviction_plan();
+        if evicted.contains(&hash)
+        {
+            self.undo_insert(sender, nonce, replaced);
+            return Err(PoolError::PoolFull);
+        }
+        for hash in &evicted
+        {
+            self.remove(hash);
+        }
+        Ok(replaced.map(|replaced| replaced.transaction))
+    }
+
+    // The 'remove' function removes a transaction, along with the later transactions of its sender
+    // that would otherwise follow a gap
+    pub fn remove(&mut self, hash: &HashDigest) -> Vec<Arc<SignedTransaction>>
+    {
+        let Some((sender, nonce)) = self.by_hash.get(hash).copied() else { return Vec::new() };
+        let queue = self.senders.get_mut(&sender).expect("Pooled transactions have a sender queue");
+        let removed: Vec<PoolEntry> = if nonce < queue.state_nonce
+        {
+            queue.transactions.remove(&nonce).into_iter().collect()
+        } else {
+            queue.transactions.split_off(&nonce).into_values().collect()
+        };
+        self.forget(&sender, &removed);
+        removed.into_iter().map(|entry| entry.transaction).collect()
+    }
+
+    // The 'prune' function drops the transactions whose nonces were used, after a block import
+    pub fn prune<S: State>(&mut self, state: &S) -> Vec<Arc<SignedTransaction>>
+    {
+        let mut pruned = Vec::new();
+        let senders: Vec<Address> = self.senders.keys().copied().collect();
+        for sender in senders
+        {
+            let queue = self.senders.get_mut(&sender).expect("Sender is pooled");
+            queue.state_nonce = state.nonce(&sender);
+            let kept = queue.transactions.split_off(&queue.state_nonce);
+            let used = std::mem::replace(&mut queue.transactions, kept);
+            let used: Vec<PoolEntry> = used.into_values().collect();
+            self.forget(&sender, &used);
+            pruned.extend(used.into_iter().map(|entry| entry.transaction));
+        }
+        pruned
+    }
+
+    // The 'evict_expired' function drops the transactions pooled for longer than `max_age`, along with
+    // the later transactions of their senders
+    pub fn evict_expired(&mut self) -> Vec<Arc<SignedTransaction>>
+    {
+        let now = self.clock.now();
+        let max_age = Duration::from_secs(self.config.max_age);
+        let expired: Vec<HashDigest> = self.senders
+            .values()
+            .flat_map(|queue| queue.transactions.values())
+            .filter(|entry| now.saturating_duration_since(entry.inserted) > max_age)
+            .map(|entry| *entry.transaction.get_hash())
+            .collect();
+
+        expired.iter().flat_map(|hash| self.remove(hash)).collect()
+    }
+
+    // The 'best_transactions' function returns pending transactions by decreasing effective tip under
+    // `base_fee`, in nonce order per sender, whose gas limits fit in the given limit
+    pub fn best_transactions(&self, gas_limit: Gas, base_fee: Gas) -> Vec<Arc<SignedTransaction>>
+    {
+        // A sender stops at its first transaction that cannot pay the base fee.
+        let mut pending: HashMap<&Address, Vec<(u64, &PoolEntry)>> = self.senders
+            .iter()
+            .map(|(sender, queue)| {
+                let entries = queue.pending()
+                    .map_while(|entry| entry.effective_tip(base_fee).map(|tip| (tip, entry)))
+                    .collect::<Vec<_>>();
+                (sender, entries)
+            })
+            .filter(|(_, entries)| !entries.is_empty())
+            .collect();
+        for entries in pending.values_mut()
+        {
+            entries.reverse();
+        }
+
+        let mut heads: BinaryHeap<(u64, Reverse<u64>, &Address)> = pending
+            .iter()
+            .map(|(sender, entries)| {
+                let (tip, head) = entries.last().expect("Queues are not empty");
+                (*tip, Reverse(head.sequence), *sender)
+            })
+            .collect();
+
+        let mut remaining = gas_limit.amount();
+        let mut best = Vec::new();
+        while let Some((_, _, sender)) = heads.pop()
+        {
+            let entries = pending.get_mut(sender).expect("Heads come from pending queues");
+            let (_, entry) = entries.pop().expect("Heads are queued");
+            let gas = entry.transaction.raw_transaction().gas().amount();
+            if gas > remaining
+            {
+                // Later nonces of this sender cannot be included without this one.
+                continue;
+            }
+            remaining -= gas;
+            best.push(entry.transaction.clone());
+            if let Some((tip, next)) = entries.last()
+            {
+                heads.push((*tip, Reverse(next.sequence), sender));
+            }
+        }
+        best
+    }
+
+    // The 'validate' function checks a transaction on its own and against the state of its sender
+    fn validate<S: State>(&self, transaction: &SignedTransaction, state: &S) -> Result<(), PoolError>
+    {
+        transaction.verify_for_chain(self.config.chain_id).map_err(|e| match e
+        {
+            TransactionError::WrongChainId { expected, found } => PoolError::WrongChainId { expected, found },
+            e => PoolError::InvalidTransaction(e),
+        })?;
+
+        let raw = transaction.raw_transaction();
+        let max_fee_per_gas = raw.max_fee_per_gas();
+        let max_priority_fee_per_gas = raw.max_priority_fee_per_gas();
+        if max_priority_fee_per_gas.amount() > max_fee_per_gas.amount()
+        {
+            return Err(PoolError::TipAboveFeeCap { max_priority_fee_per_gas, max_fee_per_gas });
+        }
+        if max_priority_fee_per_gas.amount() < self.config.min_gas_price
+        {
+            return Err(PoolError::GasPriceTooLow { minimum: Gas::new(self.config.min_gas_price), found: max_priority_fee_per_gas });
+        }
+        // A payload whose cost overflows can never be paid for.
+        let intrinsic_gas = self.config.gas_schedule.intrinsic_gas(raw).unwrap_or(Gas::MAX);
+        if raw.gas() < &intrinsic_gas
+        {
+            return Err(PoolError::IntrinsicGasTooLow { required: intrinsic_gas, provided: *raw.gas() });
+        }
+
+        let sender = transaction.sender();
+        let expected = state.nonce(&sender);
+        if raw.nonce() < &expected
+        {
+            return Err(PoolError::NonceTooLow { expected, found: *raw.nonce() });
+        }
+        let required = U256::from(raw.gas().amount())
+            .saturating_mul(U256::from(max_fee_per_gas.amount()))
+            .saturating_add(*raw.value());
+        let available = state.balance(&sender);
+        if available < required
+        {
+            return Err(PoolError::InsufficientBalance { required, available });
+        }
+        Ok(())
+    }
+
+    // The 'eviction_plan' function returns the sender queue tails tipping the least under the base fee
+    // that must be evicted, in order, for the pool to fit `max_size`. Transactions that cannot pay the
+    // base fee go first.
+    fn eviction_plan(&self) -> Vec<HashDigest>
+    {
+        let base_fee = self.base_fee;
+        let mut queues: Vec<Vec<&PoolEntry>> = self.senders
+            .values()
+            .map(|queue| queue.transactions.values().collect())
+            .collect();
+        let mut size = self.size;
+        let mut plan = Vec::new();
+        while size > self.config.max_size
+        {
+            let cheapest = queues
+                .iter()
+                .enumerate()
+                .filter_map(|(index, queue)| queue.last().map(|entry| (index, entry)))
+                .min_by_key(|(_, entry)| (entry.effective_tip(base_fee), Reverse(entry.sequence)))
+                .map(|(index, _)| index);
+            let Some(index) = cheapest else { break };
+            let entry = queues[index].pop().expect("The cheapest queue is not empty");
+            size -= entry.size;
+            plan.push(*entry.transaction.get_hash());
+        }
+        plan
+    }
+
+    // The 'undo_insert' function removes the transaction just pooled by a sender at `nonce`, pooling
+    // back the entry it replaced as it was
+    fn undo_insert(&mut self, sender: Address, nonce: U256, replaced: Option<PoolEntry>)
+    {
+        let queue = self.senders.get_mut(&sender).expect("Pooled transactions have a sender queue");
+        let removed: Vec<PoolEntry> = queue.transactions.remove(&nonce).into_iter().collect();
+        if let Some(replaced) = replaced
+        {
+            self.size += replaced.size;
+            self.by_hash.insert(*replaced.transaction.get_hash(), (sender, nonce));
+            queue.transactions.insert(nonce, replaced);
+        }
+        self.forget(&sender, &removed);
+    }
+
+    // The 'forget' function updates the indexes after entries of a sender were removed
+    fn forget(&mut self, sender: &Address, removed: &[PoolEntry])
+    {
+        for entry in removed
+        {
+            self.size -= entry.size;
+            self.by_hash.remove(entry.transaction.get_hash());
+        }
+        if self.senders.get(sender).is_some_and(|queue| queue.transactions.is_empty())
+        {
+            self.senders.remove(sender);
+        }
+    }
+}
+
+impl TransactionSource for TransactionPool
+{
+    fn best_transactions(&self, gas_limit: Gas, base_fee: Gas) -> Vec<Arc<SignedTransaction>>
+    {
+        TransactionPool::best_transactions(self, gas_limit, base_fee)
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use core_utils::timestamp::MockClock;
+    use crypto::ed25519::PrivateKey;
+    use execution::{gas_schedule::TRANSFER_GAS, state::MemoryState};
+    use primvites::{account::Account, transaction::{Action, RawTransaction, TransactionFee, TransferAction}, Bytes};
+    use rand::rngs::OsRng;
+
+    struct Sender
+    {
+        private_key: PrivateKey,
+        address: Address,
+    }
+
+    impl Sender
+    {
+        fn new(state: &mut MemoryState) -> Self
+        {
+            let mut csprng: OsRng = OsRng;
+            let private_key = PrivateKey::generate(&mut csprng);
+            let address = private_key.to_public_key().to_address();
+            state.set_account(address, Account::with_balance(U256::from(10_000_000)));
+            Sender { private_key, address }
+        }
+
+        fn transaction(&self, nonce: u64, gas_price: u64) -> SignedTransaction
+        {
+            self.transaction_with(1, nonce, gas_price, TRANSFER_GAS)
+        }
+
+        fn transaction_with(&self, chain_id: u32, nonce: u64, gas_price: u64, gas: u64) -> SignedTransaction
+        {
+            self.sign(chain_id, nonce, TransactionFee::Legacy { gas_price: Gas::from(gas_price) }, gas, Bytes::new())
+        }
+
+        fn dynamic(&self, nonce: u64, max_fee_per_gas: u64, max_priority_fee_per_gas: u64) -> SignedTransaction
+        {
+            let fee = TransactionFee::Dynamic {
+                max_fee_per_gas: Gas::from(max_fee_per_gas),
+                max_priority_fee_per_gas: Gas::from(max_priority_fee_per_gas),
+            };
+            self.sign(1, nonce, fee, TRANSFER_GAS, Bytes::new())
+        }
+
+        // A transaction carrying `data_len` bytes of data
+        fn large(&self, nonce: u64, gas_price: u64, data_len: usize) -> SignedTransaction
+        {
+            let fee = TransactionFee::Legacy { gas_price: Gas::from(gas_price) };
+            let gas = TRANSFER_GAS + PoolConfig::default().gas_schedule.data_byte.amount() * data_len as u64;
+            self.sign(1, nonce, fee, gas, vec![0xAB; data_len])
+        }
+
+        fn sign(&self, chain_id: u32, nonce: u64, fee: TransactionFee, gas: u64, data: Bytes) -> SignedTransaction
+        {
+            let raw = RawTransaction::with_fee(
+                chain_id,
+                U256::from(nonce),
+                Action::Transfer(TransferAction { to: Address::from([7; 20]), amount: U256::from(1) }),
+                fee,
+                Gas::from(gas),
+                U256::from(1),
+                data,
+            );
+            raw.sign(&self.private_key)
+        }
+    }
+
+    fn hashes(transactions: &[Arc<SignedTransaction>]) -> Vec<HashDigest>
+    {
+        transactions.iter().map(|transaction| *transaction.get_hash()).collect()
+    }
+
+    #[test]
+    fn test_validation()
+    {
+        let mut state = MemoryState::new();
+        let sender = Sender::new(&mut state);
+        let mut pool = TransactionPool::new(PoolConfig { min_gas_price: 2, ..PoolConfig::default() });
+
+        assert_eq!(
+            pool.add(sender.transaction_with(2, 0, 2, TRANSFER_GAS), &state),
+            Err(PoolError::WrongChainId { expected: 1, found: 2 })
+        );
+        assert_eq!(
+            pool.add(sender.transaction(0, 1), &state),
+            Err(PoolError::GasPriceTooLow { minimum: Gas::new(2), found: Gas::new(1) })
+        );
+        assert_eq!(
+            pool.add(sender.dynamic(0, 5, 6), &state),
+            Err(PoolError::TipAboveFeeCap { max_priority_fee_per_gas: Gas::new(6), max_fee_per_gas: Gas::new(5) })
+        );
+        assert_eq!(
+            pool.add(sender.transaction_with(1, 0, 2, TRANSFER_GAS - 1), &state),
+            Err(PoolError::IntrinsicGasTooLow { required: Gas::new(TRANSFER_GAS), provided: Gas::new(TRANSFER_GAS - 1) })
+        );
+        assert_eq!(
+            pool.add(sender.transaction(0, 1_000), &state),
+            Err(PoolError::InsufficientBalance { required: U256::from(21_000_001), available: U256::from(10_000_000) })
+        );
+
+        let mut account = state.account_or_default(&sender.address);
+        account.set_nonce(U256::from(3));
+        state.set_account(sender.address, account);
+        assert_eq!(
+            pool.add(sender.transaction(2, 2), &state),
+            Err(PoolError::NonceTooLow { expected: U256::from(3), found: U256::from(2) })
+        );
+
+        let transaction = sender.transaction(3, 2);
+        assert_eq!(pool.add(transaction.clone(), &state), Ok(None));
+        assert_eq!(pool.add(transaction.clone(), &state), Err(PoolError::AlreadyKnown(*transaction.get_hash())));
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn test_pending_and_future()
+    {
+        let mut state = MemoryState::new();
+        let sender = Sender::new(&mut state);
+        let mut pool = TransactionPool::new(PoolConfig::default());
+
+        let first = sender.transaction(0, 1);
+        let third = sender.transaction(2, 1);
+        pool.add(first.clone(), &state).unwrap();
+        pool.add(third.clone(), &state).unwrap();
+        assert_eq!(hashes(&pool.pending()), vec![*first.get_hash()]);
+        assert_eq!(hashes(&pool.future()), vec![*third.get_hash()]);
+
+        // Filling the gap promotes the future transaction.
+        let second = sender.transaction(1, 1);
+        pool.add(second.clone(), &state).unwrap();
+        assert_eq!(hashes(&pool.pending()), vec![*first.get_hash(), *second.get_hash(), *third.get_hash()]);
+        assert!(pool.future().is_empty());
+
+        // Until the next prune, a transaction whose nonce was used is neither pending nor future.
+        let mut account = state.account_or_default(&sender.address);
+        account.set_nonce(U256::one());
+        state.set_account(sender.address, account);
+        let fifth = sender.transaction(4, 1);
+        pool.add(fifth.clone(), &state).unwrap();
+        assert_eq!(hashes(&pool.pending()), vec![*second.get_hash(), *third.get_hash()]);
+        assert_eq!(hashes(&pool.future()), vec![*fifth.get_hash()]);
+    }
+
+    #[test]
+    fn test_replace_by_fee()
+    {
+        let mut state = MemoryState::new();
+        let sender = Sender::new(&mut state);
+        let mut pool = TransactionPool::new(PoolConfig::default());
+
+        let original = sender.transaction(0, 100);
+        pool.add(original.clone(), &state).unwrap();
+        assert_eq!(
+            pool.add(sender.transaction(0, 109), &state),
+            Err(PoolError::ReplacementUnderpriced { minimum: Gas::new(110), found: Gas::new(109) })
+        );
+
+        let replacement = sender.transaction(0, 110);
+        let replaced = pool.add(replacement.clone(), &state).unwrap();
+        assert_eq!(replaced.map(|transaction| *transaction.get_hash()), Some(*original.get_hash()));
+        assert!(!pool.contains(original.get_hash()));
+        assert!(pool.contains(replacement.get_hash()));
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.size(), replacement.get_size() as usize);
+    }
+
+    #[test]
+    fn test_best_transactions()
+    {
+        let mut state = MemoryState::new();
+        let alice = Sender::new(&mut state);
+        let bob = Sender::new(&mut state);
+        let mut pool = TransactionPool::new(PoolConfig::default());
+
+        let alice0 = alice.transaction(0, 5);
+        let alice1 = alice.transaction(1, 50);
+        let bob0 = bob.transaction(0, 10);
+        let bob2 = bob.transaction(2, 100);
+        for transaction in [&alice0, &alice1, &bob0, &bob2]
+        {
+            pool.add(transaction.clone(), &state).unwrap();
+        }
+
+        // Bob pays more than Alice's first transaction; Bob's gapped transaction is left out.
+        assert_eq!(
+            hashes(&pool.best_transactions(Gas::new(10 * TRANSFER_GAS), Gas::new(0))),
+            vec![*bob0.get_hash(), *alice0.get_hash(), *alice1.get_hash()]
+        );
+        assert_eq!(
+            hashes(&pool.best_transactions(Gas::new(2 * TRANSFER_GAS), Gas::new(0))),
+            vec![*bob0.get_hash(), *alice0.get_hash()]
+        );
+        assert!(pool.best_transactions(Gas::new(TRANSFER_GAS - 1), Gas::new(0)).is_empty());
+    }
+
+    #[test]
+    fn test_replace_dynamic_fee()
+    {
+        let mut state = MemoryState::new();
+        let sender = Sender::new(&mut state);
+        let mut pool = TransactionPool::new(PoolConfig::default());
+
+        pool.add(sender.dynamic(0, 100, 10), &state).unwrap();
+        // Both the fee cap and the tip must be bumped.
+        assert_eq!(
+            pool.add(sender.dynamic(0, 200, 10), &state),
+            Err(PoolError::ReplacementUnderpriced { minimum: Gas::new(11), found: Gas::new(10) })
+        );
+        assert_eq!(
+            pool.add(sender.dynamic(0, 109, 20), &state),
+            Err(PoolError::ReplacementUnderpriced { minimum: Gas::new(110), found: Gas::new(109) })
+        );
+        assert!(pool.add(sender.dynamic(0, 110, 11), &state).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_best_transactions_by_tip()
+    {
+        let mut state = MemoryState::new();
+        let alice = Sender::new(&mut state);
+        let bob = Sender::new(&mut state);
+        let carol = Sender::new(&mut state);
+        let mut pool = TransactionPool::new(PoolConfig::default());
+
+        let alice0 = alice.dynamic(0, 100, 2);
+        let alice1 = alice.dynamic(1, 40, 30);
+        let bob0 = bob.transaction(0, 55);
+        let carol0 = carol.dynamic(0, 100, 20);
+        for transaction in [&alice0, &alice1, &bob0, &carol0]
+        {
+            pool.add(transaction.clone(), &state).unwrap();
+        }
+
+        // Without base fee, legacy transactions tip their whole gas price.
+        assert_eq!(
+            hashes(&pool.best_transactions(Gas::new(10 * TRANSFER_GAS), Gas::new(0))),
+            vec![*bob0.get_hash(), *carol0.get_hash(), *alice0.get_hash(), *alice1.get_hash()]
+        );
+        // Under a base fee of 50, Bob tips 5 and Alice cannot pay for her second transaction.
+        assert_eq!(
+            hashes(&pool.best_transactions(Gas::new(10 * TRANSFER_GAS), Gas::new(50))),
+            vec![*carol0.get_hash(), *bob0.get_hash(), *alice0.get_hash()]
+        );
+        // Under a base fee of 100, nobody tips and the oldest transaction goes first.
+        assert_eq!(
+            hashes(&pool.best_transactions(Gas::new(10 * TRANSFER_GAS), Gas::new(100))),
+            vec![*alice0.get_hash(), *carol0.get_hash()]
+        );
+    }
+
+    #[test]
+    fn test_prune_and_remove()
+    {
+        let mut state = MemoryState::new();
+        let sender = Sender::new(&mut state);
+        let mut pool = TransactionPool::new(PoolConfig::default());
+        let transactions: Vec<SignedTransaction> = (0..4).map(|nonce| sender.transaction(nonce, 1)).collect();
+        for transaction in &transactions
+        {
+            pool.add(transaction.clone(), &state).unwrap();
+        }
+
+        let mut account = state.account_or_default(&sender.address);
+        account.set_nonce(U256::from(2));
+        state.set_account(sender.address, account);
+        assert_eq!(hashes(&pool.prune(&state)), vec![*transactions[0].get_hash(), *transactions[1].get_hash()]);
+        assert_eq!(pool.pending().len(), 2);
+
+        // Removing a transaction also removes the later ones that would follow a gap.
+        assert_eq!(hashes(&pool.remove(transactions[2].get_hash())), vec![*transactions[2].get_hash(), *transactions[3].get_hash()]);
+        assert!(pool.is_empty());
+        assert_eq!(pool.size(), 0);
+    }
+
+    #[test]
+    fn test_size_is_computed_from_contents()
+    {
+        let mut state = MemoryState::new();
+        let sender = Sender::new(&mut state);
+        let mut pool = TransactionPool::new(PoolConfig::default());
+        pool.add(sender.large(0, 1, 100), &state).unwrap();
+        assert_eq!(pool.size(), std::mem::size_of::<SignedTransaction>() + 100);
+    }
+
+    #[test]
+    fn test_size_eviction()
+    {
+        let mut state = MemoryState::new();
+        let alice = Sender::new(&mut state);
+        let bob = Sender::new(&mut state);
+        let size = alice.transaction(0, 1).get_size() as usize;
+        let mut pool = TransactionPool::new(PoolConfig { max_size: 2 * size, ..PoolConfig::default() });
+
+        let alice0 = alice.transaction(0, 5);
+        let bob0 = bob.transaction(0, 3);
+        pool.add(alice0.clone(), &state).unwrap();
+        pool.add(bob0.clone(), &state).unwrap();
+
+        // A cheaper transaction does not fit.
+        assert_eq!(pool.add(alice.transaction(1, 2), &state), Err(PoolError::PoolFull));
+        assert_eq!(pool.len(), 2);
+
+        // A better paying one evicts the cheapest.
+        let alice1 = alice.transaction(1, 4);
+        pool.add(alice1.clone(), &state).unwrap();
+        assert!(!pool.contains(bob0.get_hash()));
+        assert!(pool.contains(alice0.get_hash()) && pool.contains(alice1.get_hash()));
+        assert_eq!(pool.size(), 2 * size);
+
+        // A transaction larger than the pool is refused before evicting cheaper ones.
+        let carol = Sender::new(&mut state);
+        assert_eq!(pool.add(carol.large(0, 6, 2 * size), &state), Err(PoolError::PoolFull));
+        assert!(pool.contains(alice0.get_hash()) && pool.contains(alice1.get_hash()));
+        assert_eq!(pool.size(), 2 * size);
+
+        // A refused replacement leaves the original entry as it was pooled.
+        let entry = |pool: &TransactionPool| {
+            let entry = &pool.senders[&alice.address].transactions[&U256::zero()];
+            (*entry.transaction.get_hash(), entry.inserted, entry.sequence)
+        };
+        let original = entry(&pool);
+        assert_eq!(pool.add(alice.large(0, 10, 2 * size), &state), Err(PoolError::PoolFull));
+        assert_eq!(entry(&pool), original);
+        assert!(pool.contains(alice1.get_hash()));
+        assert_eq!(pool.size(), 2 * size);
+    }
+
+    #[test]
+    fn test_evict_expired()
+    {
+        let mut state = MemoryState::new();
+        let sender = Sender::new(&mut state);
+        let clock = MockClock::new(Timestamp::from(1_700_000_000));
+        let mut pool = TransactionPool::new(PoolConfig { max_age: 60, ..PoolConfig::default() })
+            .with_clock(Arc::new(clock.clone()));
+        pool.add(sender.transaction(0, 1), &state).unwrap();
+        clock.advance(Duration::from_secs(30));
+        pool.add(sender.transaction(1, 1), &state).unwrap();
+
+        assert!(pool.evict_expired().is_empty());
+        clock.advance(Duration::from_secs(30));
+        assert!(pool.evict_expired().is_empty());
+
+        // The first transaction expires, taking the later one of its sender along.
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(pool.evict_expired().len(), 2);
+        assert!(pool.is_empty());
+    }
+}
@@ -0,0 +1,386 @@
+//! # Amount
+//!
+//! This module defines `Amount`, a quantity of tokens counted in base units, the smallest
+//! indivisible unit of the chain. Balances and transferred values are counted in base units; the
+//! display unit is `10^DECIMALS` base units and is what wallets and RPC output show to users.
+//!
+//! Arithmetic is explicit about overflow: the `checked_*` functions return `None` when the result
+//! does not fit in 256 bits, and the `saturating_*` functions clamp it.
+//!
+//! Amounts are parsed from and formatted to decimal strings exactly, without floating point: a
+//! string with more fractional digits than its denomination allows is refused rather than rounded.
+//! Human-readable serializers, such as JSON, write an amount as a decimal string of base units, and
+//! binary serializers as 32 big-endian bytes, like the other 256 bit integers of the chain.
+//!
+//! ## Example
+//!
+//! ```
+//! use core_utils::amount::{Amount, Denomination};
+//!
+//! let amount = Amount::parse("1.5", Denomination::Frenyum).unwrap();
+//! assert_eq!(amount, Amount::from_base(1_500_000_000_000_000_000u64));
+//! assert_eq!(amount.to_string(), "1.5 FRN");
+//! assert_eq!(amount.format(Denomination::Fren), "1500000000000000000");
+//! ```
+
+use crate::u256_bytes;
+use ethereum_types::U256;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error as _};
+use std::{fmt, str::FromStr};
+
+// Represents errors that can occur while parsing an amount.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmountError
+{
+    Empty,
+    InvalidFormat(String),
+    TooManyDecimals { max: u32, found: usize },
+    UnknownDenomination(String),
+    Overflow,
+}
+
+impl fmt::Display for AmountError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            AmountError::Empty => write!(f, "Amount is empty!"),
+            AmountError::InvalidFormat(s) => write!(f, "Invalid amount: {}", s),
+            AmountError::TooManyDecimals { max, found } =>
+                write!(f, "Too many decimals: at most {}, found {}", max, found),
+            AmountError::UnknownDenomination(s) => write!(f, "Unknown denomination: {}", s),
+            AmountError::Overflow => write!(f, "Amount does not fit in 256 bits!"),
+        }
+    }
+}
+
+impl std::error::Error for AmountError {}
+
+// The units an amount can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Denomination
+{
+    // The base unit
+    Fren,
+    // The display unit, worth 10^DECIMALS base units
+    Frenyum,
+}
+
+impl Denomination
+{
+    // The 'decimals' function returns the number of base unit digits after the decimal point
+    pub fn decimals(&self) -> u32
+    {
+        match self
+        {
+            Denomination::Fren => 0,
+            Denomination::Frenyum => Amount::DECIMALS,
+        }
+    }
+
+    // The 'symbol' function returns the symbol of the denomination
+    pub fn symbol(&self) -> &'static str
+    {
+        match self
+        {
+            Denomination::Fren => "fren",
+            Denomination::Frenyum => "FRN",
+        }
+    }
+
+    // The 'base_units' function returns the number of base units in one unit of the denomination
+    pub fn base_units(&self) -> U256
+    {
+        U256::exp10(self.decimals() as usize)
+    }
+}
+
+impl fmt::Display for Denomination
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl FromStr for Denomination
+{
+    type Err = AmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        [Denomination::Fren, Denomination::Frenyum]
+            .into_iter()
+            .find(|denomination| denomination.symbol().eq_ignore_ascii_case(s))
+            .ok_or_else(|| AmountError::UnknownDenomination(s.to_string()))
+    }
+}
+
+// An amount of tokens in base units
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(U256);
+
+impl Amount
+{
+    // The number of decimals of the display unit
+    pub const DECIMALS: u32 = 18;
+
+    // The amount of no tokens
+    pub const ZERO: Amount = Amount(U256::zero());
+
+    // The largest amount
+    pub const MAX: Amount = Amount(U256::MAX);
+
+    // The 'from_base' function creates an amount from a number of base units
+    pub fn from_base<T: Into<U256>>(base_units: T) -> Self
+    {
+        Amount(base_units.into())
+    }
+
+    // The 'from_units' function creates an amount from a number of units of a denomination,
+    // or returns None on overflow
+    pub fn from_units<T: Into<U256>>(units: T, denomination: Denomination) -> Option<Self>
+    {
+        units.into().checked_mul(denomination.base_units()).map(Amount)
+    }
+
+    // The 'base_units' function returns the number of base units of the amount
+    pub fn base_units(&self) -> U256 { self.0 }
+
+    // The 'is_zero' function checks if the amount is zero
+    pub fn is_zero(&self) -> bool { self.0.is_zero() }
+
+    // The 'checked_add' function adds two amounts, or returns None on overflow
+    pub fn checked_add(self, other: Amount) -> Option<Amount> { self.0.checked_add(other.0).map(Amount) }
+
+    // The 'checked_sub' function subtracts an amount, or returns None if it is larger
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> { self.0.checked_sub(other.0).map(Amount) }
+
+    // The 'checked_mul' function multiplies by a factor, or returns None on overflow
+    pub fn checked_mul<T: Into<U256>>(self, factor: T) -> Option<Amount> { self.0.checked_mul(factor.into()).map(Amount) }
+
+    // The 'checked_div' function divides by a divisor, rounding down, or returns None if it is zero
+    pub fn checked_div<T: Into<U256>>(self, divisor: T) -> Option<Amount> { self.0.checked_div(divisor.into()).map(Amount) }
+
+    // The 'saturating_add' function adds two amounts, clamping at the largest amount
+    pub fn saturating_add(self, other: Amount) -> Amount { Amount(self.0.saturating_add(other.0)) }
+
+    // The 'saturating_sub' function subtracts an amount, clamping at zero
+    pub fn saturating_sub(self, other: Amount) -> Amount { Amount(self.0.saturating_sub(other.0)) }
+
+    // The 'parse' function parses a decimal string written in a denomination, such as "1.25"
+    pub fn parse(s: &str, denomination: Denomination) -> Result<Amount, AmountError>
+    {
+        if s.is_empty()
+        {
+            return Err(AmountError::Empty);
+        }
+        let (integer, fraction) = match s.split_once('.')
+        {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (s, None),
+        };
+        let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit());
+        if !is_digits(integer) || !fraction.is_none_or(is_digits)
+        {
+            return Err(AmountError::InvalidFormat(s.to_string()));
+        }
+
+        let decimals = denomination.decimals();
+        let fraction = fraction.unwrap_or("");
+        if fraction.len() > decimals as usize
+        {
+            return Err(AmountError::TooManyDecimals { max: decimals, found: fraction.len() });
+        }
+
+        let integer = U256::from_dec_str(integer).map_err(|_| AmountError::Overflow)?;
+        let fraction = if fraction.is_empty()
+        {
+            U256::zero()
+        } else {
+            // At most 18 digits scaled to 18 digits, which fits.
+            U256::from_dec_str(fraction).map_err(|_| AmountError::Overflow)? * U256::exp10(decimals as usize - fraction.len())
+        };
+        integer
+            .checked_mul(denomination.base_units())
+            .and_then(|base_units| base_units.checked_add(fraction))
+            .map(Amount)
+            .ok_or(AmountError::Overflow)
+    }
+
+    // The 'format' function writes the amount as a decimal string in a denomination, without
+    // trailing zeros after the decimal point
+    pub fn format(&self, denomination: Denomination) -> String
+    {
+        let unit = denomination.base_units();
+        let (integer, fraction) = (self.0 / unit, self.0 % unit);
+        if fraction.is_zero()
+        {
+            return integer.to_string();
+        }
+        let fraction = format!("{:0>width$}", fraction.to_string(), width = denomination.decimals() as usize);
+        format!("{}.{}", integer, fraction.trim_end_matches('0'))
+    }
+}
+
+impl fmt::Display for Amount
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{} {}", self.format(Denomination::Frenyum), Denomination::Frenyum)
+    }
+}
+
+impl FromStr for Amount
+{
+    type Err = AmountError;
+
+    // Parses a decimal string followed by an optional denomination symbol, such as "1.5 FRN" or
+    // "1500 fren". Without symbol, the amount is written in the display unit.
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let s = s.trim();
+        match s.split_once(' ')
+        {
+            Some((value, symbol)) => Amount::parse(value, symbol.trim().parse()?),
+            None => Amount::parse(s, Denomination::Frenyum),
+        }
+    }
+}
+
+impl From<U256> for Amount
+{
+    fn from(base_units: U256) -> Amount
+    {
+        Amount(base_units)
+    }
+}
+
+impl From<Amount> for U256
+{
+    fn from(amount: Amount) -> U256
+    {
+        amount.0
+    }
+}
+
+impl Serialize for Amount
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    {
+        if serializer.is_human_readable()
+        {
+            serializer.serialize_str(&self.format(Denomination::Fren))
+        } else {
+            u256_bytes::serialize(&self.0, serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Amount, D::Error>
+    {
+        if deserializer.is_human_readable()
+        {
+            let s = String::deserialize(deserializer)?;
+            Amount::parse(&s, Denomination::Fren).map_err(D::Error::custom)
+        } else {
+            u256_bytes::deserialize(deserializer).map(Amount)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    fn frenyum(s: &str) -> Amount
+    {
+        Amount::parse(s, Denomination::Frenyum).unwrap()
+    }
+
+    #[test]
+    fn test_parse()
+    {
+        assert_eq!(frenyum("1"), Amount::from_base(U256::exp10(18)));
+        assert_eq!(frenyum("0.000000000000000001"), Amount::from_base(1u64));
+        assert_eq!(frenyum("12.5"), Amount::from_base(12_500_000_000_000_000_000u128));
+        assert_eq!(Amount::parse("42", Denomination::Fren), Ok(Amount::from_base(42u64)));
+
+        assert_eq!(Amount::parse("", Denomination::Frenyum), Err(AmountError::Empty));
+        for invalid in ["1.", ".5", "1.2.3", "-1", "+1", "1e3", " 1", "0x10"]
+        {
+            assert_eq!(Amount::parse(invalid, Denomination::Frenyum), Err(AmountError::InvalidFormat(invalid.to_string())));
+        }
+        assert_eq!(
+            Amount::parse("0.0000000000000000001", Denomination::Frenyum),
+            Err(AmountError::TooManyDecimals { max: 18, found: 19 })
+        );
+        assert_eq!(Amount::parse("1.5", Denomination::Fren), Err(AmountError::TooManyDecimals { max: 0, found: 1 }));
+    }
+
+    #[test]
+    fn test_parse_overflow()
+    {
+        let max = U256::MAX.to_string();
+        assert_eq!(Amount::parse(&max, Denomination::Fren), Ok(Amount::MAX));
+        assert_eq!(Amount::parse(&max, Denomination::Frenyum), Err(AmountError::Overflow));
+        assert_eq!(Amount::parse(&format!("{}0", max), Denomination::Fren), Err(AmountError::Overflow));
+    }
+
+    #[test]
+    fn test_format()
+    {
+        assert_eq!(frenyum("12.5").format(Denomination::Frenyum), "12.5");
+        assert_eq!(frenyum("3").format(Denomination::Frenyum), "3");
+        assert_eq!(Amount::from_base(1u64).format(Denomination::Frenyum), "0.000000000000000001");
+        assert_eq!(Amount::ZERO.format(Denomination::Frenyum), "0");
+        assert_eq!(frenyum("0.25").format(Denomination::Fren), "250000000000000000");
+        assert_eq!(frenyum("0.25").to_string(), "0.25 FRN");
+
+        // Formatting and parsing round trip exactly.
+        let amount = Amount::from_base(U256::MAX);
+        assert_eq!(Amount::parse(&amount.format(Denomination::Frenyum), Denomination::Frenyum), Ok(amount));
+    }
+
+    #[test]
+    fn test_from_str()
+    {
+        assert_eq!("1.5 FRN".parse(), Ok(frenyum("1.5")));
+        assert_eq!("1.5".parse(), Ok(frenyum("1.5")));
+        assert_eq!("15 fren".parse(), Ok(Amount::from_base(15u64)));
+        assert_eq!("15 gold".parse::<Amount>(), Err(AmountError::UnknownDenomination("gold".to_string())));
+    }
+
+    #[test]
+    fn test_checked_arithmetic()
+    {
+        assert_eq!(frenyum("1").checked_add(frenyum("0.5")), Some(frenyum("1.5")));
+        assert_eq!(Amount::MAX.checked_add(Amount::from_base(1u64)), None);
+        assert_eq!(frenyum("1").checked_sub(frenyum("2")), None);
+        assert_eq!(Amount::MAX.checked_mul(2u64), None);
+        assert_eq!(frenyum("1").checked_div(0u64), None);
+        assert_eq!(frenyum("1").checked_div(4u64), Some(frenyum("0.25")));
+        assert_eq!(Amount::from_units(3u64, Denomination::Frenyum), Some(frenyum("3")));
+        assert_eq!(Amount::from_units(U256::MAX, Denomination::Frenyum), None);
+        assert_eq!(Amount::MAX.saturating_add(frenyum("1")), Amount::MAX);
+        assert_eq!(Amount::ZERO.saturating_sub(frenyum("1")), Amount::ZERO);
+    }
+
+    #[test]
+    fn test_serde()
+    {
+        let amount = frenyum("1.5");
+        let json = serde_json::to_string(&amount).unwrap();
+        assert_eq!(json, "\"1500000000000000000\"");
+        assert_eq!(serde_json::from_str::<Amount>(&json).unwrap(), amount);
+        assert!(serde_json::from_str::<Amount>("\"1.5\"").is_err());
+
+        let bytes = bincode::serialize(&amount).unwrap();
+        assert_eq!(bytes.len(), u256_bytes::LENGTH);
+        assert_eq!(bincode::deserialize::<Amount>(&bytes).unwrap(), amount);
+    }
+}
@@ -1,17 +1,57 @@
-use serde::Serialize;
+//! # Gas
+//!
+//! This module defines `Gas`, an amount of gas or a price per unit of gas, and `GasMeter`, which
+//! accounts for the gas consumed against a limit.
+//!
+//! Arithmetic on `Gas` is explicit about overflow: the `checked_*` functions return `None` when the
+//! result does not fit or when dividing by zero, and the `saturating_*` functions clamp it. The
+//! operators behave like integer operators and panic instead of returning a wrong amount.
+//!
+//! A `GasMeter` is charged for every operation. A charge exceeding the remaining gas consumes all of
+//! it and fails with `OutOfGas`. Refunds are accumulated separately and only returned once execution
+//! ends, capped to a fraction of the gas used so that they cannot pay for the execution itself.
+
+use serde::{Deserialize, Serialize};
 use std::{fmt::Formatter, fmt};
 
 // An Gas structure
-#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct Gas(u64);
 
 impl Gas
 {
+   // The amount of no gas
+   pub const ZERO: Gas = Gas(0);
+
+   // The largest amount of gas
+   pub const MAX: Gas = Gas(u64::MAX);
+
    // The 'new' function creates a new 'Gas' object with the given amount
-   pub fn new(amount: u64) -> Self { Gas(amount) } 
-    
+   pub fn new(amount: u64) -> Self { Gas(amount) }
+
    // The 'amount' function returns the amount of gas
    pub fn amount(&self) -> u64 { self.0 }
+
+   // The 'is_zero' function checks if the amount is zero
+   pub fn is_zero(&self) -> bool { self.0 == 0 }
+
+   // The 'checked_add' function adds two amounts, or returns None on overflow
+   pub fn checked_add(self, other: Gas) -> Option<Gas> { self.0.checked_add(other.0).map(Gas) }
+
+   // The 'checked_sub' function subtracts an amount, or returns None if it is larger
+   pub fn checked_sub(self, other: Gas) -> Option<Gas> { self.0.checked_sub(other.0).map(Gas) }
+
+   // The 'checked_mul' function multiplies by a factor, or returns None on overflow
+   pub fn checked_mul(self, factor: u64) -> Option<Gas> { self.0.checked_mul(factor).map(Gas) }
+
+   // The 'checked_div' function divides by a divisor, or returns None if it is zero
+   pub fn checked_div(self, divisor: u64) -> Option<Gas> { self.0.checked_div(divisor).map(Gas) }
+
+   // The 'saturating_add' function adds two amounts, clamping at the largest amount
+   pub fn saturating_add(self, other: Gas) -> Gas { Gas(self.0.saturating_add(other.0)) }
+
+   // The 'saturating_sub' function subtracts an amount, clamping at zero
+   pub fn saturating_sub(self, other: Gas) -> Gas { Gas(self.0.saturating_sub(other.0)) }
 }
 
 impl AsRef<u64> for Gas
@@ -36,12 +76,17 @@ impl std::ops::Sub<Gas> for Gas
 
     fn sub(self, other: Gas) -> Gas
     {
-        if self.0 >= other.0
-        {
-            Gas(self.0 - other.0)
-        } else {
-            Gas(0)
-        }
+        self.checked_sub(other).expect("Gas subtraction underflow")
+    }
+}
+
+impl std::ops::Add<Gas> for Gas
+{
+    type Output = Gas;
+
+    fn add(self, other: Gas) -> Gas
+    {
+        self.checked_add(other).expect("Gas addition overflow")
     }
 }
 
@@ -51,7 +96,7 @@ impl std::ops::Add<u64> for Gas
 
     fn add(self, other: u64) -> Gas
     {
-        Gas(self.0 + other)
+        self + Gas(other)
     }
 }
 
@@ -61,7 +106,7 @@ impl std::ops::Mul<Gas> for Gas
 
     fn mul(self, other: Gas) -> Gas
     {
-        Gas(self.0 * other.0)
+        self.checked_mul(other.0).expect("Gas multiplication overflow")
     }
 }
 
@@ -71,12 +116,7 @@ impl std::ops::Div<Gas> for Gas
 
     fn div(self, other: Gas) -> Gas
     {
-        if other.0 != 0      
-        {
-            Gas(self.0 / other.0)
-        } else {
-            Gas(0)
-        }
+        self.checked_div(other.0).expect("Gas division by zero")
     }
 }
 
@@ -88,12 +128,94 @@ impl From<u64> for Gas
     }
 }
 
+// The error of a charge exceeding the remaining gas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfGas
+{
+    pub limit: Gas,
+    pub used: Gas,
+    pub cost: Gas,
+}
+
+impl fmt::Display for OutOfGas
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "Out of gas: charging {} with {} of {} used", self.cost, self.used, self.limit)
+    }
+}
+
+impl std::error::Error for OutOfGas {}
+
+// Accounts for the gas consumed against a limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasMeter
+{
+    limit: Gas,
+    used: Gas,
+    refund: Gas,
+}
+
+impl GasMeter
+{
+    // The 'new' function creates a meter with nothing used
+    pub fn new(limit: Gas) -> Self
+    {
+        GasMeter { limit, used: Gas::ZERO, refund: Gas::ZERO }
+    }
+
+    // The 'limit' function returns the gas the meter can charge
+    pub fn limit(&self) -> Gas { self.limit }
+
+    // The 'used' function returns the gas charged so far
+    pub fn used(&self) -> Gas { self.used }
+
+    // The 'remaining' function returns the gas left to charge
+    pub fn remaining(&self) -> Gas { self.limit - self.used }
+
+    // The 'refund_counter' function returns the refunds accumulated so far, before the cap
+    pub fn refund_counter(&self) -> Gas { self.refund }
+
+    // The 'charge' function consumes `cost`, or consumes all the remaining gas if `cost` exceeds it
+    pub fn charge(&mut self, cost: Gas) -> Result<(), OutOfGas>
+    {
+        if cost > self.remaining()
+        {
+            let error = OutOfGas { limit: self.limit, used: self.used, cost };
+            self.used = self.limit;
+            return Err(error);
+        }
+        self.used = self.used + cost;
+        Ok(())
+    }
+
+    // The 'refund' function accumulates gas to give back once execution ends
+    pub fn refund(&mut self, amount: Gas)
+    {
+        self.refund = self.refund.saturating_add(amount);
+    }
+
+    // The 'effective_refund' function returns the refund, capped to the gas used divided by `max_refund_quotient`
+    pub fn effective_refund(&self, max_refund_quotient: u64) -> Gas
+    {
+        let cap = self.used.checked_div(max_refund_quotient).unwrap_or(Gas::ZERO);
+        self.refund.min(cap)
+    }
+
+    // The 'settle' function returns the gas used once the capped refund is given back
+    pub fn settle(&self, max_refund_quotient: u64) -> Gas
+    {
+        self.used - self.effective_refund(max_refund_quotient)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::ops::{Add, Sub, Mul, Div};
 
     #[test]
-    fn test_gas_allocation() 
+    fn test_gas_allocation()
     {
         let initial_gas = Gas::new(1000);
         let additional_gas = Gas::new(500);
@@ -104,7 +226,7 @@ mod tests {
     }
 
     #[test]
-    fn test_gas_consumption() 
+    fn test_gas_consumption()
     {
         let initial_gas = Gas::new(1000);
         let consumed_gas = Gas::new(700);
@@ -115,7 +237,7 @@ mod tests {
     }
 
     #[test]
-    fn test_gas_multiplication() 
+    fn test_gas_multiplication()
     {
         let gas1 = Gas::new(100);
         let gas2 = Gas::new(5);
@@ -126,7 +248,7 @@ mod tests {
     }
 
     #[test]
-    fn test_gas_division() 
+    fn test_gas_division()
     {
         let initial_gas = Gas::new(1000);
         let divisor_gas = Gas::new(200);
@@ -135,4 +257,65 @@ mod tests {
 
         assert_eq!(result_gas.amount(), 5);
     }
+
+    #[test]
+    fn test_checked_arithmetic()
+    {
+        assert_eq!(Gas::MAX.checked_add(Gas::new(1)), None);
+        assert_eq!(Gas::new(1).checked_sub(Gas::new(2)), None);
+        assert_eq!(Gas::MAX.checked_mul(2), None);
+        assert_eq!(Gas::new(1).checked_div(0), None);
+        assert_eq!(Gas::new(6).checked_div(4), Some(Gas::new(1)));
+        assert_eq!(Gas::MAX.saturating_add(Gas::new(1)), Gas::MAX);
+        assert_eq!(Gas::new(1).saturating_sub(Gas::new(2)), Gas::ZERO);
+    }
+
+    #[test]
+    #[should_panic(expected = "Gas subtraction underflow")]
+    fn test_sub_underflow_panics()
+    {
+        let _ = Gas::new(1) - Gas::new(2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Gas division by zero")]
+    fn test_div_by_zero_panics()
+    {
+        let _ = Gas::new(1) / Gas::ZERO;
+    }
+
+    #[test]
+    fn test_meter_charge()
+    {
+        let mut meter = GasMeter::new(Gas::new(100));
+        assert_eq!(meter.charge(Gas::new(60)), Ok(()));
+        assert_eq!(meter.charge(Gas::new(40)), Ok(()));
+        assert_eq!(meter.remaining(), Gas::ZERO);
+
+        let mut meter = GasMeter::new(Gas::new(100));
+        meter.charge(Gas::new(60)).unwrap();
+        assert_eq!(
+            meter.charge(Gas::new(41)),
+            Err(OutOfGas { limit: Gas::new(100), used: Gas::new(60), cost: Gas::new(41) })
+        );
+        // Running out of gas consumes all of it.
+        assert_eq!(meter.used(), Gas::new(100));
+        assert!(meter.charge(Gas::new(1)).is_err());
+    }
+
+    #[test]
+    fn test_meter_refund_cap()
+    {
+        let mut meter = GasMeter::new(Gas::new(100));
+        meter.charge(Gas::new(50)).unwrap();
+        meter.refund(Gas::new(4));
+        assert_eq!(meter.settle(5), Gas::new(46));
+
+        // The refund is capped to a fifth of the gas used.
+        meter.refund(Gas::new(20));
+        assert_eq!(meter.refund_counter(), Gas::new(24));
+        assert_eq!(meter.effective_refund(5), Gas::new(10));
+        assert_eq!(meter.settle(5), Gas::new(40));
+        assert_eq!(meter.settle(0), Gas::new(50));
+    }
 }
@@ -1,4 +1,6 @@
 pub mod timestamp;
 pub mod gas;
+pub mod amount;
+pub mod u256_bytes;
 pub mod configs;
 
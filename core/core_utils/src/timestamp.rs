@@ -1,41 +1,276 @@
-use std::time::{SystemTime, UNIX_EPOCH};
-use chrono::{DateTime, Utc};
+//! # Timestamp
+//!
+//! This module defines `Timestamp`, a point in time with millisecond precision, and `Clock`, the
+//! source of the current time.
+//!
+//! Components reading the time should take a `Clock` rather than calling `Timestamp::now`, so that
+//! tests can drive them with a `MockClock` instead of sleeping. Clones of a `MockClock` share their
+//! time, so a test can hand one to a component and advance the other.
+//!
+//! Arithmetic with `Duration` is explicit about overflow: the `checked_*` functions return `None`
+//! when the result is before the epoch or does not fit, and the `saturating_*` functions clamp it.
+//! The operators panic instead of returning a wrong time. Durations are truncated to whole
+//! milliseconds.
+//!
+//! A timestamp is displayed in RFC 3339, in UTC, with milliseconds only when it has some, or, with
+//! the alternate flag `{:#}`, as seconds since the epoch with the same fraction. Both formats parse
+//! back to the same timestamp. Timestamps after the year 9999 cannot be written in RFC 3339 and are
+//! always displayed as seconds since the epoch.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::str::FromStr;
 
-// An Timestamp structure
+// The number of milliseconds in a second
+const MILLIS_PER_SECOND: u64 = 1_000;
+
+// The last millisecond RFC 3339 can represent, 9999-12-31T23:59:59.999Z
+const RFC3339_MAX_MILLIS: u64 = 253_402_300_799_999;
+
+// Represents errors that can occur while parsing a timestamp.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimestampError
+{
+    Empty,
+    InvalidFormat(String),
+    BeforeEpoch,
+    TooPrecise,
+    OutOfRange,
+}
+
+impl fmt::Display for TimestampError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            TimestampError::Empty => write!(f, "Empty timestamp!"),
+            TimestampError::InvalidFormat(reason) => write!(f, "Invalid timestamp format: {}", reason),
+            TimestampError::BeforeEpoch => write!(f, "Timestamp is before the Unix epoch!"),
+            TimestampError::TooPrecise => write!(f, "Timestamp is more precise than a millisecond!"),
+            TimestampError::OutOfRange => write!(f, "Timestamp is out of range!"),
+        }
+    }
+}
+
+impl std::error::Error for TimestampError {}
+
+// 'Clock' is a source of the current time.
+pub trait Clock: Send + Sync
+{
+    // The 'now' function returns the current time
+    fn now(&self) -> Timestamp;
+}
+
+// A clock reading the system time
 #[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock
+{
+    fn now(&self) -> Timestamp
+    {
+        let since_the_epoch = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards");
+        Timestamp::from_millis(u64::try_from(since_the_epoch.as_millis()).unwrap_or(u64::MAX))
+    }
+}
+
+// A clock that only moves when it is set or advanced
+#[derive(Debug, Clone, Default)]
+pub struct MockClock
+{
+    now: Arc<AtomicU64>,
+}
+
+impl MockClock
+{
+    // The 'new' function creates a clock stopped at `start`
+    pub fn new(start: Timestamp) -> Self
+    {
+        MockClock { now: Arc::new(AtomicU64::new(start.as_millis())) }
+    }
+
+    // The 'set' function moves the clock to `now`
+    pub fn set(&self, now: Timestamp)
+    {
+        self.now.store(now.as_millis(), Ordering::SeqCst);
+    }
+
+    // The 'advance' function moves the clock forward by `duration`, stopping at the latest timestamp
+    pub fn advance(&self, duration: Duration)
+    {
+        let millis = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+        let _ = self.now.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |now| Some(now.saturating_add(millis)));
+    }
+}
+
+impl Clock for MockClock
+{
+    fn now(&self) -> Timestamp
+    {
+        Timestamp::from_millis(self.now.load(Ordering::SeqCst))
+    }
+}
+
+// An Timestamp structure, in milliseconds since the Unix epoch
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub struct Timestamp(u64);
 
 impl Timestamp
 {
     // The maximum value a timestamp can have
     pub const MAX: Timestamp = Timestamp(u64::MAX);
-    
+
+    // The Unix epoch
+    pub const EPOCH: Timestamp = Timestamp(0);
+
     // The 'now' function returns the current system time as a 'Timestamp' object
-    pub fn now() -> Self 
+    pub fn now() -> Self
+    {
+        SystemClock.now()
+    }
+
+    // The 'from_secs' function creates a timestamp from seconds since the Unix epoch, clamping at the maximum
+    pub fn from_secs(seconds: u64) -> Self
+    {
+        Timestamp(seconds.saturating_mul(MILLIS_PER_SECOND))
+    }
+
+    // The 'from_millis' function creates a timestamp from milliseconds since the Unix epoch
+    pub fn from_millis(millis: u64) -> Self
+    {
+        Timestamp(millis)
+    }
+
+    // The 'as_u64' function returns the number of whole seconds since the Unix epoch
+    pub fn as_u64(&self) -> u64
+    {
+        self.as_secs()
+    }
+
+    // The 'as_secs' function returns the number of whole seconds since the Unix epoch
+    pub fn as_secs(&self) -> u64
+    {
+        self.0 / MILLIS_PER_SECOND
+    }
+
+    // The 'as_millis' function returns the number of milliseconds since the Unix epoch
+    pub fn as_millis(&self) -> u64
+    {
+        self.0
+    }
+
+    // The 'subsec_millis' function returns the milliseconds past the last whole second
+    pub fn subsec_millis(&self) -> u64
+    {
+        self.0 % MILLIS_PER_SECOND
+    }
+
+    // The 'truncate' function rounds the timestamp down to a multiple of `precision`, such as whole seconds
+    pub fn truncate(self, precision: Duration) -> Timestamp
     {
-        let start = SystemTime::now();
-        let since_the_epoch = start.duration_since(UNIX_EPOCH).expect("Time went backwards");
-        Timestamp(since_the_epoch.as_secs())
+        match u64::try_from(precision.as_millis())
+        {
+            Ok(0) => self,
+            Ok(step) => Timestamp(self.0 - self.0 % step),
+            Err(_) => Timestamp::EPOCH,
+        }
     }
-    
+
     // The 'reset' function resets the timestamp to zero
     pub fn reset(&mut self)
     {
         self.0 = 0;
     }
 
-    // The 'elapsed' function returns the elapsed time since the timestamp was created
-    pub fn elapsed(&self) -> u64 
+    // The 'checked_add' function adds a duration, or returns None on overflow
+    pub fn checked_add(self, duration: Duration) -> Option<Timestamp>
     {
-        let now = Timestamp::now();
-        if now >= *self
+        self.0.checked_add(u64::try_from(duration.as_millis()).ok()?).map(Timestamp)
+    }
+
+    // The 'checked_sub' function subtracts a duration, or returns None if the result is before the epoch
+    pub fn checked_sub(self, duration: Duration) -> Option<Timestamp>
+    {
+        self.0.checked_sub(u64::try_from(duration.as_millis()).ok()?).map(Timestamp)
+    }
+
+    // The 'saturating_add' function adds a duration, clamping at the maximum
+    pub fn saturating_add(self, duration: Duration) -> Timestamp
+    {
+        self.checked_add(duration).unwrap_or(Timestamp::MAX)
+    }
+
+    // The 'saturating_sub' function subtracts a duration, clamping at the epoch
+    pub fn saturating_sub(self, duration: Duration) -> Timestamp
+    {
+        self.checked_sub(duration).unwrap_or(Timestamp::EPOCH)
+    }
+
+    // The 'duration_since' function returns the time since `earlier`, or None if it is later
+    pub fn duration_since(&self, earlier: Timestamp) -> Option<Duration>
+    {
+        self.0.checked_sub(earlier.0).map(Duration::from_millis)
+    }
+
+    // The 'saturating_duration_since' function returns the time since `earlier`, or zero if it is later
+    pub fn saturating_duration_since(&self, earlier: Timestamp) -> Duration
+    {
+        self.duration_since(earlier).unwrap_or(Duration::ZERO)
+    }
+
+    // The 'elapsed' function returns the time elapsed since the timestamp on a clock, or zero if it is later
+    pub fn elapsed<C: Clock + ?Sized>(&self, clock: &C) -> Duration
+    {
+        clock.now().saturating_duration_since(*self)
+    }
+
+    // The 'parse_epoch' function parses seconds since the Unix epoch, with up to three decimals
+    fn parse_epoch(s: &str) -> Result<Timestamp, TimestampError>
+    {
+        if let Some(rest) = s.strip_prefix('-')
         {
-            now.0 - self.0
-        } else {
-            self.0 - now.0
+            return match Timestamp::parse_epoch(rest)
+            {
+                Ok(timestamp) if timestamp == Timestamp::EPOCH => Ok(timestamp),
+                Ok(_) => Err(TimestampError::BeforeEpoch),
+                Err(error) => Err(error),
+            };
+        }
+
+        let (seconds, fraction) = s.split_once('.').unwrap_or((s, ""));
+        let is_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if seconds.is_empty() || !is_digits(seconds) || !is_digits(fraction) || (s.contains('.') && fraction.is_empty())
+        {
+            return Err(TimestampError::InvalidFormat(format!("'{}' is not a number of seconds", s)));
+        }
+        if fraction.len() > 3
+        {
+            return Err(TimestampError::TooPrecise);
+        }
+
+        let seconds = seconds.parse::<u64>().map_err(|_| TimestampError::OutOfRange)?;
+        let millis = format!("{:0<3}", fraction).parse::<u64>().expect("Three digits");
+        seconds
+            .checked_mul(MILLIS_PER_SECOND)
+            .and_then(|millis_of_seconds| millis_of_seconds.checked_add(millis))
+            .map(Timestamp)
+            .ok_or(TimestampError::OutOfRange)
+    }
+
+    // The 'parse_rfc3339' function parses an RFC 3339 date and time
+    fn parse_rfc3339(s: &str) -> Result<Timestamp, TimestampError>
+    {
+        let datetime = DateTime::parse_from_rfc3339(s).map_err(|error| TimestampError::InvalidFormat(error.to_string()))?;
+        if datetime.timestamp_subsec_nanos() % 1_000_000 != 0
+        {
+            return Err(TimestampError::TooPrecise);
         }
+        let millis = datetime.timestamp_millis();
+        u64::try_from(millis).map(Timestamp).map_err(|_| TimestampError::BeforeEpoch)
     }
 }
 
@@ -43,59 +278,96 @@ impl fmt::Display for Timestamp
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
     {
-        let duration_since_epoch = std::time::Duration::from_secs(self.0);
-        let datetime = UNIX_EPOCH + duration_since_epoch;
-        let datetime: DateTime<Utc> = datetime.into();
-        write!(f, "{}", datetime.format("%Y-%m-%d %H:%M:%S"))
+        if !f.alternate() && self.0 <= RFC3339_MAX_MILLIS
+        {
+            let datetime: DateTime<Utc> = Utc.timestamp_millis_opt(self.0 as i64).single().expect("Representable in RFC 3339");
+            let format = if self.subsec_millis() == 0 { SecondsFormat::Secs } else { SecondsFormat::Millis };
+            return write!(f, "{}", datetime.to_rfc3339_opts(format, true));
+        }
+
+        match self.subsec_millis()
+        {
+            0 => write!(f, "{}", self.as_secs()),
+            millis => write!(f, "{}.{:03}", self.as_secs(), millis),
+        }
     }
 }
 
 impl FromStr for Timestamp
 {
-    type Err = ();
+    type Err = TimestampError;
 
+    // Parses seconds since the Unix epoch, such as "1700000000.25", or an RFC 3339 date and time,
+    // such as "2023-11-14T22:13:20.250Z"
     fn from_str(s: &str) -> Result<Self, Self::Err>
     {
-        match s.parse::<u64>() {
-            Ok(value) => Ok(Timestamp(value)),
-            Err(_) => Err(()),
+        let s = s.trim();
+        if s.is_empty()
+        {
+            return Err(TimestampError::Empty);
+        }
+
+        let is_epoch = s.strip_prefix('-').unwrap_or(s).bytes().all(|byte| byte.is_ascii_digit() || byte == b'.');
+        if is_epoch
+        {
+            Timestamp::parse_epoch(s)
+        } else {
+            Timestamp::parse_rfc3339(s)
         }
     }
 }
 
+impl From<u64> for Timestamp
+{
+    fn from(seconds: u64) -> Timestamp
+    {
+        Timestamp::from_secs(seconds)
+    }
+}
+
 impl std::ops::Sub<Timestamp> for Timestamp
+{
+    type Output = Duration;
+
+    fn sub(self, other: Timestamp) -> Duration
+    {
+        self.duration_since(other).expect("Timestamp subtraction underflow")
+    }
+}
+
+impl std::ops::Add<Duration> for Timestamp
 {
     type Output = Timestamp;
 
-    fn sub(self, other: Timestamp) -> Timestamp
+    fn add(self, other: Duration) -> Timestamp
     {
-        Timestamp(self.0 - other.0)
+        self.checked_add(other).expect("Timestamp addition overflow")
     }
 }
 
-impl std::ops::Add<u64> for Timestamp
+impl std::ops::Sub<Duration> for Timestamp
 {
     type Output = Timestamp;
 
-    fn add(self, other: u64) -> Timestamp
+    fn sub(self, other: Duration) -> Timestamp
     {
-        Timestamp(self.0 + other)
+        self.checked_sub(other).expect("Timestamp subtraction underflow")
     }
 }
 
-impl std::ops::AddAssign<u64> for Timestamp
+impl std::ops::AddAssign<Duration> for Timestamp
 {
-    fn add_assign(&mut self, other: u64)
+    fn add_assign(&mut self, other: Duration)
     {
-        self.0 += other;
+        *self = *self + other;
     }
 }
 
-impl std::ops::SubAssign<u64> for Timestamp
+impl std::ops::SubAssign<Duration> for Timestamp
 {
-    fn sub_assign(&mut self, other: u64)
+    fn sub_assign(&mut self, other: Duration)
     {
-        self.0 -= other;
+        *self = *self - other;
     }
 }
 
@@ -107,17 +379,17 @@ impl std::cmp::PartialEq for Timestamp
     }
 }
 
-impl std::cmp::PartialOrd for Timestamp 
+impl std::cmp::PartialOrd for Timestamp
 {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering>
     {
-        self.0.partial_cmp(&other.0)
+        Some(self.cmp(other))
     }
 }
 
 impl std::cmp::Eq for Timestamp {}
 
-impl std::cmp::Ord for Timestamp 
+impl std::cmp::Ord for Timestamp
 {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering
     {
@@ -133,9 +405,10 @@ mod test
     #[test]
     fn test_now_returns_current_time()
     {
+        let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
         let now = Timestamp::now();
-        let system_now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        assert_eq!(now.0, system_now);
+        let after = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        assert!(before <= now.as_millis() && now.as_millis() <= after);
     }
 
     #[test]
@@ -149,18 +422,102 @@ mod test
     #[test]
     fn test_elapsed_function()
     {
-        let start = Timestamp::now();
-        std::thread::sleep(std::time::Duration::from_secs(2));
-        let end = Timestamp::now();
-        let elapsed = start.elapsed();
-        assert_eq!(elapsed, 2);
+        let clock = MockClock::new(Timestamp::from_secs(1_700_000_000));
+        let start = clock.now();
+        clock.advance(Duration::from_millis(2_500));
+        assert_eq!(start.elapsed(&clock), Duration::from_millis(2_500));
+
+        // A timestamp ahead of the clock has no elapsed time.
+        clock.set(Timestamp::from_secs(1_600_000_000));
+        assert_eq!(start.elapsed(&clock), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_mock_clock_is_shared()
+    {
+        let clock = MockClock::new(Timestamp::EPOCH);
+        let handle = clock.clone();
+        handle.advance(Duration::from_secs(3));
+        assert_eq!(clock.now(), Timestamp::from_secs(3));
+        clock.advance(Duration::MAX);
+        assert_eq!(handle.now(), Timestamp::MAX);
+    }
+
+    #[test]
+    fn test_seconds_and_millis()
+    {
+        let timestamp = Timestamp::from_millis(1_700_000_000_250);
+        assert_eq!(timestamp.as_secs(), 1_700_000_000);
+        assert_eq!(timestamp.as_u64(), 1_700_000_000);
+        assert_eq!(timestamp.subsec_millis(), 250);
+        assert_eq!(Timestamp::from(1_700_000_000), Timestamp::from_millis(1_700_000_000_000));
+        assert_eq!(Timestamp::from_secs(u64::MAX), Timestamp::MAX);
+    }
+
+    #[test]
+    fn test_truncate()
+    {
+        let timestamp = Timestamp::from_millis(1_700_000_012_345);
+        assert_eq!(timestamp.truncate(Duration::from_secs(1)), Timestamp::from_millis(1_700_000_012_000));
+        assert_eq!(timestamp.truncate(Duration::from_secs(10)), Timestamp::from_millis(1_700_000_010_000));
+        assert_eq!(timestamp.truncate(Duration::from_micros(10)), timestamp);
     }
 
     #[test]
     fn test_display_formats_timestap_correctly()
     {
-        let timestamp = Timestamp(123456789);
-        assert_eq!(format!("{}", timestamp), "1973-11-29 21:33:09");
+        let timestamp = Timestamp::from_secs(123456789);
+        assert_eq!(format!("{}", timestamp), "1973-11-29T21:33:09Z");
+        assert_eq!(format!("{:#}", timestamp), "123456789");
+
+        let timestamp = Timestamp::from_millis(123_456_789_050);
+        assert_eq!(format!("{}", timestamp), "1973-11-29T21:33:09.050Z");
+        assert_eq!(format!("{:#}", timestamp), "123456789.050");
+        assert_eq!(format!("{}", Timestamp::MAX), "18446744073709551.615");
+    }
+
+    #[test]
+    fn test_display_round_trips()
+    {
+        let timestamps = [
+            Timestamp::EPOCH,
+            Timestamp::from_secs(123456789),
+            Timestamp::from_millis(1_700_000_000_001),
+            Timestamp::from_millis(RFC3339_MAX_MILLIS),
+            Timestamp::from_millis(RFC3339_MAX_MILLIS + 1),
+            Timestamp::MAX,
+        ];
+        for timestamp in timestamps
+        {
+            assert_eq!(timestamp.to_string().parse::<Timestamp>(), Ok(timestamp));
+            assert_eq!(format!("{:#}", timestamp).parse::<Timestamp>(), Ok(timestamp));
+        }
+    }
+
+    #[test]
+    fn test_parse()
+    {
+        assert_eq!("1700000000".parse::<Timestamp>(), Ok(Timestamp::from_secs(1_700_000_000)));
+        assert_eq!("1700000000.25".parse::<Timestamp>(), Ok(Timestamp::from_millis(1_700_000_000_250)));
+        assert_eq!("2023-11-14T22:13:20Z".parse::<Timestamp>(), Ok(Timestamp::from_secs(1_700_000_000)));
+        assert_eq!("2023-11-15T00:13:20.5+02:00".parse::<Timestamp>(), Ok(Timestamp::from_millis(1_700_000_000_500)));
+        assert_eq!("-0".parse::<Timestamp>(), Ok(Timestamp::EPOCH));
+    }
+
+    #[test]
+    fn test_parse_errors()
+    {
+        assert_eq!("".parse::<Timestamp>(), Err(TimestampError::Empty));
+        assert_eq!("  ".parse::<Timestamp>(), Err(TimestampError::Empty));
+        assert_eq!("-1".parse::<Timestamp>(), Err(TimestampError::BeforeEpoch));
+        assert_eq!("1969-12-31T23:59:59Z".parse::<Timestamp>(), Err(TimestampError::BeforeEpoch));
+        assert_eq!("1.0001".parse::<Timestamp>(), Err(TimestampError::TooPrecise));
+        assert_eq!("2023-11-14T22:13:20.0001Z".parse::<Timestamp>(), Err(TimestampError::TooPrecise));
+        assert_eq!("18446744073709552".parse::<Timestamp>(), Err(TimestampError::OutOfRange));
+        assert!(matches!("1.".parse::<Timestamp>(), Err(TimestampError::InvalidFormat(_))));
+        assert!(matches!("1.2.3".parse::<Timestamp>(), Err(TimestampError::InvalidFormat(_))));
+        assert!(matches!("2023-11-14".parse::<Timestamp>(), Err(TimestampError::InvalidFormat(_))));
+        assert!(matches!("yesterday".parse::<Timestamp>(), Err(TimestampError::InvalidFormat(_))));
     }
 
     #[test]
@@ -169,15 +526,35 @@ mod test
         let timestamp1 = Timestamp(1000);
         let timestamp2 = Timestamp(500);
         let result = timestamp1 - timestamp2;
-        assert_eq!(result.0, 500);
+        assert_eq!(result, Duration::from_millis(500));
+        assert_eq!(timestamp2.duration_since(timestamp1), None);
     }
 
     #[test]
     fn test_adding_duration_to_timestamp_returns_correct_result()
     {
         let timestamp = Timestamp(1000);
-        let result = timestamp + 500;
+        let result = timestamp + Duration::from_millis(500);
         assert_eq!(result.0, 1500);
+        assert_eq!(timestamp - Duration::from_secs(1), Timestamp::EPOCH);
+    }
+
+    #[test]
+    fn test_checked_arithmetic()
+    {
+        assert_eq!(Timestamp::MAX.checked_add(Duration::from_millis(1)), None);
+        assert_eq!(Timestamp(1).checked_sub(Duration::from_millis(2)), None);
+        assert_eq!(Timestamp(1).checked_add(Duration::MAX), None);
+        assert_eq!(Timestamp(1).checked_add(Duration::from_micros(1_999)), Some(Timestamp(2)));
+        assert_eq!(Timestamp::MAX.saturating_add(Duration::from_secs(1)), Timestamp::MAX);
+        assert_eq!(Timestamp(1).saturating_sub(Duration::from_secs(1)), Timestamp::EPOCH);
+    }
+
+    #[test]
+    #[should_panic(expected = "Timestamp subtraction underflow")]
+    fn test_sub_underflow_panics()
+    {
+        let _ = Timestamp(1) - Timestamp(2);
     }
 
     #[test]
@@ -189,4 +566,3 @@ mod test
     }
 
 }
-
@@ -0,0 +1,77 @@
+//! # U256 Bytes
+//!
+//! This module serializes a `U256` with exactly one valid binary encoding, for fields annotated with
+//! `#[serde(with = "core_utils::u256_bytes")]`.
+//!
+//! The serde implementation of `U256` writes a hexadecimal string without leading zeros but reads
+//! any hexadecimal string, so `0xa` and `0x000a` both decode to 10. Binary serializers, such as
+//! bincode, write the value as 32 big-endian bytes instead. Human-readable serializers, such as
+//! JSON, keep the hexadecimal string.
+
+use ethereum_types::U256;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// The number of bytes of an encoded value
+pub const LENGTH: usize = 32;
+
+// The 'serialize' function writes the value as 32 big-endian bytes, or as a hexadecimal string
+pub fn serialize<S>(value: &U256, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    if serializer.is_human_readable()
+    {
+        return value.serialize(serializer);
+    }
+    let mut bytes = [0u8; LENGTH];
+    value.to_big_endian(&mut bytes);
+    bytes.serialize(serializer)
+}
+
+// The 'deserialize' function reads a value written by 'serialize'
+pub fn deserialize<'de, D>(deserializer: D) -> Result<U256, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    if deserializer.is_human_readable()
+    {
+        return U256::deserialize(deserializer);
+    }
+    <[u8; LENGTH]>::deserialize(deserializer).map(|bytes| U256::from_big_endian(&bytes))
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Wrapper(#[serde(with = "super")] U256);
+
+    #[test]
+    fn test_binary_encoding_is_fixed()
+    {
+        let bytes = bincode::serialize(&Wrapper(U256::from(10))).unwrap();
+        assert_eq!(bytes.len(), LENGTH);
+        assert_eq!(bytes[LENGTH - 1], 10);
+        assert!(bytes[..LENGTH - 1].iter().all(|byte| *byte == 0));
+        assert_eq!(bincode::deserialize::<Wrapper>(&bytes).unwrap(), Wrapper(U256::from(10)));
+
+        let max = bincode::serialize(&Wrapper(U256::MAX)).unwrap();
+        assert_eq!(bincode::deserialize::<Wrapper>(&max).unwrap(), Wrapper(U256::MAX));
+    }
+
+    #[test]
+    fn test_binary_encoding_rejects_hex_string()
+    {
+        let padded = bincode::serialize("0x000a").unwrap();
+        assert!(bincode::deserialize::<Wrapper>(&padded).is_err());
+    }
+
+    #[test]
+    fn test_human_readable_encoding_is_hex()
+    {
+        assert_eq!(serde_json::to_string(&Wrapper(U256::from(10))).unwrap(), "\"0xa\"");
+        assert_eq!(serde_json::from_str::<Wrapper>("\"0xa\"").unwrap(), Wrapper(U256::from(10)));
+    }
+}
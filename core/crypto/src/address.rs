@@ -0,0 +1,252 @@
+//! # Account Addresses
+//!
+//! This module defines the `Address` type that identifies an account, and how it is derived from an
+//! ed25519 `PublicKey`.
+//!
+//! ## Derivation
+//!
+//! An address is the first `Address::LENGTH` bytes of the Keccak digest of the version byte followed
+//! by the public key bytes.
+//!
+//! ## Text Encoding
+//!
+//! Addresses are displayed as the `fr` prefix followed by the lowercase hexadecimal encoding of the
+//! version byte, the address bytes and a 4 byte checksum. The checksum is the beginning of the double
+//! SHA-256 digest of the version byte and the address bytes, so typing errors are detected on parsing.
+//!
+//! # Examples
+//!
+//! ```
+//! use crypto::ed25519::PrivateKey;
+//! use crypto::address::Address;
+//! use rand::rngs::OsRng;
+//!
+//! let public_key = PrivateKey::generate(&mut OsRng).to_public_key();
+//! let address = public_key.to_address();
+//! let text = address.to_string();
+//! assert_eq!(text.parse::<Address>().unwrap(), address);
+//! ```
+
+use crate::{ed25519::PublicKey, hash::{Algorithm, Hasher}};
+use serde::{Serialize, Deserialize};
+use std::{fmt, str::FromStr};
+
+// The version of the address derivation and text encoding
+pub const ADDRESS_VERSION: u8 = 0;
+// The prefix of the text encoding
+pub const ADDRESS_PREFIX: &str = "fr";
+// The length of the text encoding checksum in bytes
+pub const CHECKSUM_LENGTH: usize = 4;
+
+// An account address
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub struct Address([u8; Address::LENGTH]);
+
+// Represents errors that can occur while parsing an address.
+#[derive(Debug, PartialEq)]
+pub enum AddressError
+{
+    InvalidPrefix,
+    InvalidLength,
+    HexError,
+    UnsupportedVersion(u8),
+    InvalidChecksum,
+}
+
+impl Address
+{
+    // The length of the Address
+    pub const LENGTH: usize = 20;
+
+    // The zero address
+    pub const ZERO: Address = Address([0u8; Address::LENGTH]);
+
+    // The 'from_public_key' function derives the address of a public key.
+    pub fn from_public_key(public_key: &PublicKey) -> Self
+    {
+        let mut hasher = Hasher::new(Algorithm::Keccak);
+        hasher.update(&[ADDRESS_VERSION]).update(&public_key.to_bytes());
+        let digest = hasher.finalize();
+
+        let mut address = [0u8; Address::LENGTH];
+        address.copy_from_slice(&digest.as_ref()[..Address::LENGTH]);
+        Address(address)
+    }
+
+    // The 'to_bytes' function converts the address to a byte array.
+    pub fn to_bytes(&self) -> [u8; Address::LENGTH]
+    {
+        self.0
+    }
+
+    // The 'is_zero' function checks if the address is the zero address.
+    pub fn is_zero(&self) -> bool
+    {
+        self == &Address::ZERO
+    }
+
+    // The 'checksum' function computes the text encoding checksum of a version and address.
+    fn checksum(version: u8, address: &[u8; Address::LENGTH]) -> [u8; CHECKSUM_LENGTH]
+    {
+        let mut hasher = Hasher::new(Algorithm::SHA256);
+        hasher.update(&[version]).update(address);
+        let mut hasher_twice = Hasher::new(Algorithm::SHA256);
+        hasher_twice.update(hasher.finalize().as_ref());
+
+        let mut checksum = [0u8; CHECKSUM_LENGTH];
+        checksum.copy_from_slice(&hasher_twice.finalize().as_ref()[..CHECKSUM_LENGTH]);
+        checksum
+    }
+}
+
+impl PublicKey
+{
+    // The `to_address` function derives the account address of the public key.
+    pub fn to_address(&self) -> Address
+    {
+        Address::from_public_key(self)
+    }
+}
+
+impl From<[u8; Address::LENGTH]> for Address
+{
+    fn from(bytes: [u8; Address::LENGTH]) -> Self
+    {
+        Address(bytes)
+    }
+}
+
+impl AsRef<[u8]> for Address
+{
+    fn as_ref(&self) -> &[u8]
+    {
+        &self.0
+    }
+}
+
+impl fmt::Display for Address
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let mut bytes = Vec::with_capacity(1 + Address::LENGTH + CHECKSUM_LENGTH);
+        bytes.push(ADDRESS_VERSION);
+        bytes.extend_from_slice(&self.0);
+        bytes.extend_from_slice(&Address::checksum(ADDRESS_VERSION, &self.0));
+        write!(f, "{}{}", ADDRESS_PREFIX, hex::encode(bytes))
+    }
+}
+
+impl fmt::Debug for Address
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "Address({})", self)
+    }
+}
+
+impl FromStr for Address
+{
+    type Err = AddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let encoded = s.strip_prefix(ADDRESS_PREFIX).ok_or(AddressError::InvalidPrefix)?;
+        if encoded.len() != 2 * (1 + Address::LENGTH + CHECKSUM_LENGTH)
+        {
+            return Err(AddressError::InvalidLength);
+        }
+
+        let bytes = hex::decode(encoded).map_err(|_| AddressError::HexError)?;
+        let version = bytes[0];
+        if version != ADDRESS_VERSION
+        {
+            return Err(AddressError::UnsupportedVersion(version));
+        }
+
+        let mut address = [0u8; Address::LENGTH];
+        address.copy_from_slice(&bytes[1..1 + Address::LENGTH]);
+        if bytes[1 + Address::LENGTH..] != Address::checksum(version, &address)
+        {
+            return Err(AddressError::InvalidChecksum);
+        }
+
+        Ok(Address(address))
+    }
+}
+
+impl fmt::Display for AddressError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            AddressError::InvalidPrefix => write!(f, "Address must start with '{}'!", ADDRESS_PREFIX),
+            AddressError::InvalidLength => write!(f, "Invalid address length!"),
+            AddressError::HexError => write!(f, "Address is not valid hex!"),
+            AddressError::UnsupportedVersion(version) => write!(f, "Unsupported address version: {}", version),
+            AddressError::InvalidChecksum => write!(f, "Invalid address checksum!"),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::ed25519::PrivateKey;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_address_derivation_is_deterministic()
+    {
+        let mut csprng: OsRng = OsRng;
+        let private_key = PrivateKey::generate(&mut csprng);
+        let public_key = private_key.to_public_key();
+        let other_key = PrivateKey::generate(&mut csprng).to_public_key();
+
+        assert_eq!(public_key.to_address(), Address::from_public_key(&public_key));
+        assert_ne!(public_key.to_address(), other_key.to_address());
+    }
+
+    #[test]
+    fn test_known_text_encoding()
+    {
+        let address = Address::from([0x11; Address::LENGTH]);
+        let text = address.to_string();
+
+        assert!(text.starts_with("fr001111"));
+        assert_eq!(text.len(), ADDRESS_PREFIX.len() + 2 * (1 + Address::LENGTH + CHECKSUM_LENGTH));
+        assert_eq!(text.parse::<Address>(), Ok(address));
+        assert_eq!(text.to_uppercase().replacen("FR", "fr", 1).parse::<Address>(), Ok(address));
+    }
+
+    #[test]
+    fn test_text_round_trip()
+    {
+        let mut csprng: OsRng = OsRng;
+        for _ in 0..16
+        {
+            let address = PrivateKey::generate(&mut csprng).to_public_key().to_address();
+            assert_eq!(address.to_string().parse::<Address>(), Ok(address));
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_input()
+    {
+        let text = Address::from([0x22; Address::LENGTH]).to_string();
+
+        assert_eq!(text[2..].parse::<Address>(), Err(AddressError::InvalidPrefix));
+        assert_eq!(text[..text.len() - 2].parse::<Address>(), Err(AddressError::InvalidLength));
+        assert_eq!(text.replacen('2', "z", 1).parse::<Address>(), Err(AddressError::HexError));
+
+        let mut typo = text.clone().into_bytes();
+        typo[10] = if typo[10] == b'3' { b'4' } else { b'3' };
+        assert_eq!(String::from_utf8(typo).unwrap().parse::<Address>(), Err(AddressError::InvalidChecksum));
+
+        let other_version = text.replacen("fr00", "fr01", 1);
+        assert_eq!(other_version.parse::<Address>(), Err(AddressError::UnsupportedVersion(1)));
+    }
+}
@@ -38,7 +38,7 @@ use rand::rngs::OsRng;
 use ed25519_dalek::*;
 use anyhow::Result;
 use crate::hash::*;
-use serde::Serialize;
+use serde::{Serialize, Serializer, Deserialize, Deserializer, de::Error as DeError};
 use bincode::serialize_into;
 use ring::agreement::PublicKey as RingPublicKey;
 
@@ -78,6 +78,20 @@ impl PrivateKey {
 
         Signature(sign)
     }
+
+    // The `sign` function signs the hash of the serialized message.
+    // Signatures produced by it are checked with `Signature::verify`.
+    pub fn sign<T>(&self, message: &T) -> Result<Signature, anyhow::Error>
+    where
+        T: CryptoHash + Serialize + ?Sized,
+    {
+        let mut bytes = Vec::new();
+        serialize_into(&mut bytes, &message)
+            .map_err(|_| anyhow::anyhow!("SerializationError"))?;
+
+        let binding = bytes.hash()?;
+        Ok(self.sign_message(binding.as_ref()))
+    }
 }
 
 impl From<&PrivateKey> for PublicKey
@@ -127,12 +141,36 @@ impl PublicKey {
             return Err(anyhow::anyhow!("Invalid byte array length"));
         }
 
-        PublicKey::from_bytes(bytes).map_err(|_| anyhow::anyhow!("Failed to create public key"))
+        let public_key = ed25519_dalek::PublicKey::from_bytes(bytes)
+            .map_err(|_| anyhow::anyhow!("Failed to create public key"))?;
+
+        Ok(PublicKey(public_key))
+    }
+}
+
+impl Serialize for PublicKey
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    {
+        serializer.serialize_bytes(&self.to_bytes())
+    }
+}
+
+impl<'de> Deserialize<'de> for PublicKey
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>
+    {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        let bytes: [u8; Self::LENGTH] = bytes
+            .try_into()
+            .map_err(|_| D::Error::custom("Invalid public key length"))?;
+
+        PublicKey::from_bytes(&bytes).map_err(D::Error::custom)
     }
 }
 
 // An Ed25519 signature
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct Signature(ed25519_dalek::Signature);
 
 impl Signature {
@@ -178,12 +216,41 @@ impl Signature {
         let binding = bytes.hash()?;
         let hash_bytes = binding.as_ref();
 
-        if public_key.0.verify(&hash_bytes, &self.0).is_ok() {
+        if public_key.0.verify_strict(hash_bytes, &self.0).is_ok() {
             Ok(())
         } else {
             Err(anyhow::anyhow!("Signature verification failed!"))
         }
     }
+
+    // The `verify_message` function checks a signature produced by `PrivateKey::sign_message`.
+    pub fn verify_message(&self, message: &[u8], public_key: &PublicKey) -> Result<(), anyhow::Error>
+    {
+        public_key.0
+            .verify_strict(message, &self.0)
+            .map_err(|_| anyhow::anyhow!("Signature verification failed!"))
+    }
+}
+
+impl Serialize for Signature
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    {
+        serializer.serialize_bytes(&self.to_bytes())
+    }
+}
+
+impl<'de> Deserialize<'de> for Signature
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>
+    {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        let bytes: [u8; Self::LENGTH] = bytes
+            .try_into()
+            .map_err(|_| D::Error::custom("Invalid signature length"))?;
+
+        Signature::from_bytes(&bytes).map_err(D::Error::custom)
+    }
 }
 
 mod test {
@@ -238,6 +305,49 @@ mod test {
         assert_eq!(signature, restored_signature);
     }
 
+    #[test]
+    fn test_public_key_and_signature_serde_round_trip()
+    {
+        let mut csprng: OsRng = OsRng;
+        let private_key = PrivateKey::generate(&mut csprng);
+        let public_key = private_key.to_public_key();
+        let signature = private_key.sign_message(b"FRENYUM");
+
+        let bytes = bincode::serialize(&(public_key.clone(), signature.clone())).unwrap();
+        let restored: (PublicKey, Signature) = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(restored, (public_key, signature));
+
+        let truncated = bincode::serialize(&vec![0u8; PublicKey::LENGTH - 1]).unwrap();
+        assert!(bincode::deserialize::<PublicKey>(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_sign_serialized_message_verify()
+    {
+        let mut csprng: OsRng = OsRng;
+        let private_key = PrivateKey::generate(&mut csprng);
+        let public_key = private_key.to_public_key();
+        let other_key = PrivateKey::generate(&mut csprng).to_public_key();
+        let message = b"FRENYUM_OK".to_vec();
+
+        let signature = private_key.sign(&message).unwrap();
+        assert!(signature.verify(&message, &public_key).is_ok());
+        assert!(signature.verify(&b"FRENYUM_NO".to_vec(), &public_key).is_err());
+        assert!(signature.verify(&message, &other_key).is_err());
+    }
+
+    #[test]
+    fn test_sign_message_verify_message()
+    {
+        let mut csprng: OsRng = OsRng;
+        let private_key = PrivateKey::generate(&mut csprng);
+        let public_key = private_key.to_public_key();
+        let signature = private_key.sign_message(b"FRENYUM_OK");
+
+        assert!(signature.verify_message(b"FRENYUM_OK", &public_key).is_ok());
+        assert!(signature.verify_message(b"FRENYUM_NO", &public_key).is_err());
+    }
+
     // The failed test result will then be analyzed.
     #[test]
     fn test_signature_verify()
@@ -3,25 +3,38 @@
 //! This module provides hash computation capabilities using SHA-256, SHA-512 and Keccak algorithms.
 //! The HashDigest type represents a digest based on an array.
 //!
+//! SHA-512 digests are truncated to their first `HashDigest::LENGTH` bytes so that every
+//! algorithm produces a `HashDigest` of the same size.
+//!
+//! Inputs of arbitrary length can be hashed incrementally with a `Hasher`, which can also be used
+//! as an `io::Write` sink for serializers.
+//!
 //! # Examples
 //!
 //! ```rust
-//! use crate::crypto::{Algorithm, HashDigest};
+//! use crypto::hash::{Algorithm, HashDigest, Hasher};
 //! 
 //! let hash_result = HashDigest::calculate(b"FRENYUM_TEST", Algorithm::SHA256);
 //! match hash_result {
 //!     Ok(hash) => println!("SHA-256 hash: {:?}", hash),
 //!     Err(e) => eprintln!("Error: {:?}", e),
 //! }
+//!
+//! let mut hasher = Hasher::new(Algorithm::SHA256);
+//! hasher.update(b"FRENYUM_");
+//! hasher.update(b"TEST");
+//! assert_eq!(Ok(hasher.finalize()), HashDigest::calculate(b"FRENYUM_TEST", Algorithm::SHA256));
 //! ```
 
 use sha3::{Digest, Sha3_256};
-use ring::digest::*;
+use ring::digest::{Context, SHA256, SHA512};
 use anyhow::Error as AnyhowError;
+use serde::{Serialize, Deserialize};
 use std::default::Default;
+use std::io;
 
 // Output value of our hash function.
-#[derive(Clone, Copy, PartialEq, Default)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
 pub struct HashDigest(pub [u8; HashDigest::LENGTH]);
 
 // Represents errors that can occur during hash operations.
@@ -30,12 +43,11 @@ pub enum HashError
 {
     HexError,
     SerializationError,
-    DataTooLong,
-    EmptyData,
     UnsupportedAlgorithm,
 }
 
 // Represents supported hashing algorithms.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Algorithm
 {
     Keccak,
@@ -51,46 +63,12 @@ impl HashDigest
     pub const LENGTH: usize = 32;
     
     // The 'calculate' function calculates a summary using the specified algorithm on the specified data.
+    // Empty data is hashed like any other input, as with a `Hasher` given no input.
     pub fn calculate(bytes: &[u8], algorithm: Algorithm) -> Result<HashDigest, HashError>
-    {   
-         // Check the data length and return an error if necessary.
-        if bytes.len() > HashDigest::LENGTH 
-        {
-           return Err(HashError::DataTooLong);
-        }
-
-        // Check if the data is empty and return an error if necessary.
-        if bytes.is_empty()
-        {
-            return Err(HashError::EmptyData);
-        }
-        
-        let mut hash_digest = [0u8; HashDigest::LENGTH];
-        
-        // Calculate the hash using the specified algorithm.
-        match algorithm 
-        {   
-            Algorithm::SHA256 => {
-                let mut context = Context::new(&SHA256);
-                context.update(bytes);
-                let result = context.finish();
-                hash_digest.copy_from_slice(result.as_ref());
-                Ok(HashDigest(hash_digest))
-            }
-            Algorithm::SHA512 => {
-                let mut context = Context::new(&SHA512);
-                context.update(bytes);
-                let result = context.finish();
-                hash_digest.copy_from_slice(result.as_ref());
-                Ok(HashDigest(hash_digest))
-            }
-            Algorithm::Keccak =>
-            {
-                 let hash_digest: [u8; HashDigest::LENGTH] = Sha3_256::digest(bytes).into();
-                 Ok(HashDigest(hash_digest))
-            }
-            _ => return Err(HashError::UnsupportedAlgorithm),
-        }
+    {
+        let mut hasher = Hasher::new(algorithm);
+        hasher.update(bytes);
+        Ok(hasher.finalize())
     }
 
     // The 'eq' function checks if two `HashDigests` are equal.
@@ -152,10 +130,8 @@ impl std::fmt::Display for HashError
     {
         match self 
         {
-            HashError::DataTooLong => write!(f, "Data is too long!"),
             HashError::HexError => write!(f, "Hex failed!"),
             HashError::SerializationError => write!(f, "Serialization failed!"),
-            HashError::EmptyData => write!(f, "Data is empty!"),
             HashError::UnsupportedAlgorithm => write!(f, "Unsupported algorithm!"),
         }
     }
@@ -187,6 +163,99 @@ pub fn hex_digest(algorithm: Algorithm, data: &[u8]) -> Result<HashDigest, HashE
     }
 }
 
+// The 'serialized_digest' function calculates a summary of the bincode encoding of a value.
+// The value is streamed into the hasher, so no intermediate buffer is allocated.
+pub fn serialized_digest<T>(algorithm: Algorithm, value: &T) -> Result<HashDigest, HashError>
+where
+    T: Serialize + ?Sized,
+{
+    let mut hasher = Hasher::new(algorithm);
+    bincode::serialize_into(&mut hasher, value)
+        .map_err(|_| HashError::SerializationError)?;
+    Ok(hasher.finalize())
+}
+
+// The internal state of a `Hasher` for each supported algorithm.
+#[derive(Clone)]
+enum HasherState
+{
+    Ring(Context),
+    Keccak(Sha3_256),
+}
+
+// An incremental hasher accepting input of any length.
+#[derive(Clone)]
+pub struct Hasher
+{
+    algorithm: Algorithm,
+    state: HasherState,
+}
+
+impl Hasher
+{
+    // The 'new' function creates an empty hasher for the given algorithm.
+    pub fn new(algorithm: Algorithm) -> Self
+    {
+        let state = match algorithm
+        {
+            Algorithm::SHA256 => HasherState::Ring(Context::new(&SHA256)),
+            Algorithm::SHA512 => HasherState::Ring(Context::new(&SHA512)),
+            Algorithm::Keccak => HasherState::Keccak(Sha3_256::new()),
+        };
+
+        Hasher { algorithm, state }
+    }
+
+    // The 'algorithm' function returns the algorithm used by the hasher.
+    pub fn algorithm(&self) -> Algorithm
+    {
+        self.algorithm
+    }
+
+    // The 'update' function feeds more data into the hasher.
+    pub fn update(&mut self, bytes: &[u8]) -> &mut Self
+    {
+        match &mut self.state
+        {
+            HasherState::Ring(context) => context.update(bytes),
+            HasherState::Keccak(keccak) => Digest::update(keccak, bytes),
+        }
+        self
+    }
+
+    // The 'finalize' function consumes the hasher and returns the digest.
+    // SHA-512 output is truncated to the first `HashDigest::LENGTH` bytes.
+    pub fn finalize(self) -> HashDigest
+    {
+        let mut hash_digest = [0u8; HashDigest::LENGTH];
+        match self.state
+        {
+            HasherState::Ring(context) => {
+                let result = context.finish();
+                hash_digest.copy_from_slice(&result.as_ref()[..HashDigest::LENGTH]);
+            }
+            HasherState::Keccak(keccak) => {
+                hash_digest.copy_from_slice(&keccak.finalize());
+            }
+        }
+        HashDigest(hash_digest)
+    }
+}
+
+impl io::Write for Hasher
+{
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize>
+    {
+        self.update(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()>
+    {
+        Ok(())
+    }
+}
+
 // 'CryptoHash` defines the functions required to compute a hash of data.
 pub trait CryptoHash 
 {
@@ -197,11 +266,65 @@ impl CryptoHash for [u8]
 {
     fn hash(&self) -> Result<HashDigest, HashError>
     {
-        let hash = hex_digest(Algorithm::SHA256, self)?;
-        let hash_bytes = hex::decode(hash.as_ref())
-            .map_err(|_| HashError::HexError)?;
+        let mut hasher = Hasher::new(Algorithm::SHA256);
+        hasher.update(self);
+        Ok(hasher.finalize())
+    }
+}
 
-        Ok(hash_bytes.into())
+impl CryptoHash for Vec<u8>
+{
+    fn hash(&self) -> Result<HashDigest, HashError>
+    {
+        self.as_slice().hash()
+    }
+}
+
+impl CryptoHash for str
+{
+    fn hash(&self) -> Result<HashDigest, HashError>
+    {
+        self.as_bytes().hash()
+    }
+}
+
+impl CryptoHash for String
+{
+    fn hash(&self) -> Result<HashDigest, HashError>
+    {
+        self.as_bytes().hash()
+    }
+}
+
+impl CryptoHash for HashDigest
+{
+    fn hash(&self) -> Result<HashDigest, HashError>
+    {
+        self.0.hash()
+    }
+}
+
+impl<const N: usize> CryptoHash for [u8; N]
+{
+    fn hash(&self) -> Result<HashDigest, HashError>
+    {
+        self.as_slice().hash()
+    }
+}
+
+impl CryptoHash for u64
+{
+    fn hash(&self) -> Result<HashDigest, HashError>
+    {
+        self.to_le_bytes().hash()
+    }
+}
+
+impl CryptoHash for u32
+{
+    fn hash(&self) -> Result<HashDigest, HashError>
+    {
+        self.to_le_bytes().hash()
     }
 }
 
@@ -223,6 +346,20 @@ mod tests
         assert_eq!(hashv1, Ok(HashDigest::from(hashv2)));
     }
 
+    #[test]
+    fn test_empty_data()
+    {
+        let sha256 = [
+            0xE3, 0xB0, 0xC4, 0x42, 0x98, 0xFC, 0x1C, 0x14, 0x9A, 0xFB, 0xF4, 0xC8, 0x99, 0x6F, 0xB9, 0x24,
+            0x27, 0xAE, 0x41, 0xE4, 0x64, 0x9B, 0x93, 0x4C, 0xA4, 0x95, 0x99, 0x1B, 0x78, 0x52, 0xB8, 0x55,
+        ];
+        assert_eq!(HashDigest::calculate(&[], Algorithm::SHA256), Ok(HashDigest::from(sha256)));
+        for algorithm in [Algorithm::Keccak, Algorithm::SHA256, Algorithm::SHA512]
+        {
+            assert_eq!(HashDigest::calculate(&[], algorithm), Ok(Hasher::new(algorithm).finalize()));
+        }
+    }
+
     #[test]
     fn test_keccak_eq_hash()
     {
@@ -259,10 +396,65 @@ mod tests
 
     }
 
-}
+    #[test]
+    fn test_calculate_accepts_long_data()
+    {
+        let hash = HashDigest::calculate(b"The quick brown fox jumps over the lazy dog", Algorithm::SHA256);
+        let expected = [
+        0xD7, 0xA8, 0xFB, 0xB3, 0x07, 0xD7, 0x80, 0x94, 0x69, 0xCA, 0x9A, 0xBC, 0xB0, 0x08,
+        0x2E, 0x4F, 0x8D, 0x56, 0x51, 0xE4, 0x6D, 0x3C, 0xDB, 0x76, 0x2D, 0x02, 0xD0, 0xBF,
+        0x37, 0xC9, 0xE5, 0x92
+        ];
 
+        assert_eq!(hash, Ok(HashDigest::from(expected)));
+    }
 
+    #[test]
+    fn test_sha512_hash_is_truncated()
+    {
+        let hash = HashDigest::calculate(b"abc", Algorithm::SHA512);
+        let expected = [
+        0xDD, 0xAF, 0x35, 0xA1, 0x93, 0x61, 0x7A, 0xBA, 0xCC, 0x41, 0x73, 0x49, 0xAE, 0x20,
+        0x41, 0x31, 0x12, 0xE6, 0xFA, 0x4E, 0x89, 0xA9, 0x7E, 0xA2, 0x0A, 0x9E, 0xEE, 0xE6,
+        0x4B, 0x55, 0xD3, 0x9A
+        ];
 
+        assert_eq!(hash, Ok(HashDigest::from(expected)));
+    }
 
+    #[test]
+    fn test_hasher_incremental_matches_calculate()
+    {
+        let data = vec![0xABu8; 10_000];
+        for algorithm in [Algorithm::SHA256, Algorithm::SHA512, Algorithm::Keccak]
+        {
+            let mut hasher = Hasher::new(algorithm);
+            for chunk in data.chunks(333)
+            {
+                hasher.update(chunk);
+            }
+
+            assert_eq!(Ok(hasher.finalize()), HashDigest::calculate(&data, algorithm));
+        }
+    }
+
+    #[test]
+    fn test_crypto_hash_for_common_types()
+    {
+        let expected = HashDigest::calculate(b"FRENYUM", Algorithm::SHA256).unwrap();
+
+        assert_eq!(b"FRENYUM"[..].hash(), Ok(expected));
+        assert_eq!(b"FRENYUM".to_vec().hash(), Ok(expected));
+        assert_eq!("FRENYUM".hash(), Ok(expected));
+        assert_eq!(String::from("FRENYUM").hash(), Ok(expected));
+    }
 
+    #[test]
+    fn test_serialized_digest()
+    {
+        let value = (42u64, String::from("FRENYUM"), vec![1u8; 100]);
+        let bytes = bincode::serialize(&value).unwrap();
 
+        assert_eq!(serialized_digest(Algorithm::Keccak, &value), HashDigest::calculate(&bytes, Algorithm::Keccak));
+    }
+}
@@ -1,2 +1,4 @@
 pub mod hash;
 pub mod ed25519;
+pub mod merkle;
+pub mod address;
@@ -0,0 +1,351 @@
+//! # Binary Merkle Tree
+//!
+//! This module builds binary Merkle trees over a list of leaf hashes, such as the hashes of the
+//! transactions in a block body. The root commits to every leaf and its position.
+//!
+//! Leaves and inner nodes are hashed with distinct one byte prefixes, so an inner node can never be
+//! passed off as a leaf (second-preimage protection). When a level has an odd number of nodes, the
+//! last node is promoted to the next level unchanged instead of being paired with a copy of itself.
+//! The root of an empty tree is `HashDigest::default()`.
+//!
+//! The root does not commit to the number of leaves, and because of the promoted nodes a proof can
+//! verify for several positions in trees of different sizes. A proof is therefore checked against
+//! the position and leaf count the verifier expects, such as the size of a block body.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use crypto::hash::{Algorithm, HashDigest};
+//! use crypto::merkle::MerkleTree;
+//!
+//! let leaves: Vec<HashDigest> = (0u8..5)
+//!     .map(|i| HashDigest::calculate(&[i], Algorithm::SHA256).unwrap())
+//!     .collect();
+//!
+//! let tree = MerkleTree::new(&leaves);
+//! let proof = tree.proof(3).unwrap();
+//! assert!(proof.verify(&tree.root(), &leaves[3], 3, leaves.len()).is_ok());
+//! ```
+
+use crate::hash::{Algorithm, HashDigest, Hasher};
+
+// Prefix used when hashing a leaf node.
+pub const LEAF_PREFIX: u8 = 0x00;
+// Prefix used when hashing an inner node.
+pub const NODE_PREFIX: u8 = 0x01;
+
+// Represents errors that can occur during Merkle proof operations.
+#[derive(Debug, PartialEq)]
+pub enum MerkleError
+{
+    IndexOutOfRange,
+    InvalidProof,
+    PositionMismatch,
+}
+
+impl std::fmt::Display for MerkleError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            MerkleError::IndexOutOfRange => write!(f, "Leaf index is out of range!"),
+            MerkleError::InvalidProof => write!(f, "Merkle proof is invalid!"),
+            MerkleError::PositionMismatch => write!(f, "Merkle proof is for another leaf position!"),
+        }
+    }
+}
+
+// The 'hash_leaf' function hashes a leaf with the leaf domain prefix.
+pub fn hash_leaf(leaf: &HashDigest) -> HashDigest
+{
+    let mut hasher = Hasher::new(Algorithm::SHA256);
+    hasher.update(&[LEAF_PREFIX]).update(leaf.as_ref());
+    hasher.finalize()
+}
+
+// The 'hash_node' function hashes two children with the inner node domain prefix.
+pub fn hash_node(left: &HashDigest, right: &HashDigest) -> HashDigest
+{
+    let mut hasher = Hasher::new(Algorithm::SHA256);
+    hasher.update(&[NODE_PREFIX]).update(left.as_ref()).update(right.as_ref());
+    hasher.finalize()
+}
+
+// The 'merkle_root' function computes the root of the tree built over the given leaves.
+pub fn merkle_root(leaves: &[HashDigest]) -> HashDigest
+{
+    MerkleTree::new(leaves).root()
+}
+
+// A binary Merkle tree keeping every level, from the hashed leaves up to the root.
+#[derive(Debug, Clone)]
+pub struct MerkleTree
+{
+    levels: Vec<Vec<HashDigest>>,
+}
+
+impl MerkleTree
+{
+    // The 'new' function builds a tree from a list of leaf hashes.
+    pub fn new(leaves: &[HashDigest]) -> Self
+    {
+        let mut levels = vec![leaves.iter().map(hash_leaf).collect::<Vec<_>>()];
+
+        while levels[levels.len() - 1].len() > 1
+        {
+            let level = &levels[levels.len() - 1];
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        MerkleTree { levels }
+    }
+
+    // The 'root' function returns the root of the tree.
+    pub fn root(&self) -> HashDigest
+    {
+        self.levels[self.levels.len() - 1]
+            .first()
+            .copied()
+            .unwrap_or_default()
+    }
+
+    // The 'len' function returns the number of leaves.
+    pub fn len(&self) -> usize
+    {
+        self.levels[0].len()
+    }
+
+    // The 'is_empty' function checks if the tree has no leaves.
+    pub fn is_empty(&self) -> bool
+    {
+        self.levels[0].is_empty()
+    }
+
+    // The 'proof' function generates an inclusion proof for the leaf at the given index.
+    pub fn proof(&self, index: usize) -> Result<MerkleProof, MerkleError>
+    {
+        if index >= self.len()
+        {
+            return Err(MerkleError::IndexOutOfRange);
+        }
+
+        let mut siblings = Vec::new();
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1]
+        {
+            let sibling = position ^ 1;
+            if sibling < level.len()
+            {
+                siblings.push(level[sibling]);
+            }
+            position /= 2;
+        }
+
+        Ok(MerkleProof {
+            index,
+            leaf_count: self.len(),
+            siblings,
+        })
+    }
+}
+
+// An inclusion proof for a single leaf.
+// Only the siblings that exist are stored; promoted nodes are derived from `leaf_count`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MerkleProof
+{
+    // Position of the leaf in the tree
+    index: usize,
+    // Number of leaves in the tree
+    leaf_count: usize,
+    // Sibling hashes from the leaf level upwards
+    siblings: Vec<HashDigest>,
+}
+
+impl MerkleProof
+{
+    // The 'new' function creates a proof from its parts, e.g. after receiving it from a peer.
+    pub fn new(index: usize, leaf_count: usize, siblings: Vec<HashDigest>) -> Self
+    {
+        MerkleProof { index, leaf_count, siblings }
+    }
+
+    // The 'index' function returns the position of the proven leaf.
+    pub fn index(&self) -> usize { self.index }
+
+    // The 'leaf_count' function returns the number of leaves in the tree.
+    pub fn leaf_count(&self) -> usize { self.leaf_count }
+
+    // The 'siblings' function returns the sibling hashes of the proof.
+    pub fn siblings(&self) -> &[HashDigest] { &self.siblings }
+
+    // The 'verify' function checks that the leaf is included at position `index` in the tree of
+    // `leaf_count` leaves with the given root. Both are supplied by the verifier, since the root
+    // does not bind the position claimed by the proof.
+    pub fn verify(&self, root: &HashDigest, leaf: &HashDigest, index: usize, leaf_count: usize) -> Result<(), MerkleError>
+    {
+        if self.index != index || self.leaf_count != leaf_count
+        {
+            return Err(MerkleError::PositionMismatch);
+        }
+        if self.index >= self.leaf_count
+        {
+            return Err(MerkleError::IndexOutOfRange);
+        }
+
+        let mut siblings = self.siblings.iter();
+        let mut node = hash_leaf(leaf);
+        let mut position = self.index;
+        let mut width = self.leaf_count;
+
+        while width > 1
+        {
+            let is_promoted = position == width - 1 && !width.is_multiple_of(2);
+            if !is_promoted
+            {
+                let sibling = siblings.next().ok_or(MerkleError::InvalidProof)?;
+                node = if position.is_multiple_of(2) {
+                    hash_node(&node, sibling)
+                } else {
+                    hash_node(sibling, &node)
+                };
+            }
+            position /= 2;
+            width = width.div_ceil(2);
+        }
+
+        if siblings.next().is_some() || &node != root
+        {
+            return Err(MerkleError::InvalidProof);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn leaves(count: u8) -> Vec<HashDigest>
+    {
+        (0..count)
+            .map(|i| HashDigest::calculate(&[i], Algorithm::SHA256).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_empty_tree_root()
+    {
+        let tree = MerkleTree::new(&[]);
+        assert!(tree.is_empty());
+        assert_eq!(tree.root(), HashDigest::default());
+        assert_eq!(tree.proof(0), Err(MerkleError::IndexOutOfRange));
+    }
+
+    #[test]
+    fn test_single_leaf_root()
+    {
+        let leaves = leaves(1);
+        assert_eq!(merkle_root(&leaves), hash_leaf(&leaves[0]));
+    }
+
+    #[test]
+    fn test_root_of_three_leaves()
+    {
+        let leaves = leaves(3);
+        let expected = hash_node(
+            &hash_node(&hash_leaf(&leaves[0]), &hash_leaf(&leaves[1])),
+            &hash_leaf(&leaves[2]),
+        );
+        assert_eq!(merkle_root(&leaves), expected);
+    }
+
+    #[test]
+    fn test_root_depends_on_order()
+    {
+        let mut leaves = leaves(4);
+        let root = merkle_root(&leaves);
+        leaves.swap(0, 1);
+        assert_ne!(merkle_root(&leaves), root);
+    }
+
+    #[test]
+    fn test_proofs_verify_for_every_leaf()
+    {
+        for count in 1..=17
+        {
+            let leaves = leaves(count);
+            let tree = MerkleTree::new(&leaves);
+            for (index, leaf) in leaves.iter().enumerate()
+            {
+                let proof = tree.proof(index).unwrap();
+                assert_eq!(proof.verify(&tree.root(), leaf, index, leaves.len()), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_proof_rejects_wrong_leaf_and_root()
+    {
+        let leaves = leaves(6);
+        let tree = MerkleTree::new(&leaves);
+        let proof = tree.proof(2).unwrap();
+
+        assert_eq!(proof.verify(&tree.root(), &leaves[3], 2, 6), Err(MerkleError::InvalidProof));
+        assert_eq!(proof.verify(&leaves[0], &leaves[2], 2, 6), Err(MerkleError::InvalidProof));
+    }
+
+    #[test]
+    fn test_proof_rejects_tampered_siblings()
+    {
+        let leaves = leaves(5);
+        let tree = MerkleTree::new(&leaves);
+        let proof = tree.proof(1).unwrap();
+
+        let mut extra = proof.siblings().to_vec();
+        extra.push(leaves[0]);
+        let proof = MerkleProof::new(proof.index(), proof.leaf_count(), extra);
+        assert_eq!(proof.verify(&tree.root(), &leaves[1], 1, 5), Err(MerkleError::InvalidProof));
+    }
+
+    #[test]
+    fn test_proof_rejects_other_position()
+    {
+        let leaves = leaves(5);
+        let tree = MerkleTree::new(&leaves);
+        let proof = tree.proof(4).unwrap();
+        assert_eq!(proof.verify(&tree.root(), &leaves[4], 4, 5), Ok(()));
+
+        // The last leaf is promoted twice, so its path is also that of index 2 in a 3 leaf tree.
+        let reindexed = MerkleProof::new(2, 3, proof.siblings().to_vec());
+        assert_eq!(reindexed.verify(&tree.root(), &leaves[4], 2, 3), Ok(()));
+        // Checked against the real tree size, the reindexed proof is refused.
+        assert_eq!(reindexed.verify(&tree.root(), &leaves[4], 2, 5), Err(MerkleError::PositionMismatch));
+        assert_eq!(proof.verify(&tree.root(), &leaves[4], 2, 5), Err(MerkleError::PositionMismatch));
+        let relabelled = MerkleProof::new(2, 5, proof.siblings().to_vec());
+        assert_eq!(relabelled.verify(&tree.root(), &leaves[4], 2, 5), Err(MerkleError::InvalidProof));
+    }
+
+    #[test]
+    fn test_inner_node_cannot_be_proven_as_leaf()
+    {
+        let leaves = leaves(4);
+        let tree = MerkleTree::new(&leaves);
+        let inner = hash_node(&hash_leaf(&leaves[0]), &hash_leaf(&leaves[1]));
+        let right = hash_node(&hash_leaf(&leaves[2]), &hash_leaf(&leaves[3]));
+
+        let forged = MerkleProof::new(0, 2, vec![right]);
+        assert_eq!(forged.verify(&tree.root(), &inner, 0, 2), Err(MerkleError::InvalidProof));
+    }
+}
@@ -0,0 +1,98 @@
+use crypto::hash::HashDigest;
+use serde::{Serialize, Deserialize};
+use crate::U256;
+
+// Struct representing the state of an account
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct Account
+{
+    // Balance of the account
+    #[serde(with = "core_utils::u256_bytes")]
+    balance: U256,
+    // Number of transactions sent from the account
+    #[serde(with = "core_utils::u256_bytes")]
+    nonce: U256,
+    // Hash of the account code, if any
+    code_hash: Option<HashDigest>,
+}
+
+impl Account
+{
+    // The 'new' function creates a new account
+    pub fn new(balance: U256, nonce: U256, code_hash: Option<HashDigest>) -> Self
+    {
+        Account {
+            balance,
+            nonce,
+            code_hash,
+        }
+    }
+
+    // The 'with_balance' function creates an account without code holding the given balance
+    pub fn with_balance(balance: U256) -> Self
+    {
+        Account::new(balance, U256::zero(), None)
+    }
+
+    // The 'balance' function returns the balance of the account
+    pub fn balance(&self) -> &U256 { &self.balance }
+
+    // The 'nonce' function returns the nonce of the account
+    pub fn nonce(&self) -> &U256 { &self.nonce }
+
+    // The 'code_hash' function returns the hash of the account code
+    pub fn code_hash(&self) -> Option<&HashDigest> { self.code_hash.as_ref() }
+
+    // The 'set_balance' function sets the balance of the account
+    pub fn set_balance(&mut self, balance: U256)
+    {
+        self.balance = balance;
+    }
+
+    // The 'set_nonce' function sets the nonce of the account
+    pub fn set_nonce(&mut self, nonce: U256)
+    {
+        self.nonce = nonce;
+    }
+
+    // The 'set_code_hash' function sets the hash of the account code
+    pub fn set_code_hash(&mut self, code_hash: Option<HashDigest>)
+    {
+        self.code_hash = code_hash;
+    }
+
+    // The 'is_empty' function checks if the account has no balance, nonce or code
+    pub fn is_empty(&self) -> bool
+    {
+        self.balance.is_zero() && self.nonce.is_zero() && self.code_hash.is_none()
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_default_account_is_empty()
+    {
+        let account = Account::default();
+        assert!(account.is_empty());
+        assert_eq!(account.code_hash(), None);
+    }
+
+    #[test]
+    fn test_account_setters()
+    {
+        let mut account = Account::with_balance(U256::from(100));
+        assert!(!account.is_empty());
+
+        account.set_balance(U256::from(50));
+        account.set_nonce(U256::from(1));
+        account.set_code_hash(Some(HashDigest::default()));
+
+        assert_eq!(account.balance(), &U256::from(50));
+        assert_eq!(account.nonce(), &U256::from(1));
+        assert_eq!(account.code_hash(), Some(&HashDigest::default()));
+    }
+}
@@ -1,7 +1,9 @@
 use crate::{block_header::BlockHeader, transaction::SignedTransaction};
-use core_utils::gas::Gas;
+use crypto::{hash::HashDigest, merkle::{MerkleTree, MerkleProof, MerkleError}};
+use serde::{Serialize, Deserialize};
 use std::{sync::Arc, fmt::Formatter, fmt};
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Block
 {
     header: BlockHeader,
@@ -26,6 +28,12 @@ impl Block
     {
         &self.body
     }
+
+    // The 'verify_transaction_root' function checks that the header commits to the body transactions
+    pub fn verify_transaction_root(&self) -> bool
+    {
+        self.header.transaction_root() == &self.body.compute_transaction_root()
+    }
 }
 
 impl fmt::Display for Block
@@ -45,11 +53,15 @@ impl fmt::Display for Block
                Timestamp: {}
                Nonce: {}
                Total Difficulty: {}
+               Base Fee: {}
+               Gas Limit: {}
+               Gas Used: {}
+               Coinbase: {}
+               State Root: {}
                Transaction Root: {}
+               Receipts Root: {}
              Body: 
-               Transactions: {:?}
-               Gas Used: {}
-               Gas Limit: {}",
+               Transactions: {:?}",
             self.header.hash(),
             self.header.protocol_version(),
             self.header.parent_hash(),
@@ -59,32 +71,29 @@ impl fmt::Display for Block
             self.header.timestamp(),
             self.header.nonce(),
             self.header.total_difficulty(),
+            self.header.base_fee(),
+            self.header.gas_limit(),
+            self.header.gas_used(),
+            self.header.coinbase(),
+            self.header.state_root(),
             self.header.transaction_root(),
-            self.body.transaction(),
-            self.body.gas_used(),
-            self.body.gas_limit()
+            self.header.receipts_root(),
+            self.body.transaction()
         )
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct BlockBody
 {
     transaction: Vec<Arc<SignedTransaction>>,
-    gas_used: Gas,
-    gas_limit: Gas,
 }
 
 impl BlockBody
 {
-    pub fn new(
-        transaction: Vec<Arc<SignedTransaction>>, 
-        gas_used: Gas, 
-        gas_limit: Gas
-        ) -> Self {
+    pub fn new(transaction: Vec<Arc<SignedTransaction>>) -> Self {
            BlockBody {
                transaction,
-               gas_used,
-               gas_limit,
            }
     }
 
@@ -93,14 +102,22 @@ impl BlockBody
         &self.transaction
     }
 
-    pub fn gas_used(&self) -> &Gas
+    // The 'transaction_hashes' function returns the hashes of the transactions in block order
+    pub fn transaction_hashes(&self) -> Vec<HashDigest>
     {
-        &self.gas_used
+        self.transaction.iter().map(|tx| *tx.get_hash()).collect()
     }
 
-    pub fn gas_limit(&self) -> &Gas
+    // The 'compute_transaction_root' function computes the Merkle root of the transaction hashes
+    pub fn compute_transaction_root(&self) -> HashDigest
     {
-        &self.gas_limit
+        MerkleTree::new(&self.transaction_hashes()).root()
+    }
+
+    // The 'transaction_proof' function generates an inclusion proof for the transaction at `index`
+    pub fn transaction_proof(&self, index: usize) -> Result<MerkleProof, MerkleError>
+    {
+        MerkleTree::new(&self.transaction_hashes()).proof(index)
     }
 }
 
@@ -110,10 +127,61 @@ impl fmt::Display for BlockBody
     {
         write!(
             formatter,
-            "Block Body: \nTransactions: {:?}\nGas Used: {:?}\nGas Limit: {:?}",
-            self.transaction,
-            self.gas_used,
-            self.gas_limit
+            "Block Body: \nTransactions: {:?}",
+            self.transaction
         )
     }
 }
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::{U256, Address, Bytes};
+    use crate::block_header::BlockHeaderBuilder;
+    use crate::transaction::{RawTransaction, Action, TransferAction};
+    use core_utils::gas::Gas;
+    use crypto::ed25519::PrivateKey;
+    use rand::rngs::OsRng;
+
+    fn signed_transaction(nonce: u64) -> Arc<SignedTransaction>
+    {
+        let mut csprng: OsRng = OsRng;
+        let private_key = PrivateKey::generate(&mut csprng);
+        let raw_transaction = RawTransaction::new(
+            1,
+            U256::from(nonce),
+            Action::Transfer(TransferAction { to: Address::from([1; 20]), amount: U256::from(10) }),
+            Gas::from(1),
+            Gas::from(21000),
+            U256::from(10),
+            Bytes::new(),
+        );
+        Arc::new(raw_transaction.sign(&private_key))
+    }
+
+    #[test]
+    fn test_transaction_root_matches_header()
+    {
+        let transactions: Vec<_> = (0..3).map(signed_transaction).collect();
+        let body = BlockBody::new(transactions);
+        let root = body.compute_transaction_root();
+
+        let header = BlockHeaderBuilder::new().set_transaction_root(root).build();
+        let block = Block::new(header, body);
+        assert!(block.verify_transaction_root());
+
+        let proof = block.body().transaction_proof(2).unwrap();
+        let leaf = block.body().transaction()[2].get_hash();
+        assert!(proof.verify(&root, leaf, 2, block.body().transaction().len()).is_ok());
+    }
+
+    #[test]
+    fn test_transaction_root_mismatch()
+    {
+        let body = BlockBody::new(vec![signed_transaction(0)]);
+        let header = BlockHeaderBuilder::new().set_transaction_root(HashDigest::default()).build();
+        let block = Block::new(header, body);
+        assert!(!block.verify_transaction_root());
+    }
+}
@@ -2,20 +2,25 @@
 //!
 //! This module defines the `BlockHeader` struct, which represents the header of a blockchain block.
 //! It contains various metadata about the block, such as the hash, protocol version, parent hash,
-//! block number, block height, difficulty, timestamp, nonce, total difficulty, gas used, gas limit,
-//! and transaction root.
+//! block number, block height, difficulty, timestamp, nonce, total difficulty, base fee, gas limit,
+//! gas used, coinbase, state root, transaction root, receipts root and logs bloom.
+//!
+//! The hash of a header is computed over its canonical encoding, which is a version byte followed
+//! by every other header field in a fixed order and byte layout. The nonce is encoded last, so the
+//! encoding is the concatenation of the header without nonce and the nonce. The version byte is
+//! bumped whenever the layout changes, and `decode_canonical` refuses encodings of another version.
 //!
 //! ## Example
 //!
 //! ```
-//! use crate::{BlockHeader, BlockHeaderBuilder};
+//! use primvites::block_header::{BlockHeader, BlockHeaderBuilder};
+//! use core_utils::{gas::Gas, timestamp::Timestamp};
+//! use primvites::{Address, U256};
 //! use crypto::hash::HashDigest;
-//! use core_utils::timestamp::Timestamp;
-//! use crate::{BlockNumber, BlockHeight, U256};
+//! use primvites::bloom::Bloom;
 //!
 //! // Create a new BlockHeader using the builder pattern
 //! let header = BlockHeaderBuilder::new()
-//!     .set_hash(HashDigest::default())
 //!     .set_protocol_version(1)
 //!     .set_parent_hash(HashDigest::default())
 //!     .set_block_number(123)
@@ -24,21 +29,59 @@
 //!     .set_timestamp(Timestamp::now())
 //!     .set_nonce(U256::from(12345))
 //!     .set_total_difficulty(U256::from(5000))
+//!     .set_base_fee(Gas::new(1_000))
+//!     .set_gas_limit(Gas::new(30_000_000))
+//!     .set_gas_used(Gas::new(21_000))
+//!     .set_coinbase(Address::from([7; 20]))
+//!     .set_state_root(HashDigest::default())
 //!     .set_transaction_root(HashDigest::default())
-//!     .build();
+//!     .set_receipts_root(HashDigest::default())
+//!     .set_logs_bloom(Bloom::new())
+//!     .seal();
 //!
 //! // Now you can use the `header` object
 //! assert_eq!(header.protocol_version(), 1);
 //! assert_eq!(header.block_number(), 123);
+//! assert!(header.verify_hash());
 //! ```
 //!
 //! Note: This is just an example. Actual values may vary depending on the use case.
 
-use crypto::hash::HashDigest;
-use core_utils::timestamp::Timestamp;
-use crate::{BlockNumber, BlockHeight, U256};
+use crypto::hash::{HashDigest, Hasher, Algorithm};
+use core_utils::{gas::Gas, timestamp::Timestamp};
+use serde::{Serialize, Deserialize};
+use crate::{Address, BlockNumber, BlockHeight, U256, bloom::Bloom};
+
+// The version of the canonical header encoding
+pub const HEADER_ENCODING_VERSION: u8 = 7;
+
+// Represents errors that can occur while decoding a canonical header encoding.
+#[derive(Debug, PartialEq)]
+pub enum HeaderError
+{
+    EmptyData,
+    UnsupportedEncodingVersion(u8),
+    InvalidLength { expected: usize, found: usize },
+}
+
+impl std::fmt::Display for HeaderError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            HeaderError::EmptyData => write!(f, "Data is empty!"),
+            HeaderError::UnsupportedEncodingVersion(version) => write!(f, "Unsupported header encoding version: {}", version),
+            HeaderError::InvalidLength { expected, found } =>
+                write!(f, "Invalid header encoding length: expected {}, found {}", expected, found),
+        }
+    }
+}
+
+impl std::error::Error for HeaderError {}
 
 // An Block header
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BlockHeader
 {  
     // Hash of the block
@@ -52,19 +95,39 @@ pub struct BlockHeader
     // Block height
     block_height: BlockHeight,
     // Difficulty level
+    #[serde(with = "core_utils::u256_bytes")]
     difficulty: U256,
     // Block timestamp
     timestamp: Timestamp,
     // Nonce of the block
+    #[serde(with = "core_utils::u256_bytes")]
     nonce: U256,
     // Total block difficulty
+    #[serde(with = "core_utils::u256_bytes")]
     total_difficulty: U256,
+    // Base fee per gas burned by every transaction of the block
+    base_fee: Gas,
+    // The most gas the transactions of the block can use together
+    gas_limit: Gas,
+    // The gas used by the transactions of the block
+    gas_used: Gas,
+    // The address receiving the tips and the block reward
+    coinbase: Address,
+    // Root of the account state after the block is applied
+    state_root: HashDigest,
     // Transaction root
     transaction_root: HashDigest,
+    // Receipts root
+    receipts_root: HashDigest,
+    // Bloom filter of the logs emitted by the block
+    logs_bloom: Bloom,
 }
 
 impl BlockHeader
 {
+    // Length of the canonical encoding in bytes
+    const ENCODED_LENGTH: usize = 1 + 4 + HashDigest::LENGTH + 8 + 8 + 32 + 8 + 32 + 3 * 8 + Address::LENGTH + 3 * HashDigest::LENGTH + Bloom::LENGTH + 32;
+
     // The 'hash' function returns the hash value of the block
     pub fn hash(&self) -> &HashDigest { &self.hash }
     
@@ -92,8 +155,149 @@ impl BlockHeader
     // The `total_difficulty` function returns the total difficulty of the block
     pub fn total_difficulty(&self) -> &U256 { &self.total_difficulty }
 
+    // The `base_fee` function returns the base fee per gas of the block
+    pub fn base_fee(&self) -> &Gas { &self.base_fee }
+
+    // The `gas_limit` function returns the most gas the transactions of the block can use together
+    pub fn gas_limit(&self) -> &Gas { &self.gas_limit }
+
+    // The `gas_used` function returns the gas used by the transactions of the block
+    pub fn gas_used(&self) -> &Gas { &self.gas_used }
+
+    // The `coinbase` function returns the address receiving the tips and the block reward
+    pub fn coinbase(&self) -> &Address { &self.coinbase }
+
+    // The `state_root` function returns the root of the account state after the block is applied
+    pub fn state_root(&self) -> &HashDigest { &self.state_root }
+
     // The `transaction_root` function returns the transaction root of the block
     pub fn transaction_root(&self) -> &HashDigest { &self.transaction_root }
+
+    // The `receipts_root` function returns the receipts root of the block
+    pub fn receipts_root(&self) -> &HashDigest { &self.receipts_root }
+
+    // The `logs_bloom` function returns the bloom filter of the logs emitted by the block
+    pub fn logs_bloom(&self) -> &Bloom { &self.logs_bloom }
+
+    // The `encode_without_nonce` function returns the canonical encoding of every field but the hash and nonce
+    pub fn encode_without_nonce(&self) -> Vec<u8>
+    {
+        let mut bytes = Vec::with_capacity(Self::ENCODED_LENGTH);
+        bytes.push(HEADER_ENCODING_VERSION);
+        bytes.extend_from_slice(&self.protocol_version.to_le_bytes());
+        bytes.extend_from_slice(self.parent_hash.as_ref());
+        bytes.extend_from_slice(&self.block_number.to_le_bytes());
+        bytes.extend_from_slice(&self.block_height.to_le_bytes());
+        bytes.extend_from_slice(&u256_to_bytes(&self.difficulty));
+        bytes.extend_from_slice(&self.timestamp.as_millis().to_le_bytes());
+        bytes.extend_from_slice(&u256_to_bytes(&self.total_difficulty));
+        bytes.extend_from_slice(&self.base_fee.amount().to_le_bytes());
+        bytes.extend_from_slice(&self.gas_limit.amount().to_le_bytes());
+        bytes.extend_from_slice(&self.gas_used.amount().to_le_bytes());
+        bytes.extend_from_slice(self.coinbase.as_ref());
+        bytes.extend_from_slice(self.state_root.as_ref());
+        bytes.extend_from_slice(self.transaction_root.as_ref());
+        bytes.extend_from_slice(self.receipts_root.as_ref());
+        bytes.extend_from_slice(self.logs_bloom.as_ref());
+        bytes
+    }
+
+    // The `canonical_encoding` function returns the encoding the header hash is computed from
+    pub fn canonical_encoding(&self) -> Vec<u8>
+    {
+        let mut bytes = self.encode_without_nonce();
+        bytes.extend_from_slice(&u256_to_bytes(&self.nonce));
+        bytes
+    }
+
+    // The `decode_canonical` function decodes a header from its canonical encoding and recomputes its hash
+    pub fn decode_canonical(bytes: &[u8]) -> Result<BlockHeader, HeaderError>
+    {
+        let (version, mut rest) = bytes.split_first().ok_or(HeaderError::EmptyData)?;
+        if *version != HEADER_ENCODING_VERSION
+        {
+            return Err(HeaderError::UnsupportedEncodingVersion(*version));
+        }
+        if bytes.len() != Self::ENCODED_LENGTH
+        {
+            return Err(HeaderError::InvalidLength { expected: Self::ENCODED_LENGTH, found: bytes.len() });
+        }
+
+        let mut take = |length: usize| {
+            let (field, tail) = rest.split_at(length);
+            rest = tail;
+            field
+        };
+        let protocol_version = u32::from_le_bytes(take(4).try_into().unwrap());
+        let parent_hash = HashDigest::from(take(HashDigest::LENGTH).to_vec());
+        let block_number = u64::from_le_bytes(take(8).try_into().unwrap());
+        let block_height = u64::from_le_bytes(take(8).try_into().unwrap());
+        let difficulty = U256::from_big_endian(take(32));
+        let timestamp = Timestamp::from_millis(u64::from_le_bytes(take(8).try_into().unwrap()));
+        let total_difficulty = U256::from_big_endian(take(32));
+        let base_fee = Gas::new(u64::from_le_bytes(take(8).try_into().unwrap()));
+        let gas_limit = Gas::new(u64::from_le_bytes(take(8).try_into().unwrap()));
+        let gas_used = Gas::new(u64::from_le_bytes(take(8).try_into().unwrap()));
+        let coinbase = Address::from(<[u8; Address::LENGTH]>::try_from(take(Address::LENGTH)).unwrap());
+        let state_root = HashDigest::from(take(HashDigest::LENGTH).to_vec());
+        let transaction_root = HashDigest::from(take(HashDigest::LENGTH).to_vec());
+        let receipts_root = HashDigest::from(take(HashDigest::LENGTH).to_vec());
+        let logs_bloom = Bloom::from(<[u8; Bloom::LENGTH]>::try_from(take(Bloom::LENGTH)).unwrap());
+        let nonce = U256::from_big_endian(take(32));
+
+        let mut header = BlockHeader {
+            hash: HashDigest::default(),
+            protocol_version,
+            parent_hash,
+            block_number,
+            block_height,
+            difficulty,
+            timestamp,
+            nonce,
+            total_difficulty,
+            base_fee,
+            gas_limit,
+            gas_used,
+            coinbase,
+            state_root,
+            transaction_root,
+            receipts_root,
+            logs_bloom,
+        };
+        header.hash = header.compute_hash();
+        Ok(header)
+    }
+
+    // The `compute_hash` function computes the hash of the header from its contents
+    pub fn compute_hash(&self) -> HashDigest
+    {
+        let mut hasher = Hasher::new(Algorithm::SHA256);
+        hasher.update(&self.canonical_encoding());
+        hasher.finalize()
+    }
+
+    // The `verify_hash` function checks that the stored hash commits to the header contents
+    pub fn verify_hash(&self) -> bool
+    {
+        self.hash == self.compute_hash()
+    }
+
+    // The `with_nonce` function returns a copy of the header with the given nonce, sealed with its new hash
+    pub fn with_nonce(&self, nonce: U256) -> BlockHeader
+    {
+        let mut header = self.clone();
+        header.nonce = nonce;
+        header.hash = header.compute_hash();
+        header
+    }
+}
+
+// The 'u256_to_bytes' function converts a `U256` to its 32 byte big-endian form
+fn u256_to_bytes(value: &U256) -> [u8; 32]
+{
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    bytes
 }
 
 
@@ -109,7 +313,14 @@ pub struct BlockHeaderBuilder
     timestamp: Timestamp,
     nonce: U256,
     total_difficulty: U256,
+    base_fee: Gas,
+    gas_limit: Gas,
+    gas_used: Gas,
+    coinbase: Address,
+    state_root: HashDigest,
     transaction_root: HashDigest,
+    receipts_root: HashDigest,
+    logs_bloom: Bloom,
 }
 
 impl Default for BlockHeader
@@ -122,11 +333,18 @@ impl Default for BlockHeader
             parent_hash: Default::default(),
             block_number: 0,
             block_height: 0,
-            difficulty: Default::default(),// The `build` function constructs a `BlockHeader` using the provided builder parameters.
+            difficulty: Default::default(),
             timestamp: Default::default(),
             nonce: Default::default(),
             total_difficulty: Default::default(),
+            base_fee: Default::default(),
+            gas_limit: Default::default(),
+            gas_used: Default::default(),
+            coinbase: Default::default(),
+            state_root: Default::default(),
             transaction_root: Default::default(),
+            receipts_root: Default::default(),
+            logs_bloom: Default::default(),
         }
     }
 }
@@ -146,15 +364,23 @@ impl BlockHeaderBuilder
             timestamp: Default::default(),
             nonce: Default::default(),
             total_difficulty: Default::default(),
+            base_fee: Default::default(),
+            gas_limit: Default::default(),
+            gas_used: Default::default(),
+            coinbase: Default::default(),
+            state_root: Default::default(),
             transaction_root: Default::default(),
+            receipts_root: Default::default(),
+            logs_bloom: Default::default(),
         }  
     }
 
-    // The 'set_hash' function sets the hash value of the block
+    // The 'set_hash' function sets the hash value of the block.
+    // Use `seal` to compute the hash from the header contents instead.
     pub fn set_hash(&mut self, hash: HashDigest) -> &mut Self
     {
         self.hash = hash;
-        self// The `build` function constructs a `BlockHeader` using the provided builder parameters.
+        self
     }
     
     // The `set_protocol_version` function sets the protocol version of the block
@@ -213,12 +439,61 @@ impl BlockHeaderBuilder
         self
     }
 
+    // The `set_base_fee` function sets the base fee per gas of the block
+    pub fn set_base_fee(&mut self, base_fee: Gas) -> &mut Self
+    {
+        self.base_fee = base_fee;
+        self
+    }
+
+    // The `set_gas_limit` function sets the most gas the transactions of the block can use together
+    pub fn set_gas_limit(&mut self, gas_limit: Gas) -> &mut Self
+    {
+        self.gas_limit = gas_limit;
+        self
+    }
+
+    // The `set_gas_used` function sets the gas used by the transactions of the block
+    pub fn set_gas_used(&mut self, gas_used: Gas) -> &mut Self
+    {
+        self.gas_used = gas_used;
+        self
+    }
+
+    // The `set_coinbase` function sets the address receiving the tips and the block reward
+    pub fn set_coinbase(&mut self, coinbase: Address) -> &mut Self
+    {
+        self.coinbase = coinbase;
+        self
+    }
+
+    // The `set_state_root` function sets the root of the account state after the block is applied
+    pub fn set_state_root(&mut self, state_root: HashDigest) -> &mut Self
+    {
+        self.state_root = state_root;
+        self
+    }
+
     // The `set_transaction_root` function sets the transaction root of the block
     pub fn set_transaction_root(&mut self, transaction_root: HashDigest) -> &mut Self  
     {
         self.transaction_root = transaction_root;
         self
     }
+
+    // The `set_receipts_root` function sets the receipts root of the block
+    pub fn set_receipts_root(&mut self, receipts_root: HashDigest) -> &mut Self
+    {
+        self.receipts_root = receipts_root;
+        self
+    }
+
+    // The `set_logs_bloom` function sets the bloom filter of the logs emitted by the block
+    pub fn set_logs_bloom(&mut self, logs_bloom: Bloom) -> &mut Self
+    {
+        self.logs_bloom = logs_bloom;
+        self
+    }
     
     // The `build` function constructs a `BlockHeader` using the provided builder parameters
     pub fn build(&self) -> BlockHeader
@@ -232,7 +507,14 @@ impl BlockHeaderBuilder
         let timestamp = self.timestamp;
         let nonce = self.nonce;
         let total_difficulty = self.total_difficulty;
+        let base_fee = self.base_fee;
+        let gas_limit = self.gas_limit;
+        let gas_used = self.gas_used;
+        let coinbase = self.coinbase;
+        let state_root = self.state_root;
         let transaction_root = self.transaction_root;
+        let receipts_root = self.receipts_root;
+        let logs_bloom = self.logs_bloom;
 
         
         BlockHeader {
@@ -245,9 +527,24 @@ impl BlockHeaderBuilder
             timestamp,
             nonce,
             total_difficulty,
+            base_fee,
+            gas_limit,
+            gas_used,
+            coinbase,
+            state_root,
             transaction_root,
+            receipts_root,
+            logs_bloom,
         }
     } 
+
+    // The `seal` function constructs a `BlockHeader` whose hash is computed from its contents
+    pub fn seal(&self) -> BlockHeader
+    {
+        let mut header = self.build();
+        header.hash = header.compute_hash();
+        header
+    }
 }
 
 #[cfg(test)]
@@ -302,4 +599,108 @@ mod test
         assert_eq!(header.protocol_version(), 0);
         assert_eq!(header.parent_hash(), &HashDigest::default());
     }
+
+    fn sample_builder() -> BlockHeaderBuilder
+    {
+        let mut builder = BlockHeaderBuilder::new();
+        builder
+            .set_protocol_version(1)
+            .set_parent_hash(HashDigest::from([7; HashDigest::LENGTH]))
+            .set_block_number(10)
+            .set_block_height(10)
+            .set_difficulty(U256::from(1000))
+            .set_timestamp(Timestamp::from(1_700_000_000))
+            .set_nonce(U256::from(42))
+            .set_total_difficulty(U256::from(10_000))
+            .set_base_fee(Gas::new(1_000))
+            .set_gas_limit(Gas::new(30_000_000))
+            .set_gas_used(Gas::new(21_000))
+            .set_coinbase(Address::from([4; 20]))
+            .set_state_root(HashDigest::from([6; HashDigest::LENGTH]))
+            .set_transaction_root(HashDigest::from([9; HashDigest::LENGTH]))
+            .set_receipts_root(HashDigest::from([5; HashDigest::LENGTH]))
+            .set_logs_bloom(Bloom::from([3; Bloom::LENGTH]));
+        builder
+    }
+
+    #[test]
+    fn test_seal_computes_hash()
+    {
+        let header = sample_builder().seal();
+        assert_ne!(header.hash(), &HashDigest::default());
+        assert_eq!(header.hash(), &header.compute_hash());
+        assert!(header.verify_hash());
+    }
+
+    #[test]
+    fn test_seal_is_deterministic()
+    {
+        assert_eq!(sample_builder().seal().hash(), sample_builder().seal().hash());
+    }
+
+    #[test]
+    fn test_canonical_encoding_layout()
+    {
+        let header = sample_builder().build();
+        let encoding = header.canonical_encoding();
+        let without_nonce = header.encode_without_nonce();
+
+        assert_eq!(encoding.len(), BlockHeader::ENCODED_LENGTH);
+        assert_eq!(encoding[0], HEADER_ENCODING_VERSION);
+        assert_eq!(&encoding[..without_nonce.len()], &without_nonce[..]);
+        assert_eq!(&encoding[without_nonce.len()..], &u256_to_bytes(&U256::from(42))[..]);
+    }
+
+    #[test]
+    fn test_decode_canonical()
+    {
+        let header = sample_builder().seal();
+        let mut encoding = header.canonical_encoding();
+        assert_eq!(BlockHeader::decode_canonical(&encoding), Ok(header));
+
+        assert_eq!(BlockHeader::decode_canonical(&[]), Err(HeaderError::EmptyData));
+        assert_eq!(
+            BlockHeader::decode_canonical(&encoding[..encoding.len() - 1]),
+            Err(HeaderError::InvalidLength { expected: BlockHeader::ENCODED_LENGTH, found: BlockHeader::ENCODED_LENGTH - 1 })
+        );
+        encoding[0] = HEADER_ENCODING_VERSION + 1;
+        assert_eq!(
+            BlockHeader::decode_canonical(&encoding),
+            Err(HeaderError::UnsupportedEncodingVersion(HEADER_ENCODING_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn test_with_nonce_reseals()
+    {
+        let header = sample_builder().seal().with_nonce(U256::from(7));
+        assert_eq!(header.nonce(), &U256::from(7));
+        assert!(header.verify_hash());
+        assert_eq!(header, sample_builder().set_nonce(U256::from(7)).seal());
+    }
+
+    #[test]
+    fn test_verify_hash_detects_tampering()
+    {
+        let sealed = sample_builder().seal();
+        let tampered: Vec<BlockHeader> = vec![
+            sample_builder().set_parent_hash(HashDigest::default()).set_hash(*sealed.hash()).build(),
+            sample_builder().set_block_number(11).set_hash(*sealed.hash()).build(),
+            sample_builder().set_difficulty(U256::from(1)).set_hash(*sealed.hash()).build(),
+            sample_builder().set_nonce(U256::from(43)).set_hash(*sealed.hash()).build(),
+            sample_builder().set_base_fee(Gas::new(999)).set_hash(*sealed.hash()).build(),
+            sample_builder().set_gas_limit(Gas::new(29_999_999)).set_hash(*sealed.hash()).build(),
+            sample_builder().set_gas_used(Gas::new(21_001)).set_hash(*sealed.hash()).build(),
+            sample_builder().set_coinbase(Address::default()).set_hash(*sealed.hash()).build(),
+            sample_builder().set_state_root(HashDigest::default()).set_hash(*sealed.hash()).build(),
+            sample_builder().set_transaction_root(HashDigest::default()).set_hash(*sealed.hash()).build(),
+            sample_builder().set_receipts_root(HashDigest::default()).set_hash(*sealed.hash()).build(),
+            sample_builder().set_logs_bloom(Bloom::new()).set_hash(*sealed.hash()).build(),
+        ];
+
+        for header in tampered
+        {
+            assert!(!header.verify_hash());
+        }
+    }
 }
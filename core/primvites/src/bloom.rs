@@ -0,0 +1,316 @@
+//! # Log Bloom Filter
+//!
+//! This module defines `Bloom`, a 2048 bit bloom filter summarizing the logs of a receipt or a block.
+//! The address and every topic of a log are added to the filter, and the bloom of a block is the
+//! union of the blooms of its receipts. It is stored in the block header, so indexers can skip blocks
+//! that certainly contain no matching log.
+//!
+//! An input sets three bits: each of the first three pairs of bytes of its Keccak digest selects a bit
+//! with its low 11 bits. A query can return false positives but never false negatives.
+//!
+//! ## Example
+//!
+//! ```
+//! use primvites::bloom::{Bloom, LogQuery};
+//! use primvites::receipt::Log;
+//! use primvites::Address;
+//!
+//! let log = Log::new(Address::from([1; 20]), vec![], vec![]);
+//! let bloom = Bloom::from_logs(&[log]);
+//! assert!(LogQuery::new().address(Address::from([1; 20])).matches(&bloom));
+//! ```
+
+use crypto::hash::{HashDigest, Hasher, Algorithm};
+use serde::{Serialize, Serializer, Deserialize, Deserializer, de::Error as DeError};
+use std::fmt;
+use crate::{Address, receipt::{Log, Receipt}};
+
+// Number of bits set for every input
+const BLOOM_HASHES: usize = 3;
+
+// A 2048 bit bloom filter of log addresses and topics
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bloom([u8; Bloom::LENGTH]);
+
+impl Bloom
+{
+    // The length of the Bloom in bytes
+    pub const LENGTH: usize = 256;
+
+    // The 'new' function creates an empty bloom filter
+    pub fn new() -> Self
+    {
+        Bloom([0u8; Bloom::LENGTH])
+    }
+
+    // The 'from_logs' function creates the bloom filter of a list of logs
+    pub fn from_logs(logs: &[Log]) -> Self
+    {
+        let mut bloom = Bloom::new();
+        for log in logs
+        {
+            bloom.accrue_log(log);
+        }
+        bloom
+    }
+
+    // The 'from_receipts' function creates the bloom filter of a block from its receipts
+    pub fn from_receipts(receipts: &[Receipt]) -> Self
+    {
+        let mut bloom = Bloom::new();
+        for receipt in receipts
+        {
+            bloom.accrue_bloom(&receipt.bloom());
+        }
+        bloom
+    }
+
+    // The 'positions' function returns the byte index and mask of each bit set by an input
+    fn positions(input: &[u8]) -> [(usize, u8); BLOOM_HASHES]
+    {
+        let mut hasher = Hasher::new(Algorithm::Keccak);
+        hasher.update(input);
+        let digest = hasher.finalize();
+        let digest = digest.as_ref();
+
+        let mut positions = [(0, 0); BLOOM_HASHES];
+        for (i, position) in positions.iter_mut().enumerate()
+        {
+            let bit = (((digest[2 * i] as usize) << 8) | digest[2 * i + 1] as usize) % (Bloom::LENGTH * 8);
+            *position = (Bloom::LENGTH - 1 - bit / 8, 1u8 << (bit % 8));
+        }
+        positions
+    }
+
+    // The 'accrue' function adds an input to the bloom filter
+    pub fn accrue(&mut self, input: &[u8])
+    {
+        for (index, mask) in Self::positions(input)
+        {
+            self.0[index] |= mask;
+        }
+    }
+
+    // The 'accrue_log' function adds the address and topics of a log to the bloom filter
+    pub fn accrue_log(&mut self, log: &Log)
+    {
+        self.accrue(log.address().as_ref());
+        for topic in log.topics()
+        {
+            self.accrue(topic.as_ref());
+        }
+    }
+
+    // The 'accrue_bloom' function adds every input of another bloom filter to this one
+    pub fn accrue_bloom(&mut self, other: &Bloom)
+    {
+        for (byte, other_byte) in self.0.iter_mut().zip(other.0.iter())
+        {
+            *byte |= other_byte;
+        }
+    }
+
+    // The 'contains_input' function checks if an input might have been added to the bloom filter
+    pub fn contains_input(&self, input: &[u8]) -> bool
+    {
+        Self::positions(input)
+            .iter()
+            .all(|(index, mask)| self.0[*index] & mask == *mask)
+    }
+
+    // The 'contains_bloom' function checks if every bit of another bloom filter is set in this one
+    pub fn contains_bloom(&self, other: &Bloom) -> bool
+    {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .all(|(byte, other_byte)| byte & other_byte == *other_byte)
+    }
+
+    // The 'is_empty' function checks if no input was added to the bloom filter
+    pub fn is_empty(&self) -> bool
+    {
+        self.0.iter().all(|byte| *byte == 0)
+    }
+
+    // The 'to_bytes' function converts the bloom filter to a byte array
+    pub fn to_bytes(&self) -> [u8; Bloom::LENGTH]
+    {
+        self.0
+    }
+}
+
+impl Default for Bloom
+{
+    fn default() -> Self
+    {
+        Bloom::new()
+    }
+}
+
+impl From<[u8; Bloom::LENGTH]> for Bloom
+{
+    fn from(bytes: [u8; Bloom::LENGTH]) -> Self
+    {
+        Bloom(bytes)
+    }
+}
+
+impl AsRef<[u8]> for Bloom
+{
+    fn as_ref(&self) -> &[u8]
+    {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Bloom
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "Bloom({})", hex::encode(self.0))
+    }
+}
+
+impl Serialize for Bloom
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bloom
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>
+    {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        let bytes: [u8; Bloom::LENGTH] = bytes
+            .try_into()
+            .map_err(|_| D::Error::custom("Invalid bloom length"))?;
+        Ok(Bloom(bytes))
+    }
+}
+
+// A query for logs emitted by any of a set of addresses and carrying all of a set of topics
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LogQuery
+{
+    addresses: Vec<Address>,
+    topics: Vec<HashDigest>,
+}
+
+impl LogQuery
+{
+    // The 'new' function creates a query matching every log
+    pub fn new() -> Self
+    {
+        LogQuery::default()
+    }
+
+    // The 'address' function adds an address the log may be emitted by
+    pub fn address(mut self, address: Address) -> Self
+    {
+        self.addresses.push(address);
+        self
+    }
+
+    // The 'topic' function adds a topic the log must carry
+    pub fn topic(mut self, topic: HashDigest) -> Self
+    {
+        self.topics.push(topic);
+        self
+    }
+
+    // The 'matches' function checks if a block with the given bloom might contain matching logs
+    pub fn matches(&self, bloom: &Bloom) -> bool
+    {
+        let address_matches = self.addresses.is_empty()
+            || self.addresses.iter().any(|address| bloom.contains_input(address.as_ref()));
+        address_matches && self.topics.iter().all(|topic| bloom.contains_input(topic.as_ref()))
+    }
+
+    // The 'matches_log' function checks if a log matches the query exactly
+    pub fn matches_log(&self, log: &Log) -> bool
+    {
+        let address_matches = self.addresses.is_empty() || self.addresses.contains(log.address());
+        address_matches && self.topics.iter().all(|topic| log.topics().contains(topic))
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::transaction::ExecutionStatus;
+    use core_utils::gas::Gas;
+
+    fn log(address: u8, topic: u8) -> Log
+    {
+        Log::new(Address::from([address; 20]), vec![HashDigest::from([topic; 32])], vec![])
+    }
+
+    #[test]
+    fn test_empty_bloom()
+    {
+        let bloom = Bloom::new();
+        assert!(bloom.is_empty());
+        assert!(!bloom.contains_input(b"FRENYUM"));
+        assert!(LogQuery::new().matches(&bloom));
+    }
+
+    #[test]
+    fn test_accrue_sets_bits()
+    {
+        let mut bloom = Bloom::new();
+        bloom.accrue(b"FRENYUM");
+
+        assert!(bloom.contains_input(b"FRENYUM"));
+        let set_bits: u32 = bloom.to_bytes().iter().map(|byte| byte.count_ones()).sum();
+        assert!(set_bits >= 1 && set_bits <= BLOOM_HASHES as u32);
+    }
+
+    #[test]
+    fn test_bloom_from_receipts_aggregates_logs()
+    {
+        let receipts = vec![
+            Receipt::new(HashDigest::default(), ExecutionStatus::Success, Gas::new(1), Gas::new(1), vec![log(1, 10)]),
+            Receipt::new(HashDigest::default(), ExecutionStatus::Success, Gas::new(1), Gas::new(2), vec![log(2, 20)]),
+        ];
+        let bloom = Bloom::from_receipts(&receipts);
+
+        assert!(bloom.contains_bloom(&receipts[0].bloom()));
+        assert!(bloom.contains_bloom(&receipts[1].bloom()));
+        assert_eq!(bloom, Bloom::from_logs(&[log(1, 10), log(2, 20)]));
+    }
+
+    #[test]
+    fn test_log_query()
+    {
+        let bloom = Bloom::from_logs(&[log(1, 10)]);
+
+        assert!(LogQuery::new().address(Address::from([1; 20])).matches(&bloom));
+        assert!(LogQuery::new().topic(HashDigest::from([10; 32])).matches(&bloom));
+        assert!(LogQuery::new()
+            .address(Address::from([3; 20]))
+            .address(Address::from([1; 20]))
+            .topic(HashDigest::from([10; 32]))
+            .matches(&bloom));
+        assert!(!LogQuery::new().address(Address::from([3; 20])).matches(&bloom));
+        assert!(!LogQuery::new()
+            .address(Address::from([1; 20]))
+            .topic(HashDigest::from([11; 32]))
+            .matches(&bloom));
+
+        assert!(LogQuery::new().address(Address::from([1; 20])).matches_log(&log(1, 10)));
+        assert!(!LogQuery::new().topic(HashDigest::from([11; 32])).matches_log(&log(1, 10)));
+    }
+
+    #[test]
+    fn test_bloom_serde_round_trip()
+    {
+        let bloom = Bloom::from_logs(&[log(1, 10)]);
+        let bytes = bincode::serialize(&bloom).unwrap();
+        assert_eq!(bincode::deserialize::<Bloom>(&bytes).unwrap(), bloom);
+    }
+}
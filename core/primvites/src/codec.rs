@@ -0,0 +1,338 @@
+//! # Codec
+//!
+//! This module defines the canonical binary encoding of every primitive type, used to persist
+//! blocks and transactions in storage and to exchange them over the network.
+//!
+//! An encoded value is a single version byte followed by the bincode encoding of the value, using
+//! fixed-width little-endian integers. 256 bit integers are written as 32 big-endian bytes rather
+//! than as hexadecimal strings, which would accept leading zeros. Decoding rejects unknown versions
+//! and trailing bytes, so every value has exactly one valid encoding. Fields that are not signed nor
+//! committed to, such as the time a transaction was received, are not encoded.
+//!
+//! ## Example
+//!
+//! ```
+//! use primvites::codec::Codec;
+//! use primvites::block_header::{BlockHeader, BlockHeaderBuilder};
+//!
+//! let header = BlockHeaderBuilder::new().set_block_number(7).seal();
+//! let bytes = header.encode().unwrap();
+//! assert_eq!(BlockHeader::decode(&bytes).unwrap(), header);
+//! ```
+
+use bincode::Options;
+use serde::{Serialize, de::DeserializeOwned};
+use crate::{
+    account::Account,
+    block::{Block, BlockBody},
+    block_header::BlockHeader,
+    bloom::Bloom,
+    receipt::{Receipt, Log},
+    transaction::{RawTransaction, SignedTransaction, Action, TransferAction, TransactionFee, ExecutionOutcome},
+};
+
+// The version of the binary encoding, bumped whenever the layout or the meaning of an encoded field
+// changes, such as timestamps counting milliseconds instead of seconds
+pub const CODEC_VERSION: u8 = 5;
+
+// The maximum size of an encoded value in bytes
+pub const MAX_ENCODED_SIZE: u64 = 32 * 1024 * 1024;
+
+// Represents errors that can occur while encoding or decoding a value.
+#[derive(Debug, PartialEq)]
+pub enum CodecError
+{
+    EmptyData,
+    UnsupportedVersion(u8),
+    SerializationError(String),
+    DeserializationError(String),
+}
+
+impl std::fmt::Display for CodecError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            CodecError::EmptyData => write!(f, "Data is empty!"),
+            CodecError::UnsupportedVersion(version) => write!(f, "Unsupported codec version: {}", version),
+            CodecError::SerializationError(e) => write!(f, "Serialization failed: {}", e),
+            CodecError::DeserializationError(e) => write!(f, "Deserialization failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+// The 'options' function returns the bincode configuration of the canonical encoding
+fn options() -> impl Options
+{
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_little_endian()
+        .with_limit(MAX_ENCODED_SIZE)
+        .reject_trailing_bytes()
+}
+
+// The 'encode' function encodes a value with the canonical encoding
+pub fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, CodecError>
+{
+    let mut bytes = vec![CODEC_VERSION];
+    options()
+        .serialize_into(&mut bytes, value)
+        .map_err(|e| CodecError::SerializationError(e.to_string()))?;
+    Ok(bytes)
+}
+
+// The 'decode' function decodes a value from its canonical encoding
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CodecError>
+{
+    let (version, payload) = bytes.split_first().ok_or(CodecError::EmptyData)?;
+    if *version != CODEC_VERSION
+    {
+        return Err(CodecError::UnsupportedVersion(*version));
+    }
+
+    options()
+        .deserialize(payload)
+        .map_err(|e| CodecError::DeserializationError(e.to_string()))
+}
+
+// 'Codec' defines the canonical encoding and decoding of a type.
+pub trait Codec: Serialize + DeserializeOwned
+{
+    // The 'encode' function encodes the value with the canonical encoding
+    fn encode(&self) -> Result<Vec<u8>, CodecError>
+    {
+        encode(self)
+    }
+
+    // The 'decode' function decodes a value from its canonical encoding
+    fn decode(bytes: &[u8]) -> Result<Self, CodecError>
+    {
+        decode(bytes)
+    }
+}
+
+impl Codec for RawTransaction {}
+impl Codec for Action {}
+impl Codec for TransferAction {}
+impl Codec for TransactionFee {}
+impl Codec for SignedTransaction {}
+impl Codec for BlockHeader {}
+impl Codec for BlockBody {}
+impl Codec for Block {}
+impl Codec for Account {}
+impl Codec for ExecutionOutcome {}
+impl Codec for Receipt {}
+impl Codec for Log {}
+impl Codec for Bloom {}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::{U256, Address, Bytes};
+    use crate::block_header::BlockHeaderBuilder;
+    use crate::transaction::ExecutionStatus;
+    use core_utils::{gas::Gas, timestamp::Timestamp};
+    use crypto::{ed25519::PrivateKey, hash::HashDigest};
+    use rand::{Rng, thread_rng, rngs::OsRng};
+    use std::{fmt::Debug, sync::Arc};
+
+    const ROUNDS: usize = 64;
+
+    fn random_u256<R: Rng>(rng: &mut R) -> U256
+    {
+        U256::from_big_endian(&rng.gen::<[u8; 32]>())
+    }
+
+    fn random_fee<R: Rng>(rng: &mut R) -> TransactionFee
+    {
+        if rng.gen()
+        {
+            TransactionFee::Legacy { gas_price: Gas::from(rng.gen::<u64>()) }
+        } else {
+            TransactionFee::Dynamic {
+                max_fee_per_gas: Gas::from(rng.gen::<u64>()),
+                max_priority_fee_per_gas: Gas::from(rng.gen::<u64>()),
+            }
+        }
+    }
+
+    fn random_raw_transaction<R: Rng>(rng: &mut R) -> RawTransaction
+    {
+        let data_len = rng.gen_range(0, 256);
+        RawTransaction::with_fee(
+            rng.gen(),
+            random_u256(rng),
+            Action::Transfer(TransferAction {
+                to: Address::from(rng.gen::<[u8; 20]>()),
+                amount: random_u256(rng),
+            }),
+            random_fee(rng),
+            Gas::from(rng.gen::<u64>()),
+            random_u256(rng),
+            (0..data_len).map(|_| rng.gen()).collect::<Bytes>(),
+        )
+    }
+
+    fn random_signed_transaction<R: Rng>(rng: &mut R) -> SignedTransaction
+    {
+        let mut csprng: OsRng = OsRng;
+        random_raw_transaction(rng).sign(&PrivateKey::generate(&mut csprng))
+    }
+
+    fn random_header<R: Rng>(rng: &mut R) -> BlockHeader
+    {
+        BlockHeaderBuilder::new()
+            .set_protocol_version(rng.gen())
+            .set_parent_hash(HashDigest::from(rng.gen::<[u8; 32]>()))
+            .set_block_number(rng.gen())
+            .set_block_height(rng.gen())
+            .set_difficulty(random_u256(rng))
+            .set_timestamp(Timestamp::from_millis(rng.gen::<u64>()))
+            .set_nonce(random_u256(rng))
+            .set_total_difficulty(random_u256(rng))
+            .set_base_fee(Gas::from(rng.gen::<u64>()))
+            .set_gas_limit(Gas::from(rng.gen::<u64>()))
+            .set_gas_used(Gas::from(rng.gen::<u64>()))
+            .set_coinbase(Address::from(rng.gen::<[u8; Address::LENGTH]>()))
+            .set_state_root(HashDigest::from(rng.gen::<[u8; 32]>()))
+            .set_transaction_root(HashDigest::from(rng.gen::<[u8; 32]>()))
+            .set_receipts_root(HashDigest::from(rng.gen::<[u8; 32]>()))
+            .set_logs_bloom(Bloom::from([rng.gen::<u8>(); Bloom::LENGTH]))
+            .seal()
+    }
+
+    fn random_block<R: Rng>(rng: &mut R) -> Block
+    {
+        let count = rng.gen_range(0, 4);
+        let transactions = (0..count)
+            .map(|_| Arc::new(random_signed_transaction(rng)))
+            .collect();
+        let body = BlockBody::new(transactions);
+        Block::new(random_header(rng), body)
+    }
+
+    fn assert_round_trip<T: Codec + PartialEq + Debug>(value: T)
+    {
+        let bytes = value.encode().unwrap();
+        assert_eq!(bytes[0], CODEC_VERSION);
+        assert_eq!(T::decode(&bytes).unwrap(), value);
+        // Encoding is deterministic
+        assert_eq!(T::decode(&bytes).unwrap().encode().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_raw_transaction_round_trip()
+    {
+        let mut rng = thread_rng();
+        for _ in 0..ROUNDS
+        {
+            assert_round_trip(random_raw_transaction(&mut rng));
+        }
+    }
+
+    #[test]
+    fn test_signed_transaction_round_trip()
+    {
+        let mut rng = thread_rng();
+        for _ in 0..ROUNDS
+        {
+            assert_round_trip(random_signed_transaction(&mut rng));
+        }
+    }
+
+    #[test]
+    fn test_block_header_round_trip()
+    {
+        let mut rng = thread_rng();
+        for _ in 0..ROUNDS
+        {
+            let header = random_header(&mut rng);
+            let decoded = BlockHeader::decode(&header.encode().unwrap()).unwrap();
+            assert!(decoded.verify_hash());
+            assert_round_trip(header);
+        }
+    }
+
+    #[test]
+    fn test_block_round_trip()
+    {
+        let mut rng = thread_rng();
+        for _ in 0..ROUNDS / 4
+        {
+            let block = random_block(&mut rng);
+            assert_round_trip(block.body().clone());
+            assert_round_trip(block);
+        }
+    }
+
+    #[test]
+    fn test_receipt_round_trip()
+    {
+        let mut rng = thread_rng();
+        for _ in 0..ROUNDS
+        {
+            let logs = (0..rng.gen_range(0, 4))
+                .map(|_| Log::new(
+                    Address::from(rng.gen::<[u8; 20]>()),
+                    vec![HashDigest::from(rng.gen::<[u8; 32]>())],
+                    (0..rng.gen_range(0, 64)).map(|_| rng.gen()).collect(),
+                ))
+                .collect();
+            let status = if rng.gen() { ExecutionStatus::Success } else { ExecutionStatus::Failure };
+            assert_round_trip(Receipt::new(
+                HashDigest::from(rng.gen::<[u8; 32]>()),
+                status,
+                Gas::from(rng.gen::<u64>()),
+                Gas::from(rng.gen::<u64>()),
+                logs,
+            ));
+        }
+    }
+
+    #[test]
+    fn test_u256_has_one_encoding()
+    {
+        #[derive(Serialize)]
+        struct HexAccount
+        {
+            balance: &'static str,
+            nonce: &'static str,
+            code_hash: Option<HashDigest>,
+        }
+
+        let bytes = Account::with_balance(U256::from(10)).encode().unwrap();
+        // The version byte, two 32 byte big-endian integers and the absent code hash
+        assert_eq!(bytes.len(), 1 + 2 * 32 + 1);
+        assert_eq!(bytes[32], 10);
+
+        // Hexadecimal strings, with or without leading zeros, are not an encoding of an integer
+        for balance in ["0xa", "0x000a"]
+        {
+            let hex = encode(&HexAccount { balance, nonce: "0x0", code_hash: None }).unwrap();
+            assert!(matches!(Account::decode(&hex), Err(CodecError::DeserializationError(_))));
+        }
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_input()
+    {
+        let mut rng = thread_rng();
+        let mut bytes = random_header(&mut rng).encode().unwrap();
+
+        assert_eq!(BlockHeader::decode(&[]), Err(CodecError::EmptyData));
+
+        bytes.push(0);
+        assert!(matches!(BlockHeader::decode(&bytes), Err(CodecError::DeserializationError(_))));
+        bytes.pop();
+
+        bytes.truncate(bytes.len() - 1);
+        assert!(matches!(BlockHeader::decode(&bytes), Err(CodecError::DeserializationError(_))));
+
+        bytes[0] = CODEC_VERSION + 1;
+        assert_eq!(BlockHeader::decode(&bytes), Err(CodecError::UnsupportedVersion(CODEC_VERSION + 1)));
+    }
+}
@@ -1,8 +1,13 @@
-pub use ethereum_types::{Address, U256};
+pub use ethereum_types::U256;
+pub use crypto::address::Address;
 
+pub mod account;
 pub mod block;
 pub mod transaction;
+pub mod receipt;
+pub mod bloom;
 pub mod block_header;
+pub mod codec;
 
 pub type Bytes = Vec<u8>;
 pub type BlockNumber = u64;
@@ -0,0 +1,203 @@
+//! # Receipts
+//!
+//! This module defines the `Receipt` of an executed transaction and the `Log` entries it emits.
+//! A receipt records whether the transaction succeeded, the gas it used and the gas used by the block
+//! up to and including it, so indexers don't need to re-execute blocks.
+//!
+//! The receipts of a block are committed in its header through the receipts root, the Merkle root of
+//! the receipt hashes in transaction order.
+
+use core_utils::gas::Gas;
+use crypto::{hash::{HashDigest, HashError, Algorithm, CryptoHash, serialized_digest}, merkle::merkle_root};
+use serde::{Serialize, Deserialize};
+use crate::{Address, Bytes, bloom::Bloom, transaction::{ExecutionOutcome, ExecutionStatus}};
+
+// Struct representing an event emitted during execution
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Log
+{
+    // Address of the account that emitted the log
+    address: Address,
+    // Indexed topics of the log
+    topics: Vec<HashDigest>,
+    // Additional data of the log
+    data: Bytes,
+}
+
+impl Log
+{
+    // The 'new' function creates a new log
+    pub fn new(address: Address, topics: Vec<HashDigest>, data: Bytes) -> Self
+    {
+        Log {
+            address,
+            topics,
+            data,
+        }
+    }
+
+    // The 'address' function returns the address that emitted the log
+    pub fn address(&self) -> &Address { &self.address }
+
+    // The 'topics' function returns the topics of the log
+    pub fn topics(&self) -> &[HashDigest] { &self.topics }
+
+    // The 'data' function returns the data of the log
+    pub fn data(&self) -> &Bytes { &self.data }
+}
+
+// The 'address_topic' function converts an address to a log topic by left-padding it with zeros
+pub fn address_topic(address: &Address) -> HashDigest
+{
+    let mut topic = [0u8; HashDigest::LENGTH];
+    topic[HashDigest::LENGTH - Address::LENGTH..].copy_from_slice(address.as_ref());
+    HashDigest::from(topic)
+}
+
+// Struct representing the receipt of an executed transaction
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Receipt
+{
+    // Hash of the executed transaction
+    transaction_hash: HashDigest,
+    // Status of the execution
+    status: ExecutionStatus,
+    // Gas used by the transaction
+    gas_used: Gas,
+    // Gas used by the block up to and including the transaction
+    cumulative_gas_used: Gas,
+    // Logs emitted by the transaction
+    logs: Vec<Log>,
+}
+
+impl Receipt
+{
+    // The 'new' function creates a new receipt
+    pub fn new(
+        transaction_hash: HashDigest,
+        status: ExecutionStatus,
+        gas_used: Gas,
+        cumulative_gas_used: Gas,
+        logs: Vec<Log>,
+    ) -> Self {
+        Receipt {
+            transaction_hash,
+            status,
+            gas_used,
+            cumulative_gas_used,
+            logs,
+        }
+    }
+
+    // The 'from_outcome' function creates the receipt of an execution outcome
+    pub fn from_outcome(outcome: ExecutionOutcome, cumulative_gas_used: Gas) -> Self
+    {
+        Receipt::new(
+            *outcome.transaction_hash(),
+            outcome.status(),
+            *outcome.gas_used(),
+            cumulative_gas_used,
+            outcome.logs().to_vec(),
+        )
+    }
+
+    // The 'transaction_hash' function returns the hash of the executed transaction
+    pub fn transaction_hash(&self) -> &HashDigest { &self.transaction_hash }
+
+    // The 'status' function returns the status of the execution
+    pub fn status(&self) -> ExecutionStatus { self.status }
+
+    // The 'gas_used' function returns the gas used by the transaction
+    pub fn gas_used(&self) -> &Gas { &self.gas_used }
+
+    // The 'cumulative_gas_used' function returns the gas used by the block up to this transaction
+    pub fn cumulative_gas_used(&self) -> &Gas { &self.cumulative_gas_used }
+
+    // The 'logs' function returns the logs emitted by the transaction
+    pub fn logs(&self) -> &[Log] { &self.logs }
+
+    // The 'bloom' function returns the bloom filter of the logs emitted by the transaction
+    pub fn bloom(&self) -> Bloom
+    {
+        Bloom::from_logs(&self.logs)
+    }
+
+    // The 'is_success' function checks if the transaction was applied
+    pub fn is_success(&self) -> bool
+    {
+        self.status == ExecutionStatus::Success
+    }
+}
+
+impl CryptoHash for Receipt
+{
+    fn hash(&self) -> Result<HashDigest, HashError>
+    {
+        serialized_digest(Algorithm::SHA256, self)
+    }
+}
+
+// The 'receipts_root' function computes the Merkle root of the receipts of a block
+pub fn receipts_root(receipts: &[Receipt]) -> Result<HashDigest, HashError>
+{
+    let hashes = receipts
+        .iter()
+        .map(|receipt| receipt.hash())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(merkle_root(&hashes))
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    fn receipt(index: u8, status: ExecutionStatus, cumulative: u64) -> Receipt
+    {
+        Receipt::new(
+            HashDigest::from([index; HashDigest::LENGTH]),
+            status,
+            Gas::new(21_000),
+            Gas::new(cumulative),
+            vec![Log::new(Address::from([index; 20]), vec![HashDigest::from([1; 32])], vec![index])],
+        )
+    }
+
+    #[test]
+    fn test_receipt_from_outcome()
+    {
+        let log = Log::new(Address::from([1; 20]), vec![], vec![]);
+        let outcome = ExecutionOutcome::new(HashDigest::from([2; 32]), ExecutionStatus::Failure, Gas::new(10), vec![log.clone()]);
+        let receipt = Receipt::from_outcome(outcome, Gas::new(30));
+
+        assert_eq!(receipt.transaction_hash(), &HashDigest::from([2; 32]));
+        assert!(!receipt.is_success());
+        assert_eq!(receipt.gas_used(), &Gas::new(10));
+        assert_eq!(receipt.cumulative_gas_used(), &Gas::new(30));
+        assert_eq!(receipt.logs(), &[log]);
+    }
+
+    #[test]
+    fn test_address_topic()
+    {
+        let topic = address_topic(&Address::from([0xAB; 20]));
+        assert_eq!(&topic.as_ref()[..12], &[0u8; 12]);
+        assert_eq!(&topic.as_ref()[12..], &[0xAB; 20]);
+    }
+
+    #[test]
+    fn test_receipts_root_commits_to_receipts()
+    {
+        let receipts = vec![
+            receipt(1, ExecutionStatus::Success, 21_000),
+            receipt(2, ExecutionStatus::Success, 42_000),
+        ];
+        let root = receipts_root(&receipts).unwrap();
+        assert_ne!(root, HashDigest::default());
+        assert_eq!(receipts_root(&[]).unwrap(), HashDigest::default());
+
+        let mut changed = receipts.clone();
+        changed[1] = receipt(2, ExecutionStatus::Failure, 42_000);
+        assert_ne!(receipts_root(&changed).unwrap(), root);
+    }
+}
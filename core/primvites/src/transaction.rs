@@ -1,25 +1,83 @@
 use core_utils::{gas::Gas, timestamp::Timestamp};
-use crypto::{hash::HashDigest, hash::Algorithm, ed25519::Signature, ed25519::PublicKey, ed25519::PrivateKey};
-use crate::{U256, Address, Bytes};
-use serde::Serialize;
+use crypto::{hash::{HashDigest, HashError, Algorithm, Hasher}, ed25519::Signature, ed25519::PublicKey, ed25519::PrivateKey};
+use crate::{U256, Address, Bytes, receipt::Log};
+use serde::{Serialize, Deserialize};
 use anyhow::anyhow;
 use bincode;
 
+// The domain separator prefixed to the signed payload of every transaction
+pub const TRANSACTION_DOMAIN: &[u8] = b"FRENYUM_TRANSACTION";
+
+// Enum representing how a transaction pays for its gas
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum TransactionFee
+{
+    // A single price per gas, paying the base fee and tipping the rest
+    Legacy { gas_price: Gas },
+    // A cap on the price per gas, of which at most the priority fee is tipped above the base fee
+    Dynamic { max_fee_per_gas: Gas, max_priority_fee_per_gas: Gas },
+}
+
+impl TransactionFee
+{
+    // The 'max_fee_per_gas' function returns the highest price per gas the sender pays
+    pub fn max_fee_per_gas(&self) -> Gas
+    {
+        match self
+        {
+            TransactionFee::Legacy { gas_price } => *gas_price,
+            TransactionFee::Dynamic { max_fee_per_gas, .. } => *max_fee_per_gas,
+        }
+    }
+
+    // The 'max_priority_fee_per_gas' function returns the highest price per gas tipped above the base fee
+    pub fn max_priority_fee_per_gas(&self) -> Gas
+    {
+        match self
+        {
+            TransactionFee::Legacy { gas_price } => *gas_price,
+            TransactionFee::Dynamic { max_priority_fee_per_gas, .. } => *max_priority_fee_per_gas,
+        }
+    }
+
+    // The 'effective_gas_price' function returns the price per gas paid under the given base fee,
+    // or None if the fee cap is below the base fee
+    pub fn effective_gas_price(&self, base_fee: Gas) -> Option<Gas>
+    {
+        let max_fee = self.max_fee_per_gas().amount();
+        if max_fee < base_fee.amount()
+        {
+            return None;
+        }
+        let tipped = base_fee.amount().saturating_add(self.max_priority_fee_per_gas().amount());
+        Some(Gas::new(max_fee.min(tipped)))
+    }
+
+    // The 'effective_tip' function returns the price per gas paid above the given base fee,
+    // or None if the fee cap is below the base fee
+    pub fn effective_tip(&self, base_fee: Gas) -> Option<Gas>
+    {
+        self.effective_gas_price(base_fee).map(|price| Gas::new(price.amount() - base_fee.amount()))
+    }
+}
+
 // Struct representing a raw transaction
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct RawTransaction 
 {
     // Chain identifier
     chain_id: u32,
     // Nonce for transaction ordering
+    #[serde(with = "core_utils::u256_bytes")]
     nonce: U256,
     // Action to be performed in the transaction
     action: Action,
-    // Gas price in the transaction
-    gas_price: Gas,
+    // Fee paid per unit of gas
+    fee: TransactionFee,
     // Gas limit for the transaction
     gas: Gas,
     // Value (amount) to be transferred in the transaction
+    #[serde(with = "core_utils::u256_bytes")]
     value: U256,
     // Additional data payload for the transaction
     data: Bytes,
@@ -27,7 +85,7 @@ pub struct RawTransaction
 
 impl RawTransaction 
 {
-    // The 'new' function creates a new raw transaction constructor
+    // The 'new' function creates a new raw transaction constructor paying a legacy gas price
     pub fn new(
         chain_id: u32,
         nonce: U256,
@@ -36,49 +94,98 @@ impl RawTransaction
         gas: Gas,
         value: U256,
         data: Bytes,
+    ) -> Self {
+        Self::with_fee(chain_id, nonce, action, TransactionFee::Legacy { gas_price }, gas, value, data)
+    }
+
+    // The 'with_fee' function creates a new raw transaction constructor paying the given fee
+    pub fn with_fee(
+        chain_id: u32,
+        nonce: U256,
+        action: Action,
+        fee: TransactionFee,
+        gas: Gas,
+        value: U256,
+        data: Bytes,
     ) -> Self {
         RawTransaction {
             chain_id,
             nonce,
             action,
-            gas_price,
+            fee,
             gas,
             value,
             data,
         }
     }
 
-    // The 'sign' function sign the raw transaction
-    pub fn sign(
-        &self,
-        private_key: PrivateKey,
-        public_key: PublicKey,
-    ) -> SignedTransaction {
-        // Serialize raw transaction to bytes
-        let raw_tx_bytes = bincode::serialize(self)
-            .map_err(|e| anyhow::anyhow!("SerializationError: {}", e)).unwrap();
-        // Sign the serialized transaction bytes
-        let signature = private_key.sign_message(&raw_tx_bytes);
-        // Calculate hash digest of the serialized transaction bytes
-        let hash_digest = HashDigest::calculate(&raw_tx_bytes, Algorithm::SHA256).expect("Failed to hash.");
+    // The 'sign' function signs the raw transaction, sent by the owner of the private key
+    pub fn sign(&self, private_key: &PrivateKey) -> SignedTransaction
+    {
+        let public_key = private_key.to_public_key();
+        // Calculate hash digest of the serialized transaction and signer
+        let hash_digest = self.transaction_hash(&public_key).expect("Failed to hash.");
+        // Sign the hash digest of the serialized transaction
+        let signature = private_key.sign_message(hash_digest.as_ref());
         // Create a signed transaction
         SignedTransaction::new(
             self.clone(),
-            public_key.clone(),
+            public_key,
             signature,
             hash_digest,
         )
     }
 
-    // The 'to_bytes' function// The 'new' function creates a new raw transaction constructor 
+    // The 'chain_id' function returns the chain identifier
+    pub fn chain_id(&self) -> u32 { self.chain_id }
+
+    // The 'nonce' function returns the nonce of the transaction
+    pub fn nonce(&self) -> &U256 { &self.nonce }
+
+    // The 'action' function returns the action performed by the transaction
+    pub fn action(&self) -> &Action { &self.action }
+
+    // The 'fee' function returns how the transaction pays for its gas
+    pub fn fee(&self) -> &TransactionFee { &self.fee }
+
+    // The 'max_fee_per_gas' function returns the highest price per gas of the transaction
+    pub fn max_fee_per_gas(&self) -> Gas { self.fee.max_fee_per_gas() }
+
+    // The 'max_priority_fee_per_gas' function returns the highest tip per gas of the transaction
+    pub fn max_priority_fee_per_gas(&self) -> Gas { self.fee.max_priority_fee_per_gas() }
+
+    // The 'gas' function returns the gas limit of the transaction
+    pub fn gas(&self) -> &Gas { &self.gas }
+
+    // The 'value' function returns the value transferred by the transaction
+    pub fn value(&self) -> &U256 { &self.value }
+
+    // The 'data' function returns the data payload of the transaction
+    pub fn data(&self) -> &Bytes { &self.data }
+
+    // The 'to_bytes' function serializes the raw transaction to the bytes that are signed
     pub fn to_bytes(&self) -> Result<Vec<u8>, bincode::Error>
     {
         bincode::serialize(self)
     }
+
+    // The 'transaction_hash' function returns the hash identifying the transaction once signed: the
+    // SHA-256 digest of the serialized raw transaction and the public key of its signer, so the same
+    // raw transaction signed by two keys gives two transactions. The payload is prefixed with the
+    // transaction domain and the chain identifier, so a signature is only valid on its own chain
+    pub fn transaction_hash(&self, public_key: &PublicKey) -> Result<HashDigest, HashError>
+    {
+        let mut hasher = Hasher::new(Algorithm::SHA256);
+        hasher.update(TRANSACTION_DOMAIN);
+        hasher.update(&self.chain_id.to_le_bytes());
+        bincode::serialize_into(&mut hasher, &(self, public_key))
+            .map_err(|_| HashError::SerializationError)?;
+        Ok(hasher.finalize())
+    }
 }
 
 // Enum representing different types of actions in a transaction
-#[derive(Serialize, Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub enum Action
 {
     Transfer(TransferAction),
@@ -86,27 +193,29 @@ pub enum Action
 }
 
 // Struct representing a transfer action
-#[derive(Serialize, Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct TransferAction
 {
    pub to: Address,
+   #[serde(with = "core_utils::u256_bytes")]
    pub amount: U256,
 }
 
 // Struct representing a signed transaction
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SignedTransaction
 {
-    // Timestamp of the transaction
+    // Time the transaction was signed or received, which is not signed, hence not encoded
+    #[serde(skip, default = "Timestamp::now")]
     timestamp: Timestamp,
     // Raw transaction being signed
     raw_transaction: RawTransaction,
+    // Public key of the signer
+    public_key: PublicKey,
     // Signature of the transaction
     signature: Signature,
     // Hash digest of the transaction
     hash: HashDigest,
-    // Size of the transaction
-    size: u32,
 }
 
 impl SignedTransaction 
@@ -118,20 +227,20 @@ impl SignedTransaction
         signature: Signature,
         hash: HashDigest,
     ) -> Self {
-        let size = std::mem::size_of::<SignedTransaction>() as u32;
         SignedTransaction {
             timestamp: Timestamp::now(),
             raw_transaction,
+            public_key,
             signature,
             hash,
-            size,
         }
     }
 
-    // The 'get_size' function get the size of the transaction
+    // The 'get_size' function computes the size of the transaction from its contents
     pub fn get_size(&self) -> u32
     {
-        self.size
+        // The data payload is held on the heap, outside of the structure
+        (std::mem::size_of::<SignedTransaction>() + self.raw_transaction.data().len()) as u32
     }
     
     // The 'get_hash' function get the hash digest of the transaction
@@ -139,27 +248,165 @@ impl SignedTransaction
     {
         &self.hash
     }
+
+    // The 'raw_transaction' function returns the raw transaction that was signed
+    pub fn raw_transaction(&self) -> &RawTransaction
+    {
+        &self.raw_transaction
+    }
+
+    // The 'public_key' function returns the public key of the signer
+    pub fn public_key(&self) -> &PublicKey
+    {
+        &self.public_key
+    }
+
+    // The 'sender' function returns the address of the signer
+    pub fn sender(&self) -> Address
+    {
+        self.public_key.to_address()
+    }
+
+    // The 'signature' function returns the signature of the transaction
+    pub fn signature(&self) -> &Signature
+    {
+        &self.signature
+    }
+
+    // The 'timestamp' function returns the time the transaction was signed, or decoded when received
+    pub fn timestamp(&self) -> &Timestamp
+    {
+        &self.timestamp
+    }
+
+    // The 'verify' function recomputes the transaction hash and checks the signature against it
+    pub fn verify(&self) -> Result<(), TransactionError>
+    {
+        let hash = self.raw_transaction
+            .transaction_hash(&self.public_key)
+            .map_err(|e| TransactionError::MalformedPayload(e.to_string()))?;
+        if hash != self.hash
+        {
+            return Err(TransactionError::HashMismatch);
+        }
+
+        self.signature
+            .verify_message(hash.as_ref(), &self.public_key)
+            .map_err(|_| TransactionError::InvalidSignature)
+    }
+
+    // The 'verify_for_chain' function checks that the transaction was signed for the given chain, then verifies it
+    pub fn verify_for_chain(&self, chain_id: u32) -> Result<(), TransactionError>
+    {
+        let found = self.raw_transaction.chain_id;
+        if found != chain_id
+        {
+            return Err(TransactionError::WrongChainId { expected: chain_id, found });
+        }
+        self.verify()
+    }
+}
+
+// Transactions are equal when their signed contents are, whenever they were received
+impl PartialEq for SignedTransaction
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        self.raw_transaction == other.raw_transaction
+            && self.public_key == other.public_key
+            && self.signature == other.signature
+            && self.hash == other.hash
+    }
+}
+
+// Represents the reasons a transaction can be rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionError
+{
+    InvalidSignature,
+    HashMismatch,
+    MalformedPayload(String),
+    WrongChainId { expected: u32, found: u32 },
+}
+
+impl std::fmt::Display for TransactionError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            TransactionError::InvalidSignature => write!(f, "Invalid transaction signature!"),
+            TransactionError::HashMismatch => write!(f, "Transaction hash does not match its contents!"),
+            TransactionError::MalformedPayload(e) => write!(f, "Malformed transaction payload: {}", e),
+            TransactionError::WrongChainId { expected, found } =>
+                write!(f, "Transaction signed for chain {}, expected chain {}", found, expected),
+        }
+    }
+}
+
+impl std::error::Error for TransactionError {}
+
+// Enum representing the result of executing a transaction
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum ExecutionStatus
+{   
+    // The transaction was applied
+    Success,
+    // The transaction was included and charged, but its action was reverted
+    Failure,
+}
+
+// Struct representing the outcome of executing a transaction
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ExecutionOutcome
+{
+    // Hash of the executed transaction
+    transaction_hash: HashDigest,
+    // Status of the execution
+    status: ExecutionStatus,
+    // Gas consumed by the execution
+    gas_used: Gas,
+    // Logs emitted by the execution
+    logs: Vec<Log>,
 }
 
-/// pub enum ExecutionStatus
-/// {   
-///     Failure,
-///     Succes,
-///     ...
-/// }
-///
-/// pub struct ExecutionOutcome
-/// {
-///     Status
-///     Gas
-///     ...
-/// }
-///
+impl ExecutionOutcome
+{
+    // The 'new' function creates a new execution outcome
+    pub fn new(transaction_hash: HashDigest, status: ExecutionStatus, gas_used: Gas, logs: Vec<Log>) -> Self
+    {
+        ExecutionOutcome {
+            transaction_hash,
+            status,
+            gas_used,
+            logs,
+        }
+    }
+
+    // The 'transaction_hash' function returns the hash of the executed transaction
+    pub fn transaction_hash(&self) -> &HashDigest { &self.transaction_hash }
+
+    // The 'status' function returns the status of the execution
+    pub fn status(&self) -> ExecutionStatus { self.status }
+
+    // The 'gas_used' function returns the gas consumed by the execution
+    pub fn gas_used(&self) -> &Gas { &self.gas_used }
+
+    // The 'logs' function returns the logs emitted by the execution
+    pub fn logs(&self) -> &[Log] { &self.logs }
+
+    // The 'is_success' function checks if the transaction was applied
+    pub fn is_success(&self) -> bool
+    {
+        self.status == ExecutionStatus::Success
+    }
+}
 
 #[cfg(test)]
 mod test
 {
     use super::*;
+    use crate::codec::Codec;
     use rand::rngs::OsRng;
     use anyhow::anyhow;
     use crypto::ed25519::{PrivateKey, PublicKey, Signature};
@@ -170,7 +417,6 @@ mod test
     { 
         let mut csprng: OsRng = OsRng;
         let private_key = PrivateKey::generate(&mut csprng);
-        let public_key = private_key.to_public_key();
 
         let raw_transaction = RawTransaction {
             chain_id: 1,
@@ -179,13 +425,13 @@ mod test
                 to: Address::from([0; 20]), 
                 amount: U256::from(100), 
             }),
-            gas_price: Gas::from(10),
+            fee: TransactionFee::Legacy { gas_price: Gas::from(10) },
             gas: Gas::from(1000),
             value: U256::from(500),
             data: Bytes::from(&[1, 2, 3, 4]),
         };
 
-        let signed_transaction = raw_transaction.sign(private_key, public_key);
+        let signed_transaction = raw_transaction.sign(&private_key);
 
         assert_eq!(signed_transaction.raw_transaction.chain_id, 1);
         assert_eq!(signed_transaction.raw_transaction.nonce, U256::from(12345));
@@ -196,9 +442,134 @@ mod test
                 amount: U256::from(100),
             })
         );
-        assert_eq!(signed_transaction.raw_transaction.gas_price, Gas::from(10));
+        assert_eq!(signed_transaction.raw_transaction.fee, TransactionFee::Legacy { gas_price: Gas::from(10) });
         assert_eq!(signed_transaction.raw_transaction.gas, Gas::from(1000));
         assert_eq!(signed_transaction.raw_transaction.value, U256::from(500));
         assert_eq!(signed_transaction.raw_transaction.data, Bytes::from(&[1, 2, 3, 4]));
     }
+
+    fn sample_raw_transaction() -> RawTransaction
+    {
+        RawTransaction::new(
+            1,
+            U256::from(7),
+            Action::Transfer(TransferAction { to: Address::from([2; 20]), amount: U256::from(100) }),
+            Gas::from(10),
+            Gas::from(21000),
+            U256::from(100),
+            Bytes::from(&[1, 2, 3]),
+        )
+    }
+
+    #[test]
+    fn test_verify_signed_transaction()
+    {
+        let mut csprng: OsRng = OsRng;
+        let private_key = PrivateKey::generate(&mut csprng);
+        let public_key = private_key.to_public_key();
+
+        let signed_transaction = sample_raw_transaction().sign(&private_key);
+
+        assert_eq!(signed_transaction.public_key(), &public_key);
+        assert_eq!(signed_transaction.sender(), public_key.to_address());
+        assert_eq!(signed_transaction.get_hash(), &sample_raw_transaction().transaction_hash(&public_key).unwrap());
+        assert_eq!(signed_transaction.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_verify_rejects_tampered_payload()
+    {
+        let mut csprng: OsRng = OsRng;
+        let private_key = PrivateKey::generate(&mut csprng);
+
+        let mut signed_transaction = sample_raw_transaction().sign(&private_key);
+        signed_transaction.raw_transaction.value = U256::from(1_000_000);
+        assert_eq!(signed_transaction.verify(), Err(TransactionError::HashMismatch));
+
+        signed_transaction.hash = signed_transaction.raw_transaction.transaction_hash(&signed_transaction.public_key).unwrap();
+        assert_eq!(signed_transaction.verify(), Err(TransactionError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_verify_rejects_wrong_signer()
+    {
+        let mut csprng: OsRng = OsRng;
+        let private_key = PrivateKey::generate(&mut csprng);
+        let other_key = PrivateKey::generate(&mut csprng).to_public_key();
+
+        let mut signed_transaction = sample_raw_transaction().sign(&private_key);
+        signed_transaction.hash = signed_transaction.raw_transaction.transaction_hash(&other_key).unwrap();
+        signed_transaction.public_key = other_key;
+        assert_eq!(signed_transaction.verify(), Err(TransactionError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_local_fields_are_not_encoded()
+    {
+        let mut csprng: OsRng = OsRng;
+        let private_key = PrivateKey::generate(&mut csprng);
+        let signed_transaction = sample_raw_transaction().sign(&private_key);
+
+        // A relayer cannot rewrite the timestamp, since it is not part of the encoding.
+        let mut relayed = signed_transaction.clone();
+        relayed.timestamp = Timestamp::from(1);
+        assert_eq!(relayed.encode().unwrap(), signed_transaction.encode().unwrap());
+
+        let received = Timestamp::now();
+        let decoded = SignedTransaction::decode(&relayed.encode().unwrap()).unwrap();
+        assert_eq!(decoded, signed_transaction);
+        assert!(decoded.timestamp() >= &received);
+        assert_eq!(decoded.get_size() as usize, std::mem::size_of::<SignedTransaction>() + 3);
+    }
+
+    #[test]
+    fn test_hash_depends_on_signer()
+    {
+        let mut csprng: OsRng = OsRng;
+        let first_key = PrivateKey::generate(&mut csprng);
+        let second_key = PrivateKey::generate(&mut csprng);
+
+        let first = sample_raw_transaction().sign(&first_key);
+        let second = sample_raw_transaction().sign(&second_key);
+        assert_ne!(first.get_hash(), second.get_hash());
+        assert_eq!(first.verify(), Ok(()));
+        assert_eq!(second.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_chain_id_replay_protection()
+    {
+        let mut csprng: OsRng = OsRng;
+        let private_key = PrivateKey::generate(&mut csprng);
+        let public_key = private_key.to_public_key();
+
+        let signed_transaction = sample_raw_transaction().sign(&private_key);
+        assert_eq!(signed_transaction.verify_for_chain(1), Ok(()));
+        assert_eq!(signed_transaction.verify_for_chain(2), Err(TransactionError::WrongChainId { expected: 2, found: 1 }));
+
+        // Replaying the signature on another chain does not verify.
+        let mut replayed = signed_transaction.clone();
+        replayed.raw_transaction.chain_id = 2;
+        assert_eq!(replayed.verify_for_chain(2), Err(TransactionError::HashMismatch));
+        replayed.hash = replayed.raw_transaction.transaction_hash(&public_key).unwrap();
+        assert_ne!(replayed.get_hash(), signed_transaction.get_hash());
+        assert_eq!(replayed.verify_for_chain(2), Err(TransactionError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_effective_gas_price()
+    {
+        let legacy = TransactionFee::Legacy { gas_price: Gas::from(10) };
+        assert_eq!(legacy.effective_gas_price(Gas::from(4)), Some(Gas::from(10)));
+        assert_eq!(legacy.effective_tip(Gas::from(4)), Some(Gas::from(6)));
+        assert_eq!(legacy.effective_gas_price(Gas::from(11)), None);
+
+        let dynamic = TransactionFee::Dynamic { max_fee_per_gas: Gas::from(10), max_priority_fee_per_gas: Gas::from(2) };
+        assert_eq!(dynamic.effective_gas_price(Gas::from(4)), Some(Gas::from(6)));
+        assert_eq!(dynamic.effective_tip(Gas::from(4)), Some(Gas::from(2)));
+        // The tip is cut so that the fee cap holds.
+        assert_eq!(dynamic.effective_gas_price(Gas::from(9)), Some(Gas::from(10)));
+        assert_eq!(dynamic.effective_tip(Gas::from(9)), Some(Gas::from(1)));
+        assert_eq!(dynamic.effective_tip(Gas::from(11)), None);
+    }
 }
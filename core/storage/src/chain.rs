@@ -0,0 +1,367 @@
+//! # Chain Store
+//!
+//! This module defines `ChainStore`, which stores blocks and maintains the indexes over them: the
+//! canonical block hash at every number, the header, body and total difficulty of every block, and
+//! the block and position of every canonical transaction.
+//!
+//! Blocks are imported with their total difficulty, either as the new canonical head or on a side
+//! branch. Moving the canonical head to another block walks back to the first block already
+//! canonical, removes the number and transaction indexes of the blocks leaving the canonical chain
+//! and adds those of the blocks joining it. Every import and head change is written as a single
+//! batch, so the indexes never reflect half a reorg. Only canonical blocks are found by number or
+//! through their transactions.
+
+use crypto::hash::HashDigest;
+use primvites::{
+    BlockNumber,
+    U256,
+    block::{Block, BlockBody},
+    block_header::BlockHeader,
+    transaction::SignedTransaction,
+};
+use std::sync::Arc;
+use crate::{
+    db::key_value::KeyValueStore,
+    error::StorageError,
+    schema::{
+        BodySchema, CanonicalHashSchema, CanonicalHeadSchema, HeaderSchema,
+        TotalDifficultySchema, TransactionIndexSchema, TransactionLocation,
+    },
+    store::{SchemaBatch, SchemaStore},
+};
+
+pub struct ChainStore<'a, D: KeyValueStore>
+{
+    store: SchemaStore<'a, D>,
+}
+
+impl<'a, D: KeyValueStore> ChainStore<'a, D>
+{
+    // The 'new' function creates a chain store over the given database.
+    pub fn new(db: &'a D) -> Self
+    {
+        ChainStore { store: SchemaStore::new(db) }
+    }
+
+    // The 'canonical_head_hash' function returns the hash of the canonical head, if any block was made canonical.
+    pub fn canonical_head_hash(&self) -> Result<Option<HashDigest>, StorageError>
+    {
+        self.store.get_metadata::<CanonicalHeadSchema>()
+    }
+
+    // The 'canonical_head' function returns the header of the canonical head.
+    pub fn canonical_head(&self) -> Result<Option<BlockHeader>, StorageError>
+    {
+        match self.canonical_head_hash()?
+        {
+            Some(hash) => self.header_by_hash(&hash),
+            None => Ok(None),
+        }
+    }
+
+    // The 'canonical_hash' function returns the hash of the canonical block at a number.
+    pub fn canonical_hash(&self, number: BlockNumber) -> Result<Option<HashDigest>, StorageError>
+    {
+        self.store.get::<CanonicalHashSchema>(&number)
+    }
+
+    // The 'header_by_hash' function reads the header of a block.
+    pub fn header_by_hash(&self, hash: &HashDigest) -> Result<Option<BlockHeader>, StorageError>
+    {
+        self.store.get::<HeaderSchema>(hash)
+    }
+
+    // The 'body_by_hash' function reads the body of a block.
+    pub fn body_by_hash(&self, hash: &HashDigest) -> Result<Option<BlockBody>, StorageError>
+    {
+        self.store.get::<BodySchema>(hash)
+    }
+
+    // The 'block_by_hash' function reads a block, canonical or not.
+    pub fn block_by_hash(&self, hash: &HashDigest) -> Result<Option<Block>, StorageError>
+    {
+        let header = match self.header_by_hash(hash)?
+        {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let body = self.body_by_hash(hash)?.ok_or(StorageError::UnknownBlock(*hash))?;
+        Ok(Some(Block::new(header, body)))
+    }
+
+    // The 'block_by_number' function reads the canonical block at a number.
+    pub fn block_by_number(&self, number: BlockNumber) -> Result<Option<Block>, StorageError>
+    {
+        match self.canonical_hash(number)?
+        {
+            Some(hash) => self.block_by_hash(&hash),
+            None => Ok(None),
+        }
+    }
+
+    // The 'total_difficulty' function returns the total difficulty of the chain ending at a block.
+    pub fn total_difficulty(&self, hash: &HashDigest) -> Result<Option<U256>, StorageError>
+    {
+        self.store.get::<TotalDifficultySchema>(hash)
+    }
+
+    // The 'transaction_location' function returns the canonical block including a transaction and its position.
+    pub fn transaction_location(&self, hash: &HashDigest) -> Result<Option<TransactionLocation>, StorageError>
+    {
+        self.store.get::<TransactionIndexSchema>(hash)
+    }
+
+    // The 'transaction_by_hash' function reads a canonical transaction along with its location.
+    pub fn transaction_by_hash(
+        &self,
+        hash: &HashDigest,
+    ) -> Result<Option<(Arc<SignedTransaction>, TransactionLocation)>, StorageError> {
+        let location = match self.transaction_location(hash)?
+        {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        let body = self.body_by_hash(&location.block_hash)?.ok_or(StorageError::UnknownBlock(location.block_hash))?;
+        Ok(body.transaction().get(location.index).map(|transaction| (transaction.clone(), location)))
+    }
+
+    // The 'import_block' function stores a block with its total difficulty and, if `canonical`, makes it
+    // the canonical head, in a single batch. The parent of a canonical block must be stored.
+    pub fn import_block(&self, block: &Block, total_difficulty: U256, canonical: bool) -> Result<(), StorageError>
+    {
+        let hash = block.header().hash();
+        let mut batch = SchemaBatch::new();
+        batch.put::<HeaderSchema>(hash, block.header())?;
+        batch.put::<BodySchema>(hash, block.body())?;
+        batch.put::<TotalDifficultySchema>(hash, &total_difficulty)?;
+        if canonical
+        {
+            self.canonicalize(&mut batch, block.header().clone(), block.body().clone())?;
+        }
+        self.store.write(batch)
+    }
+
+    // The 'set_canonical_head' function makes a stored block the canonical head, reorganizing the indexes
+    // of the blocks that leave and join the canonical chain in a single batch.
+    pub fn set_canonical_head(&self, hash: &HashDigest) -> Result<(), StorageError>
+    {
+        let header = self.header_by_hash(hash)?.ok_or(StorageError::UnknownBlock(*hash))?;
+        let body = self.body_by_hash(hash)?.ok_or(StorageError::UnknownBlock(*hash))?;
+        let mut batch = SchemaBatch::new();
+        self.canonicalize(&mut batch, header, body)?;
+        self.store.write(batch)
+    }
+
+    // The 'canonicalize' function records in `batch` the index updates making `head` the canonical head
+    fn canonicalize(&self, batch: &mut SchemaBatch, head: BlockHeader, head_body: BlockBody) -> Result<(), StorageError>
+    {
+        let head_hash = *head.hash();
+
+        // Walk back from the new head to the first block already canonical.
+        let mut enacted = Vec::new();
+        let (mut header, mut body) = (head, head_body);
+        let fork_number = loop
+        {
+            let number = header.block_number();
+            if self.canonical_hash(number)?.as_ref() == Some(header.hash())
+            {
+                break Some(number);
+            }
+            let parent = *header.parent_hash();
+            enacted.push((header, body));
+            if number == 0
+            {
+                break None;
+            }
+            header = self.header_by_hash(&parent)?.ok_or(StorageError::UnknownBlock(parent))?;
+            body = self.body_by_hash(&parent)?.ok_or(StorageError::UnknownBlock(parent))?;
+        };
+
+        // Retract the canonical blocks above the fork point. Deletions come first in the batch, so that
+        // the blocks enacted below overwrite them.
+        if let Some(old_head) = self.canonical_head()?
+        {
+            let first = fork_number.map_or(0, |number| number + 1);
+            for number in first..=old_head.block_number()
+            {
+                let hash = match self.canonical_hash(number)?
+                {
+                    Some(hash) => hash,
+                    None => continue,
+                };
+                let body = self.body_by_hash(&hash)?.ok_or(StorageError::UnknownBlock(hash))?;
+                for transaction_hash in body.transaction_hashes()
+                {
+                    batch.delete::<TransactionIndexSchema>(&transaction_hash);
+                }
+                batch.delete::<CanonicalHashSchema>(&number);
+            }
+        }
+
+        // Enact the new canonical blocks, oldest first.
+        for (header, body) in enacted.iter().rev()
+        {
+            batch.put::<CanonicalHashSchema>(&header.block_number(), header.hash())?;
+            for (index, transaction_hash) in body.transaction_hashes().iter().enumerate()
+            {
+                let location = TransactionLocation { block_hash: *header.hash(), index };
+                batch.put::<TransactionIndexSchema>(transaction_hash, &location)?;
+            }
+        }
+        batch.put_metadata::<CanonicalHeadSchema>(&head_hash)
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use core_utils::gas::Gas;
+    use crypto::ed25519::PrivateKey;
+    use primvites::{
+        Address,
+        Bytes,
+        block_header::BlockHeaderBuilder,
+        transaction::{Action, RawTransaction, TransferAction},
+    };
+    use rand::rngs::OsRng;
+    use crate::db::backend_tests;
+
+    fn transfer(private_key: &PrivateKey, nonce: u64) -> Arc<SignedTransaction>
+    {
+        let raw = RawTransaction::new(
+            1,
+            U256::from(nonce),
+            Action::Transfer(TransferAction { to: Address::from([7; 20]), amount: U256::from(10) }),
+            Gas::from(1),
+            Gas::from(21_000),
+            U256::from(10),
+            Bytes::new(),
+        );
+        Arc::new(raw.sign(private_key))
+    }
+
+    // Builds a block on `parent`; `salt` tells apart siblings with the same transactions
+    fn block(parent: Option<&Block>, salt: u64, transactions: Vec<Arc<SignedTransaction>>) -> Block
+    {
+        let number = parent.map_or(0, |parent| parent.header().block_number() + 1);
+        let parent_hash = parent.map_or(HashDigest::default(), |parent| *parent.header().hash());
+        let body = BlockBody::new(transactions);
+        let header = BlockHeaderBuilder::new()
+            .set_parent_hash(parent_hash)
+            .set_block_number(number)
+            .set_block_height(number)
+            .set_nonce(U256::from(salt))
+            .set_transaction_root(body.compute_transaction_root())
+            .seal();
+        Block::new(header, body)
+    }
+
+    fn test_import_canonical_blocks<D: KeyValueStore>(db: &D)
+    {
+        let chain = ChainStore::new(db);
+        let private_key = PrivateKey::generate(&mut OsRng);
+        let transaction = transfer(&private_key, 0);
+
+        let genesis = block(None, 0, Vec::new());
+        let first = block(Some(&genesis), 0, vec![transaction.clone()]);
+        assert_eq!(chain.canonical_head().unwrap(), None);
+        chain.import_block(&genesis, U256::from(1), true).unwrap();
+        chain.import_block(&first, U256::from(2), true).unwrap();
+
+        assert_eq!(chain.canonical_head().unwrap().as_ref(), Some(first.header()));
+        assert_eq!(chain.block_by_number(0).unwrap(), Some(genesis.clone()));
+        assert_eq!(chain.block_by_number(1).unwrap(), Some(first.clone()));
+        assert_eq!(chain.block_by_number(2).unwrap(), None);
+        assert_eq!(chain.block_by_hash(first.header().hash()).unwrap(), Some(first.clone()));
+        assert_eq!(chain.total_difficulty(first.header().hash()).unwrap(), Some(U256::from(2)));
+
+        let location = TransactionLocation { block_hash: *first.header().hash(), index: 0 };
+        assert_eq!(chain.transaction_by_hash(transaction.get_hash()).unwrap(), Some((transaction, location)));
+    }
+
+    fn test_side_block_is_not_indexed<D: KeyValueStore>(db: &D)
+    {
+        let chain = ChainStore::new(db);
+        let private_key = PrivateKey::generate(&mut OsRng);
+        let transaction = transfer(&private_key, 0);
+
+        let genesis = block(None, 0, Vec::new());
+        let side = block(Some(&genesis), 1, vec![transaction.clone()]);
+        chain.import_block(&genesis, U256::from(1), true).unwrap();
+        chain.import_block(&side, U256::from(2), false).unwrap();
+
+        assert_eq!(chain.canonical_head_hash().unwrap(), Some(*genesis.header().hash()));
+        assert_eq!(chain.block_by_hash(side.header().hash()).unwrap(), Some(side));
+        assert_eq!(chain.block_by_number(1).unwrap(), None);
+        assert_eq!(chain.transaction_by_hash(transaction.get_hash()).unwrap(), None);
+    }
+
+    fn test_reorg_rewrites_indexes<D: KeyValueStore>(db: &D)
+    {
+        let chain = ChainStore::new(db);
+        let private_key = PrivateKey::generate(&mut OsRng);
+        let (shared, dropped, moved) = (transfer(&private_key, 0), transfer(&private_key, 1), transfer(&private_key, 2));
+
+        // The old branch has two blocks above genesis, the new branch three.
+        let genesis = block(None, 0, Vec::new());
+        let old_first = block(Some(&genesis), 1, vec![shared.clone()]);
+        let old_second = block(Some(&old_first), 1, vec![dropped.clone(), moved.clone()]);
+        let new_first = block(Some(&genesis), 2, vec![shared.clone()]);
+        let new_second = block(Some(&new_first), 2, Vec::new());
+        let new_third = block(Some(&new_second), 2, vec![moved.clone()]);
+
+        chain.import_block(&genesis, U256::from(1), true).unwrap();
+        chain.import_block(&old_first, U256::from(2), true).unwrap();
+        chain.import_block(&old_second, U256::from(3), true).unwrap();
+        chain.import_block(&new_first, U256::from(2), false).unwrap();
+        chain.import_block(&new_second, U256::from(3), false).unwrap();
+        chain.import_block(&new_third, U256::from(4), true).unwrap();
+
+        assert_eq!(chain.canonical_head_hash().unwrap(), Some(*new_third.header().hash()));
+        assert_eq!(chain.canonical_hash(1).unwrap(), Some(*new_first.header().hash()));
+        assert_eq!(chain.canonical_hash(2).unwrap(), Some(*new_second.header().hash()));
+        assert_eq!(chain.canonical_hash(3).unwrap(), Some(*new_third.header().hash()));
+        assert_eq!(
+            chain.transaction_location(shared.get_hash()).unwrap(),
+            Some(TransactionLocation { block_hash: *new_first.header().hash(), index: 0 })
+        );
+        assert_eq!(
+            chain.transaction_location(moved.get_hash()).unwrap(),
+            Some(TransactionLocation { block_hash: *new_third.header().hash(), index: 0 })
+        );
+        assert_eq!(chain.transaction_location(dropped.get_hash()).unwrap(), None);
+
+        // Rewinding to the old branch drops the numbers above it.
+        chain.set_canonical_head(old_first.header().hash()).unwrap();
+        assert_eq!(chain.canonical_hash(1).unwrap(), Some(*old_first.header().hash()));
+        assert_eq!(chain.canonical_hash(2).unwrap(), None);
+        assert_eq!(chain.canonical_hash(3).unwrap(), None);
+        assert_eq!(chain.transaction_location(moved.get_hash()).unwrap(), None);
+        assert_eq!(
+            chain.transaction_location(shared.get_hash()).unwrap(),
+            Some(TransactionLocation { block_hash: *old_first.header().hash(), index: 0 })
+        );
+    }
+
+    fn test_canonical_block_needs_stored_parent<D: KeyValueStore>(db: &D)
+    {
+        let chain = ChainStore::new(db);
+        let genesis = block(None, 0, Vec::new());
+        let orphan = block(Some(&genesis), 0, Vec::new());
+
+        assert!(matches!(
+            chain.import_block(&orphan, U256::from(2), true),
+            Err(StorageError::UnknownBlock(hash)) if hash == *genesis.header().hash()
+        ));
+        assert!(matches!(chain.set_canonical_head(genesis.header().hash()), Err(StorageError::UnknownBlock(_))));
+        assert_eq!(chain.canonical_head_hash().unwrap(), None);
+    }
+
+    backend_tests!(
+        test_import_canonical_blocks,
+        test_side_block_is_not_indexed,
+        test_reorg_rewrites_indexes,
+        test_canonical_block_needs_stored_parent,
+    );
+}
@@ -1,15 +1,32 @@
 use std::fmt;
 
-#[derive(Debug, Eq, Hash, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
 pub enum Column
 {
     BlockHeader,
     BlockBody,
     Transaction,
+    Receipt,
+    CanonicalHash,
+    TransactionIndex,
+    TotalDifficulty,
+    Metadata,
 }
 
 impl Column
 {
+    // Every column of the database
+    pub const ALL: [Column; 8] = [
+        Column::BlockHeader,
+        Column::BlockBody,
+        Column::Transaction,
+        Column::Receipt,
+        Column::CanonicalHash,
+        Column::TransactionIndex,
+        Column::TotalDifficulty,
+        Column::Metadata,
+    ];
+
     pub fn is_type(&self, column_type: &str) -> bool
     {
         match (self, column_type)
@@ -17,6 +34,11 @@ impl Column
             (Column::BlockHeader, "BlockHeader") => true,
             (Column::BlockBody, "BlockBody") => true,
             (Column::Transaction, "Transaction") => true,
+            (Column::Receipt, "Receipt") => true,
+            (Column::CanonicalHash, "CanonicalHash") => true,
+            (Column::TransactionIndex, "TransactionIndex") => true,
+            (Column::TotalDifficulty, "TotalDifficulty") => true,
+            (Column::Metadata, "Metadata") => true,
             _ => false,
         }
     }
@@ -28,6 +50,11 @@ impl Column
             Column::BlockHeader => "BlockHeader".to_string(),
             Column::BlockBody => "BlockBody".to_string(),
             Column::Transaction => "Transaction".to_string(),
+            Column::Receipt => "Receipt".to_string(),
+            Column::CanonicalHash => "CanonicalHash".to_string(),
+            Column::TransactionIndex => "TransactionIndex".to_string(),
+            Column::TotalDifficulty => "TotalDifficulty".to_string(),
+            Column::Metadata => "Metadata".to_string(),
         }
     }
 }
@@ -1,14 +1,54 @@
 pub mod rocksdb;
+pub mod memory;
+pub mod key_value;
 pub mod iter;
 pub mod snapshot;
-pub mod transaction;
 pub mod write_batch;
+#[cfg(test)]
+mod conformance;
+
+// Runs every given test, a function generic over the backend, against a fresh 'MemoryDB' and a fresh
+// temporary RocksDB, each with every column unless other columns follow the name of the test
+#[cfg(test)]
+macro_rules! backend_tests {
+    (@columns) => { &crate::column::Column::ALL };
+    (@columns $columns:expr) => { $columns };
+    ($($test:ident $(: $columns:expr)?),* $(,)?) => {
+        mod memory
+        {
+            $(
+                #[test]
+                fn $test()
+                {
+                    super::$test(&crate::db::memory::MemoryDB::with_columns($crate::db::backend_tests!(@columns $($columns)?)));
+                }
+            )*
+        }
+
+        mod rocksdb
+        {
+            $(
+                #[test]
+                fn $test()
+                {
+                    let columns = $crate::db::backend_tests!(@columns $($columns)?);
+                    super::$test(&*crate::db::rocksdb::TemporaryRocksDB::with_columns(stringify!($test), columns));
+                }
+            )*
+        }
+    };
+}
+
+#[cfg(test)]
+pub(crate) use backend_tests;
 
 use core_utils::configs::db::StoreConfig;
-use crate::db::rocksdb::RocksDB;
-use rocksdb::db::{DB, Options, WriteBatch, ColumnFamily, TransactionDB, Error};
+use crate::{db::rocksdb::RocksDB, error::StorageError, migration::Migrator};
 use std::path::PathBuf;
 
+// The directory of the database when the configuration does not give one
+const DEFAULT_PATH: &str = "default_db_path";
+
 pub struct DBManager
 {
     db: RocksDB,
@@ -16,12 +56,13 @@ pub struct DBManager
 
 impl DBManager 
 {
-    pub fn new(config: StoreConfig) -> Result<Self, rocksdb::error>
+    // Opens the database, refusing a schema version newer than this node supports and
+    // migrating an older one to the current version.
+    pub fn new(config: StoreConfig) -> Result<Self, StorageError>
     {
-        let options = config.to_options();
-        let path = config.path.unwrap_or_else(|| PathBuf::from("default_db_path"));
-        
-        let db = DB::open(&options, path);
+        let path = config.path.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_PATH));
+        let db = RocksDB::open(&path, &config)?;
+        Migrator::latest().run(&db)?;
         Ok(Self {
             db,
         })
@@ -32,3 +73,54 @@ impl DBManager
         &self.db
     }
 }
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::{
+        migration::SCHEMA_VERSION,
+        schema::SchemaVersionSchema,
+        store::SchemaStore,
+    };
+
+    #[test]
+    fn test_open_checks_schema_version()
+    {
+        let path = std::env::temp_dir().join(format!("frenyum-manager-{}", std::process::id()));
+        let config = StoreConfig { path: Some(path.clone()), ..StoreConfig::default() };
+        {
+            let manager = DBManager::new(config.clone()).unwrap();
+            let store = SchemaStore::new(manager.db());
+            assert_eq!(store.get_metadata::<SchemaVersionSchema>().unwrap(), Some(SCHEMA_VERSION));
+            store.put_metadata::<SchemaVersionSchema>(&(SCHEMA_VERSION + 1)).unwrap();
+        }
+        let result = DBManager::new(config);
+        let _ = ::rocksdb::DB::destroy(&::rocksdb::Options::default(), &path);
+        let _ = std::fs::remove_dir_all(&path);
+        assert!(matches!(result, Err(StorageError::IncompatibleSchemaVersion { .. })));
+    }
+
+    #[test]
+    fn test_open_with_unknown_column()
+    {
+        let path = std::env::temp_dir().join(format!("frenyum-manager-unknown-column-{}", std::process::id()));
+        let config = StoreConfig { path: Some(path.clone()), ..StoreConfig::default() };
+        {
+            let manager = DBManager::new(config.clone()).unwrap();
+            SchemaStore::new(manager.db()).put_metadata::<SchemaVersionSchema>(&(SCHEMA_VERSION + 1)).unwrap();
+        }
+        // A newer version adds a column family this version does not know.
+        {
+            let mut options = ::rocksdb::Options::default();
+            options.create_missing_column_families(true);
+            let names = ::rocksdb::DB::list_cf(&options, &path).unwrap().into_iter().chain([String::from("Unknown")]);
+            let descriptors = names.map(|name| ::rocksdb::ColumnFamilyDescriptor::new(name, ::rocksdb::Options::default()));
+            ::rocksdb::DB::open_cf_descriptors(&options, &path, descriptors).unwrap();
+        }
+        let result = DBManager::new(config);
+        let _ = ::rocksdb::DB::destroy(&::rocksdb::Options::default(), &path);
+        let _ = std::fs::remove_dir_all(&path);
+        assert!(matches!(result, Err(StorageError::IncompatibleSchemaVersion { .. })));
+    }
+}
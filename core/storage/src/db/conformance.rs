@@ -0,0 +1,210 @@
+//! # Conformance
+//!
+//! The behaviour every `KeyValueStore` must share, run against each backend by the
+//! `conformance_suite!` macro.
+
+use crate::{
+    column::Column,
+    db::{
+        key_value::{KeyValueIterator, KeyValueSnapshot, KeyValueStore},
+        memory::MemoryDB,
+        rocksdb::TemporaryRocksDB,
+        write_batch::WriteBatch,
+    },
+    error::StorageError,
+};
+
+// The 'collect' function reads every entry of an iterator as plain vectors
+fn collect(iter: KeyValueIterator<'_>) -> Vec<(Vec<u8>, Vec<u8>)>
+{
+    iter.map(|item| item.map(|(key, value)| (key.into_vec(), value.into_vec())))
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn entry(key: &[u8], value: &[u8]) -> (Vec<u8>, Vec<u8>)
+{
+    (key.to_vec(), value.to_vec())
+}
+
+fn get_put_delete<D: KeyValueStore>(db: &D)
+{
+    assert_eq!(db.get(&Column::BlockHeader, b"key").unwrap(), None);
+    db.put(&Column::BlockHeader, b"key", b"first").unwrap();
+    assert_eq!(db.get(&Column::BlockHeader, b"key").unwrap(), Some(b"first".to_vec()));
+    db.put(&Column::BlockHeader, b"key", b"second").unwrap();
+    assert_eq!(db.get(&Column::BlockHeader, b"key").unwrap(), Some(b"second".to_vec()));
+    db.delete(&Column::BlockHeader, b"key").unwrap();
+    assert_eq!(db.get(&Column::BlockHeader, b"key").unwrap(), None);
+    // Deleting a missing key is not an error.
+    db.delete(&Column::BlockHeader, b"key").unwrap();
+    db.put(&Column::BlockHeader, b"empty", b"").unwrap();
+    assert_eq!(db.get(&Column::BlockHeader, b"empty").unwrap(), Some(Vec::new()));
+}
+
+fn columns_are_independent<D: KeyValueStore>(db: &D)
+{
+    db.put(&Column::BlockHeader, b"key", b"header").unwrap();
+    db.put(&Column::Receipt, b"key", b"receipt").unwrap();
+    assert_eq!(db.get(&Column::BlockHeader, b"key").unwrap(), Some(b"header".to_vec()));
+    assert_eq!(db.get(&Column::Receipt, b"key").unwrap(), Some(b"receipt".to_vec()));
+    db.delete(&Column::Receipt, b"key").unwrap();
+    assert_eq!(db.get(&Column::BlockHeader, b"key").unwrap(), Some(b"header".to_vec()));
+    assert_eq!(collect(db.iter(&Column::Receipt).unwrap()), Vec::new());
+}
+
+// Expects a database holding the 'BlockHeader' column only
+fn unknown_column<D: KeyValueStore>(db: &D)
+{
+    assert!(matches!(db.get(&Column::Receipt, b"key"), Err(StorageError::ColumnNotFound(_))));
+    assert!(matches!(db.put(&Column::Receipt, b"key", b"value"), Err(StorageError::ColumnNotFound(_))));
+    assert!(matches!(db.iter(&Column::Receipt), Err(StorageError::ColumnNotFound(_))));
+}
+
+fn batch_applies_in_order<D: KeyValueStore>(db: &D)
+{
+    db.put(&Column::BlockHeader, b"old", b"value").unwrap();
+    let mut batch = WriteBatch::new();
+    batch.put(Column::BlockHeader, b"key", b"first");
+    batch.put(Column::BlockHeader, b"key", b"second");
+    batch.delete(Column::BlockHeader, b"old");
+    batch.put(Column::Receipt, b"gone", b"value");
+    batch.delete(Column::Receipt, b"gone");
+    db.write_batch(batch).unwrap();
+    assert_eq!(db.get(&Column::BlockHeader, b"key").unwrap(), Some(b"second".to_vec()));
+    assert_eq!(db.get(&Column::BlockHeader, b"old").unwrap(), None);
+    assert_eq!(db.get(&Column::Receipt, b"gone").unwrap(), None);
+    db.write_batch(WriteBatch::new()).unwrap();
+}
+
+// Expects a database holding the 'BlockHeader' column only
+fn batch_is_atomic<D: KeyValueStore>(db: &D)
+{
+    let mut batch = WriteBatch::new();
+    batch.put(Column::BlockHeader, b"key", b"value");
+    batch.put(Column::Receipt, b"key", b"value");
+    assert!(matches!(db.write_batch(batch), Err(StorageError::ColumnNotFound(_))));
+    assert_eq!(db.get(&Column::BlockHeader, b"key").unwrap(), None);
+}
+
+fn iteration_in_key_order<D: KeyValueStore>(db: &D)
+{
+    for key in [&[0xFF][..], &[1, 0], &[0, 5], &[1], &[2, 0xFF]]
+    {
+        db.put(&Column::Transaction, key, &[key.len() as u8]).unwrap();
+    }
+    db.put(&Column::Receipt, &[1, 1], b"other column").unwrap();
+    let all = collect(db.iter(&Column::Transaction).unwrap());
+    let keys: Vec<Vec<u8>> = all.iter().map(|(key, _)| key.clone()).collect();
+    assert_eq!(keys, vec![vec![0, 5], vec![1], vec![1, 0], vec![2, 0xFF], vec![0xFF]]);
+    assert_eq!(all[1], entry(&[1], &[1]));
+    let from: Vec<Vec<u8>> = collect(db.iter_from(&Column::Transaction, &[1, 0]).unwrap())
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    assert_eq!(from, vec![vec![1, 0], vec![2, 0xFF], vec![0xFF]]);
+    let between: Vec<Vec<u8>> = collect(db.iter_from(&Column::Transaction, &[1, 0, 0]).unwrap())
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    assert_eq!(between, vec![vec![2, 0xFF], vec![0xFF]]);
+    assert_eq!(collect(db.iter_from(&Column::Transaction, &[0xFF, 0]).unwrap()), Vec::new());
+}
+
+fn multi_get_keeps_key_order<D: KeyValueStore>(db: &D)
+{
+    db.put(&Column::Receipt, b"a", b"1").unwrap();
+    db.put(&Column::Receipt, b"c", b"3").unwrap();
+    let keys = vec![b"c".to_vec(), b"b".to_vec(), b"a".to_vec(), b"c".to_vec()];
+    assert_eq!(
+        db.multi_get(&Column::Receipt, &keys).unwrap(),
+        vec![Some(b"3".to_vec()), None, Some(b"1".to_vec()), Some(b"3".to_vec())]
+    );
+    assert_eq!(db.multi_get(&Column::Receipt, &[]).unwrap(), Vec::<Option<Vec<u8>>>::new());
+}
+
+fn snapshot_is_isolated<D: KeyValueStore>(db: &D)
+{
+    db.put(&Column::BlockBody, b"a", b"before").unwrap();
+    db.put(&Column::BlockBody, b"b", b"before").unwrap();
+    let snapshot = db.snapshot();
+    db.put(&Column::BlockBody, b"a", b"after").unwrap();
+    db.delete(&Column::BlockBody, b"b").unwrap();
+    let mut batch = WriteBatch::new();
+    batch.put(Column::BlockBody, b"c", b"after");
+    db.write_batch(batch).unwrap();
+
+    assert_eq!(snapshot.get(&Column::BlockBody, b"a").unwrap(), Some(b"before".to_vec()));
+    assert_eq!(snapshot.get(&Column::BlockBody, b"b").unwrap(), Some(b"before".to_vec()));
+    assert_eq!(snapshot.get(&Column::BlockBody, b"c").unwrap(), None);
+    assert_eq!(
+        collect(snapshot.iter_from(&Column::BlockBody, b"").unwrap()),
+        vec![entry(b"a", b"before"), entry(b"b", b"before")]
+    );
+    assert_eq!(collect(snapshot.iter_from(&Column::BlockBody, b"b").unwrap()), vec![entry(b"b", b"before")]);
+    assert_eq!(
+        collect(db.iter(&Column::BlockBody).unwrap()),
+        vec![entry(b"a", b"after"), entry(b"c", b"after")]
+    );
+}
+
+// Runs every conformance test against the backend opened by `$open`, given the columns to open
+macro_rules! conformance_suite {
+    ($backend:ident, $open:expr) => {
+        mod $backend
+        {
+            use super::*;
+
+            #[test]
+            fn test_get_put_delete()
+            {
+                get_put_delete(&*$open(&Column::ALL));
+            }
+
+            #[test]
+            fn test_columns_are_independent()
+            {
+                columns_are_independent(&*$open(&Column::ALL));
+            }
+
+            #[test]
+            fn test_unknown_column()
+            {
+                unknown_column(&*$open(&[Column::BlockHeader]));
+            }
+
+            #[test]
+            fn test_batch_applies_in_order()
+            {
+                batch_applies_in_order(&*$open(&Column::ALL));
+            }
+
+            #[test]
+            fn test_batch_is_atomic()
+            {
+                batch_is_atomic(&*$open(&[Column::BlockHeader]));
+            }
+
+            #[test]
+            fn test_iteration_in_key_order()
+            {
+                iteration_in_key_order(&*$open(&Column::ALL));
+            }
+
+            #[test]
+            fn test_multi_get_keeps_key_order()
+            {
+                multi_get_keeps_key_order(&*$open(&Column::ALL));
+            }
+
+            #[test]
+            fn test_snapshot_is_isolated()
+            {
+                snapshot_is_isolated(&*$open(&Column::ALL));
+            }
+        }
+    };
+}
+
+conformance_suite!(memory, |columns: &[Column]| Box::new(MemoryDB::with_columns(columns)));
+conformance_suite!(rocksdb, |columns: &[Column]| TemporaryRocksDB::with_columns("conformance", columns));
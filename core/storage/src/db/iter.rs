@@ -1,4 +1,5 @@
 pub use rocksdb::{DBIterator, IteratorMode};
+use crate::error::StorageError;
 
 // Structure for RocksDB Iteration
 pub struct RocksDBIterator<'a> 
@@ -19,15 +20,13 @@ impl<'a> RocksDBIterator<'a>
 // Implement the Iterator trait for RocksDBIterator
 impl<'a> Iterator for RocksDBIterator<'a>
 {
-    // The Item relation of the Iterator is a tuple of type Box<[u8]> for both key and value
-    type Item = (Box<[u8]>, Box<[u8]>);
+    // The Item relation of the Iterator is a tuple of type Box<[u8]> for both key and value,
+    // or the error that interrupted the iteration
+    type Item = Result<(Box<[u8]>, Box<[u8]>), StorageError>;
     
     // Function to get the next item
     fn next(&mut self) -> Option<Self::Item>
     {
-        match self.iter.next() {
-            Some(Ok((key, value))) => Some((key, value)),
-            _ => None,
-        }
+        self.iter.next().map(|item| item.map_err(StorageError::from))
     }
 }
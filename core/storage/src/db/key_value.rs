@@ -0,0 +1,60 @@
+//! # Key-Value Store
+//!
+//! This module defines the `KeyValueStore` trait, the operations a storage backend provides over
+//! the columns of the database, and `KeyValueSnapshot`, a frozen view of a backend.
+//!
+//! `RocksDB` is the backend of a node and `MemoryDB` the backend of tests. Both must behave the
+//! same: a missing key reads as `None`, iteration visits keys in byte order, the writes of a batch
+//! are applied in order and all at once, and a snapshot never sees writes made after it was taken.
+//! The conformance suite runs the same tests against both.
+
+use crate::{column::Column, db::write_batch::WriteBatch, error::StorageError};
+
+// An iterator over the key-value pairs of a column in key order
+pub type KeyValueIterator<'a> = Box<dyn Iterator<Item = Result<(Box<[u8]>, Box<[u8]>), StorageError>> + 'a>;
+
+// 'KeyValueSnapshot' defines reads from a view of a backend frozen when it was taken.
+pub trait KeyValueSnapshot
+{
+    // The 'get' function reads the value stored for a key in a column
+    fn get(&self, column: &Column, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError>;
+
+    // The 'iter_from' function iterates over a column in key order, starting at the first key not below `from`
+    fn iter_from(&self, column: &Column, from: &[u8]) -> Result<KeyValueIterator<'_>, StorageError>;
+}
+
+// 'KeyValueStore' defines the operations of a storage backend.
+pub trait KeyValueStore
+{
+    type Snapshot<'a>: KeyValueSnapshot where Self: 'a;
+
+    // The 'get' function reads the value stored for a key in a column
+    fn get(&self, column: &Column, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError>;
+
+    // The 'put' function writes a value for a key in a column
+    fn put(&self, column: &Column, key: &[u8], value: &[u8]) -> Result<(), StorageError>;
+
+    // The 'delete' function removes the value stored for a key in a column
+    fn delete(&self, column: &Column, key: &[u8]) -> Result<(), StorageError>;
+
+    // The 'write_batch' function applies every write of a batch atomically, whatever columns they touch
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), StorageError>;
+
+    // The 'iter_from' function iterates over a column in key order, starting at the first key not below `from`
+    fn iter_from(&self, column: &Column, from: &[u8]) -> Result<KeyValueIterator<'_>, StorageError>;
+
+    // The 'snapshot' function freezes the current content of the backend
+    fn snapshot(&self) -> Self::Snapshot<'_>;
+
+    // The 'multi_get' function reads the values stored for several keys in a column, in the order of the keys
+    fn multi_get(&self, column: &Column, keys: &[Vec<u8>]) -> Result<Vec<Option<Vec<u8>>>, StorageError>
+    {
+        keys.iter().map(|key| self.get(column, key)).collect()
+    }
+
+    // The 'iter' function iterates over every key-value pair of a column in key order
+    fn iter(&self, column: &Column) -> Result<KeyValueIterator<'_>, StorageError>
+    {
+        self.iter_from(column, &[])
+    }
+}
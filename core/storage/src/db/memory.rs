@@ -0,0 +1,144 @@
+//! # Memory Database
+//!
+//! This module defines `MemoryDB`, a `KeyValueStore` keeping every column in a `BTreeMap`, for tests
+//! of the layers above storage. A batch is applied under a single write lock, and a snapshot is a
+//! copy of the columns taken under a read lock.
+
+use std::{collections::{BTreeMap, HashMap}, sync::RwLock};
+use crate::{
+    column::Column,
+    db::{
+        key_value::{KeyValueIterator, KeyValueSnapshot, KeyValueStore},
+        write_batch::{BatchOperation, WriteBatch},
+    },
+    error::StorageError,
+};
+
+type Columns = HashMap<Column, BTreeMap<Vec<u8>, Vec<u8>>>;
+
+// The 'column' function returns the entries of a column
+fn column<'a>(columns: &'a Columns, column: &Column) -> Result<&'a BTreeMap<Vec<u8>, Vec<u8>>, StorageError>
+{
+    columns.get(column).ok_or_else(|| StorageError::ColumnNotFound(column.to_string()))
+}
+
+// The 'iter_from' function collects the entries of a column from the first key not below `from`
+fn iter_from<'a>(columns: &Columns, name: &Column, from: &[u8]) -> Result<KeyValueIterator<'a>, StorageError>
+{
+    let entries: Vec<_> = column(columns, name)?
+        .range(from.to_vec()..)
+        .map(|(key, value)| Ok((key.clone().into_boxed_slice(), value.clone().into_boxed_slice())))
+        .collect();
+    Ok(Box::new(entries.into_iter()))
+}
+
+// An in-memory database
+#[derive(Debug)]
+pub struct MemoryDB
+{
+    columns: RwLock<Columns>,
+}
+
+impl MemoryDB
+{
+    // The 'new' function creates an empty database with every column.
+    pub fn new() -> Self
+    {
+        Self::with_columns(&Column::ALL)
+    }
+
+    // The 'with_columns' function creates an empty database with the given columns.
+    pub fn with_columns(columns: &[Column]) -> Self
+    {
+        MemoryDB { columns: RwLock::new(columns.iter().map(|column| (*column, BTreeMap::new())).collect()) }
+    }
+}
+
+impl Default for MemoryDB
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl KeyValueStore for MemoryDB
+{
+    type Snapshot<'a> = MemorySnapshot;
+
+    fn get(&self, name: &Column, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError>
+    {
+        let columns = self.columns.read().expect("Lock is not poisoned");
+        Ok(column(&columns, name)?.get(key).cloned())
+    }
+
+    fn put(&self, column: &Column, key: &[u8], value: &[u8]) -> Result<(), StorageError>
+    {
+        let mut batch = WriteBatch::new();
+        batch.put(*column, key, value);
+        self.write_batch(batch)
+    }
+
+    fn delete(&self, column: &Column, key: &[u8]) -> Result<(), StorageError>
+    {
+        let mut batch = WriteBatch::new();
+        batch.delete(*column, key);
+        self.write_batch(batch)
+    }
+
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), StorageError>
+    {
+        let mut columns = self.columns.write().expect("Lock is not poisoned");
+        // Every column is checked before the first write, so that a batch is applied entirely or not at all.
+        for operation in batch.operations()
+        {
+            let (BatchOperation::Put { column: name, .. } | BatchOperation::Delete { column: name, .. }) = operation;
+            column(&columns, name)?;
+        }
+        for operation in batch.operations()
+        {
+            match operation
+            {
+                BatchOperation::Put { column, key, value } =>
+                {
+                    columns.get_mut(column).expect("Column was checked").insert(key.clone(), value.clone());
+                },
+                BatchOperation::Delete { column, key } =>
+                {
+                    columns.get_mut(column).expect("Column was checked").remove(key);
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn iter_from(&self, column: &Column, from: &[u8]) -> Result<KeyValueIterator<'_>, StorageError>
+    {
+        iter_from(&self.columns.read().expect("Lock is not poisoned"), column, from)
+    }
+
+    fn snapshot(&self) -> MemorySnapshot
+    {
+        MemorySnapshot { columns: self.columns.read().expect("Lock is not poisoned").clone() }
+    }
+}
+
+// A copy of the columns of a 'MemoryDB'
+#[derive(Debug, Clone)]
+pub struct MemorySnapshot
+{
+    columns: Columns,
+}
+
+impl KeyValueSnapshot for MemorySnapshot
+{
+    fn get(&self, name: &Column, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError>
+    {
+        Ok(column(&self.columns, name)?.get(key).cloned())
+    }
+
+    fn iter_from(&self, column: &Column, from: &[u8]) -> Result<KeyValueIterator<'_>, StorageError>
+    {
+        iter_from(&self.columns, column, from)
+    }
+}
@@ -1,48 +1,51 @@
 use core_utils::configs::db::StoreConfig;
 use rocksdb::{
     DB, Options, WriteBatch, ColumnFamily, ColumnFamilyDescriptor,
-    IteratorMode,
+    IteratorMode, Direction,
 };
 use std::path::Path;
-use std::collections::HashMap;
-use crate::{column::Column, db::iter::RocksDBIterator};
+use crate::{
+    column::Column,
+    db::{
+        iter::RocksDBIterator,
+        key_value::{KeyValueIterator, KeyValueStore},
+        snapshot::Snapshot,
+        write_batch::{BatchOperation, WriteBatch as StorageWriteBatch},
+    },
+    error::StorageError,
+};
 
 pub struct RocksDB
 {
     db: DB,
     db_opt: Options,
-    db_cf: HashMap<Column, ColumnFamily>,
 }
 
 // Database operations
 impl RocksDB 
 {
-    pub fn open(
-        path: &Path, 
-        db_cf: HashMap<Column, ColumnFamily>
-    ) -> Result<Self, rocksdb::Error> {
-        let db_opt = Options::default();
-        let db = DB::open_default(path)?;
-        Ok(Self { db, db_opt, db_cf })
+    // Opens the database with every column, creating the database and missing columns. Column
+    // families this version does not know are opened too, so that the schema version can be checked.
+    pub fn open(path: &Path, config: &StoreConfig) -> Result<Self, rocksdb::Error>
+    {
+        Self::open_with_columns(path, config, &Column::ALL)
     }
 
     fn open_with_options(
         path: &Path,
         db_opt: Options,
-        db_cf: HashMap<Column, ColumnFamily>
     ) -> Result<Self, rocksdb::Error> {
         let db = DB::open(&db_opt, path)?;
-        Ok(Self { db, db_opt, db_cf })
+        Ok(Self { db, db_opt })
     }
 
     fn open_with_columns(
         path: &Path,
         config: &StoreConfig,
-        db_cf: HashMap<Column, ColumnFamily>,
         columns: &[Column],
     ) -> Result<Self, rocksdb::Error> {
         let (db, db_opt) = Self::open_db(path, config, columns)?;
-        Ok(Self { db, db_opt, db_cf })
+        Ok(Self { db, db_opt })
     }
 
     fn open_db(
@@ -50,12 +53,23 @@ impl RocksDB
         config: &StoreConfig,
         columns: &[Column],
      ) -> Result<(DB, Options), rocksdb::Error> {
-        let db_opt = config.to_options();
-        let cf_descriptors: Vec<_> = columns.iter().map(|column| {
-            let column = column.to_string(); 
+        let mut db_opt = config.to_options();
+        db_opt.create_if_missing(true);
+        db_opt.create_missing_column_families(true);
+        // RocksDB refuses to open a database without every one of its column families, such as
+        // the ones added by a newer version. A database that does not exist yet has none.
+        let mut names: Vec<String> = columns.iter().map(Column::to_string).collect();
+        for name in DB::list_cf(&db_opt, path).unwrap_or_default()
+        {
+            if !names.contains(&name)
+            {
+                names.push(name);
+            }
+        }
+        let cf_descriptors: Vec<_> = names.into_iter().map(|name| {
             let options = Options::default();
-            ColumnFamilyDescriptor::new(column, options)
-        }).collect();   
+            ColumnFamilyDescriptor::new(name, options)
+        }).collect();
         let db = DB::open_cf_descriptors(&db_opt, path, cf_descriptors)?;
         Ok((db, db_opt))
     }
@@ -81,5 +95,138 @@ impl RocksDB
          RocksDBIterator::new(self.db.iterator(mode))
     }
 
+    // Returns the handle of a column family.
+    fn cf_handle(&self, column: &Column) -> Result<&ColumnFamily, StorageError>
+    {
+        self.db
+            .cf_handle(&column.to_string())
+            .ok_or_else(|| StorageError::ColumnNotFound(column.to_string()))
+    }
+
+    // Writes several key-value pairs in a column atomically.
+    pub fn put_batch<K, V, I>(&self, column: &Column, entries: I) -> Result<(), StorageError>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let cf = self.cf_handle(column)?;
+        let mut batch = WriteBatch::default();
+        for (key, value) in entries
+        {
+            batch.put_cf(cf, key, value);
+        }
+        Ok(self.db.write(batch)?)
+    }
+
+    // Iterates over every key-value pair of a column in key order.
+    pub fn iter_column<'a>(&'a self, column: &Column) -> Result<RocksDBIterator<'a>, StorageError>
+    {
+        let cf = self.cf_handle(column)?;
+        Ok(RocksDBIterator::new(self.db.iterator_cf(cf, IteratorMode::Start)))
+    }
 }
 
+impl KeyValueStore for RocksDB
+{
+    type Snapshot<'a> = Snapshot<'a>;
+
+    fn get(&self, column: &Column, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError>
+    {
+        let cf = self.cf_handle(column)?;
+        Ok(self.db.get_cf(cf, key)?)
+    }
+
+    fn put(&self, column: &Column, key: &[u8], value: &[u8]) -> Result<(), StorageError>
+    {
+        let cf = self.cf_handle(column)?;
+        Ok(self.db.put_cf(cf, key, value)?)
+    }
+
+    fn delete(&self, column: &Column, key: &[u8]) -> Result<(), StorageError>
+    {
+        let cf = self.cf_handle(column)?;
+        Ok(self.db.delete_cf(cf, key)?)
+    }
+
+    fn write_batch(&self, batch: StorageWriteBatch) -> Result<(), StorageError>
+    {
+        let mut inner = WriteBatch::default();
+        for operation in batch.operations()
+        {
+            match operation
+            {
+                BatchOperation::Put { column, key, value } => inner.put_cf(self.cf_handle(column)?, key, value),
+                BatchOperation::Delete { column, key } => inner.delete_cf(self.cf_handle(column)?, key),
+            }
+        }
+        Ok(self.db.write(inner)?)
+    }
+
+    fn iter_from(&self, column: &Column, from: &[u8]) -> Result<KeyValueIterator<'_>, StorageError>
+    {
+        let cf = self.cf_handle(column)?;
+        Ok(Box::new(RocksDBIterator::new(self.db.iterator_cf(cf, IteratorMode::From(from, Direction::Forward)))))
+    }
+
+    fn snapshot(&self) -> Snapshot<'_>
+    {
+        Snapshot::new(&self.db)
+    }
+
+    fn multi_get(&self, column: &Column, keys: &[Vec<u8>]) -> Result<Vec<Option<Vec<u8>>>, StorageError>
+    {
+        let cf = self.cf_handle(column)?;
+        self.db
+            .multi_get_cf(keys.iter().map(|key| (cf, key)))
+            .into_iter()
+            .map(|value| value.map_err(StorageError::from))
+            .collect()
+    }
+}
+
+
+// A database in a fresh temporary directory, destroyed when dropped
+#[cfg(test)]
+pub(crate) struct TemporaryRocksDB
+{
+    db: Option<RocksDB>,
+    path: std::path::PathBuf,
+}
+
+#[cfg(test)]
+impl TemporaryRocksDB
+{
+    pub(crate) fn with_columns(name: &str, columns: &[Column]) -> Self
+    {
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+        let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("frenyum-{}-{}-{}-{}", name, std::process::id(), nanos, count));
+        let db = RocksDB::open_with_columns(&path, &StoreConfig::default(), columns).expect("Temporary database opens");
+        TemporaryRocksDB { db: Some(db), path }
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TemporaryRocksDB
+{
+    type Target = RocksDB;
+
+    fn deref(&self) -> &RocksDB
+    {
+        self.db.as_ref().expect("Database is open")
+    }
+}
+
+#[cfg(test)]
+impl Drop for TemporaryRocksDB
+{
+    fn drop(&mut self)
+    {
+        // The database must be closed before its files are removed.
+        self.db.take();
+        let _ = DB::destroy(&Options::default(), &self.path);
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -1,5 +1,10 @@
 // RocksDB snapshot wrapper
-use rocksdb::{DB, ColumnFamily, ReadOptions, Error, IteratorMode, DBIterator, DBRawIterator, Snapshot as RocksDBSnapshot};
+use rocksdb::{DB, ColumnFamily, ReadOptions, Error, IteratorMode, DBIterator, DBRawIterator, Direction, Snapshot as RocksDBSnapshot};
+use crate::{
+    column::Column,
+    db::{iter::RocksDBIterator, key_value::{KeyValueIterator, KeyValueSnapshot}},
+    error::StorageError,
+};
 
 // Represents a snapshot taken from RocksDB.
 pub struct Snapshot<'a>
@@ -55,6 +60,33 @@ impl<'a> Snapshot<'a>
     }
 }
 
+impl<'a> Snapshot<'a>
+{
+    // Returns the handle of a column family.
+    fn cf_handle(&self, column: &Column) -> Result<&'a ColumnFamily, StorageError>
+    {
+        self.db
+            .cf_handle(&column.to_string())
+            .ok_or_else(|| StorageError::ColumnNotFound(column.to_string()))
+    }
+}
+
+impl<'a> KeyValueSnapshot for Snapshot<'a>
+{
+    fn get(&self, column: &Column, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError>
+    {
+        Ok(self.get_cf(self.cf_handle(column)?, key)?)
+    }
+
+    fn iter_from(&self, column: &Column, from: &[u8]) -> Result<KeyValueIterator<'_>, StorageError>
+    {
+        let mut readopts = ReadOptions::default();
+        readopts.set_snapshot(&self.snapshot);
+        let iter = self.db.iterator_cf_opt(self.cf_handle(column)?, readopts, IteratorMode::From(from, Direction::Forward));
+        Ok(Box::new(RocksDBIterator::new(iter)))
+    }
+}
+
 // To specify that the Snapshot structure is thread-safe, we set the Sync and Send traits to
 // must be marked manually. Because snapshots are only read and not modified.
 unsafe impl<'a> Sync for Snapshot<'a> {}
@@ -1,56 +1,77 @@
-use rocksdb::{TransactionDB, WriteBatch};
-use std::sync::Arc;
+// Column-aware write batch
+use crate::column::Column;
 
-pub struct RocksDBWriteBatch
+// A write recorded in a batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOperation
 {
-    pub(crate) db: Arc<TransactionDB>,
-    pub(crate) inner: WriteBatch,
+    Put { column: Column, key: Vec<u8>, value: Vec<u8> },
+    Delete { column: Column, key: Vec<u8> },
 }
 
-impl RocksDBWriteBatch
+// Collects writes to several columns, applied atomically by 'KeyValueStore::write_batch'
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteBatch
 {
+    operations: Vec<BatchOperation>,
+    size_in_bytes: usize,
+}
+
+impl WriteBatch
+{
+    // The 'new' function creates an empty batch.
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    // The 'len' function returns the number of writes in the batch.
     pub fn len(&self) -> usize
     {
-        self.inner.len()
+        self.operations.len()
     }
 
+    // The 'size_in_bytes' function returns the size of the keys and values in the batch.
     pub fn size_in_bytes(&self) -> usize
     {
-        self.inner.size_in_bytes()
+        self.size_in_bytes
     }
 
+    // The 'is_empty' function checks if the batch has no writes.
     pub fn is_empty(&self) -> bool
     {
-        self.inner.is_empty()
+        self.operations.is_empty()
     }
 
-    pub fn put<K, V>(
-        &mut self,
-        key: K,
-        value: V,
-    ) -> Result<(), rocksdb::Error>
+    // The 'put' function records a value to write for a key in a column.
+    pub fn put<K, V>(&mut self, column: Column, key: K, value: V)
     where
         K: AsRef<[u8]>,
         V: AsRef<[u8]>,
     {
-        self.inner.put(key, value)?;
-        Ok(())
+        let (key, value) = (key.as_ref().to_vec(), value.as_ref().to_vec());
+        self.size_in_bytes += key.len() + value.len();
+        self.operations.push(BatchOperation::Put { column, key, value });
     }
 
-    pub fn delete<K>(
-        &mut self,
-        key: K,
-    ) -> Result<(), rocksdb::Error>
-    where
-        K: AsRef<[u8]>,
+    // The 'delete' function records the deletion of a key in a column.
+    pub fn delete<K: AsRef<[u8]>>(&mut self, column: Column, key: K)
     {
-        self.inner.put(key)?;
-        Ok(())
+        let key = key.as_ref().to_vec();
+        self.size_in_bytes += key.len();
+        self.operations.push(BatchOperation::Delete { column, key });
     }
 
-    pub fn clear(&mut self) -> Result<(), rocksdb::Error>
+    // The 'operations' function returns the writes in the order they were recorded.
+    pub fn operations(&self) -> &[BatchOperation]
     {
-        self.inner.clear();
+        &self.operations
     }
-}
 
+    // The 'clear' function drops every write of the batch.
+    pub fn clear(&mut self)
+    {
+        self.operations.clear();
+        self.size_in_bytes = 0;
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt;
+use crypto::hash::HashDigest;
+use primvites::codec::CodecError;
+
+// Represents errors that can occur during storage operations.
+#[derive(Debug)]
+pub enum StorageError
+{
+    RocksDB(rocksdb::Error),
+    ColumnNotFound(String),
+    Codec(CodecError),
+    InvalidKey { expected: usize, found: usize },
+    InvalidValue { expected: usize, found: usize },
+    UnknownBlock(HashDigest),
+    UnknownMetadataKey(String),
+    IncompatibleSchemaVersion { found: u64, supported: u64 },
+    Migration { version: u64, error: Box<StorageError> },
+}
+
+impl fmt::Display for StorageError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            StorageError::RocksDB(e) => write!(f, "RocksDB error: {}", e),
+            StorageError::ColumnNotFound(column) => write!(f, "Column family not found: {}", column),
+            StorageError::Codec(e) => write!(f, "Codec error: {}", e),
+            StorageError::InvalidKey { expected, found } =>
+                write!(f, "Invalid key length: expected {} bytes, found {}", expected, found),
+            StorageError::InvalidValue { expected, found } =>
+                write!(f, "Invalid value length: expected {} bytes, found {}", expected, found),
+            StorageError::UnknownBlock(hash) => write!(f, "Unknown block: {}", hash),
+            StorageError::UnknownMetadataKey(key) => write!(f, "Unknown metadata key: {}", key),
+            StorageError::IncompatibleSchemaVersion { found, supported } =>
+                write!(f, "Database schema version {} is newer than the supported version {}, upgrade the node to open it", found, supported),
+            StorageError::Migration { version, error } =>
+                write!(f, "Migration to schema version {} failed: {}", version, error),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<rocksdb::Error> for StorageError
+{
+    fn from(err: rocksdb::Error) -> Self
+    {
+        StorageError::RocksDB(err)
+    }
+}
+
+impl From<CodecError> for StorageError
+{
+    fn from(err: CodecError) -> Self
+    {
+        StorageError::Codec(err)
+    }
+}
@@ -0,0 +1,71 @@
+// Block header storage, keyed by block hash
+use crypto::hash::HashDigest;
+use primvites::{BlockNumber, block_header::BlockHeader, bloom::LogQuery};
+use crate::{db::key_value::KeyValueStore, error::StorageError, schema::HeaderSchema, store::SchemaStore};
+
+pub struct HeaderStore<'a, D: KeyValueStore>
+{
+    store: SchemaStore<'a, D>,
+}
+
+impl<'a, D: KeyValueStore> HeaderStore<'a, D>
+{
+    // The 'new' function creates a header store over the given database.
+    pub fn new(db: &'a D) -> Self
+    {
+        HeaderStore { store: SchemaStore::new(db) }
+    }
+
+    // The 'put_header' function stores a block header under its hash.
+    pub fn put_header(&self, header: &BlockHeader) -> Result<(), StorageError>
+    {
+        self.store.put::<HeaderSchema>(header.hash(), header)
+    }
+
+    // The 'get_header' function reads the header of a block.
+    pub fn get_header(&self, hash: &HashDigest) -> Result<Option<BlockHeader>, StorageError>
+    {
+        self.store.get::<HeaderSchema>(hash)
+    }
+
+    // The 'delete_header' function removes the header of a block.
+    pub fn delete_header(&self, hash: &HashDigest) -> Result<(), StorageError>
+    {
+        self.store.delete::<HeaderSchema>(hash)
+    }
+
+    // The 'candidate_blocks' function walks the chain ending at `head` back through parent hashes and
+    // returns the number and hash of every block in `from..=to` whose logs bloom might match the query,
+    // ordered by block number. Blocks off that chain are never returned, and the walk stops early at a
+    // header that is not stored. Candidates can be false positives, so their receipts must still be
+    // checked with `matches_log`.
+    pub fn candidate_blocks(
+        &self,
+        head: &HashDigest,
+        from: BlockNumber,
+        to: BlockNumber,
+        query: &LogQuery,
+    ) -> Result<Vec<(BlockNumber, HashDigest)>, StorageError> {
+        let mut candidates = Vec::new();
+        let mut next = self.get_header(head)?;
+        while let Some(header) = next
+        {
+            let number = header.block_number();
+            if number < from
+            {
+                break;
+            }
+            if number <= to && query.matches(header.logs_bloom())
+            {
+                candidates.push((number, *header.hash()));
+            }
+            if number == 0
+            {
+                break;
+            }
+            next = self.get_header(header.parent_hash())?;
+        }
+        candidates.reverse();
+        Ok(candidates)
+    }
+}
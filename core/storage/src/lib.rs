@@ -1,2 +1,9 @@
 pub mod column;
 pub mod db;
+pub mod error;
+pub mod receipt;
+pub mod header;
+pub mod schema;
+pub mod store;
+pub mod chain;
+pub mod migration;
@@ -0,0 +1,359 @@
+//! # Migration
+//!
+//! This module records the version of the layout a database is written with, and upgrades older
+//! databases to the current layout in place, so that upgrading a node never requires a resync.
+//!
+//! The version is stored in the `Metadata` column. A database newer than `SCHEMA_VERSION` is refused,
+//! since this node cannot know its layout. An empty database is stamped with `SCHEMA_VERSION`, and a
+//! database written before the version was recorded is at `LEGACY_SCHEMA_VERSION`.
+//!
+//! A `Migration` upgrades the database to its version in steps. Each step is written in a single batch
+//! together with the cursor it stopped at, and the last one together with the new version, so that an
+//! interrupted migration resumes from its last step when the database is opened again. A new column is
+//! created when the database is opened, before any migration runs, so a migration adding a column only
+//! has to fill it.
+
+use crate::{
+    column::Column,
+    db::{key_value::KeyValueStore, write_batch::WriteBatch},
+    error::StorageError,
+    schema::{MigrationCursorSchema, SchemaVersionSchema},
+    store::{SchemaBatch, SchemaStore},
+};
+
+// The version of the layout written by this node
+pub const SCHEMA_VERSION: u64 = 1;
+
+// The version of the databases written before the version was recorded
+pub const LEGACY_SCHEMA_VERSION: u64 = 1;
+
+// What remains of a migration after a step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Progress
+{
+    // The migration continues after the given cursor
+    Continue(Vec<u8>),
+    Done,
+}
+
+// 'Migration' defines an upgrade of the database from the previous schema version.
+pub trait Migration<D: KeyValueStore>
+{
+    // The 'version' function returns the schema version of the database once the migration is applied
+    fn version(&self) -> u64;
+
+    // The 'description' function describes what the migration changes
+    fn description(&self) -> &str;
+
+    // The 'step' function records in `batch` the writes of the next step, starting after `cursor`, which is
+    // returned by the previous step, or `None` for the first one. A step must only read data it has not written.
+    fn step(&self, db: &D, cursor: Option<&[u8]>, batch: &mut WriteBatch) -> Result<Progress, StorageError>;
+}
+
+// Returns the new encoding of the value stored for a key
+pub type Reencode = fn(&[u8], &[u8]) -> Result<Vec<u8>, StorageError>;
+
+// Re-encodes every value of a column, a bounded number of entries per step
+pub struct ReencodeColumn
+{
+    pub version: u64,
+    pub description: &'static str,
+    pub column: Column,
+    pub reencode: Reencode,
+    pub entries_per_step: usize,
+}
+
+impl<D: KeyValueStore> Migration<D> for ReencodeColumn
+{
+    fn version(&self) -> u64
+    {
+        self.version
+    }
+
+    fn description(&self) -> &str
+    {
+        self.description
+    }
+
+    fn step(&self, db: &D, cursor: Option<&[u8]>, batch: &mut WriteBatch) -> Result<Progress, StorageError>
+    {
+        // The first key after the cursor is the cursor followed by a zero byte.
+        let from = cursor.map_or_else(Vec::new, |cursor| [cursor, &[0]].concat());
+        let limit = self.entries_per_step.max(1);
+        let (mut count, mut last) = (0, None);
+        for entry in db.iter_from(&self.column, &from)?.take(limit)
+        {
+            let (key, value) = entry?;
+            batch.put(self.column, &key, (self.reencode)(&key, &value)?);
+            count += 1;
+            last = Some(key.into_vec());
+        }
+        // A full step may have left entries behind, a shorter one reached the end of the column.
+        Ok(match last
+        {
+            Some(key) if count == limit => Progress::Continue(key),
+            _ => Progress::Done,
+        })
+    }
+}
+
+// The 'migrations' function returns the migrations from 'LEGACY_SCHEMA_VERSION' to 'SCHEMA_VERSION'.
+pub fn migrations<D: KeyValueStore>() -> Vec<Box<dyn Migration<D>>>
+{
+    Vec::new()
+}
+
+// Checks the schema version of a database and applies the migrations it is missing
+pub struct Migrator<D: KeyValueStore>
+{
+    target: u64,
+    migrations: Vec<Box<dyn Migration<D>>>,
+}
+
+impl<D: KeyValueStore> Migrator<D>
+{
+    // The 'new' function creates a migrator up to `target`. The migrations must be ordered by version,
+    // and none of them may go past `target`.
+    pub fn new(target: u64, migrations: Vec<Box<dyn Migration<D>>>) -> Self
+    {
+        for pair in migrations.windows(2)
+        {
+            assert!(pair[0].version() < pair[1].version(), "Migrations must be ordered by version");
+        }
+        assert!(
+            migrations.iter().all(|migration| migration.version() <= target),
+            "Migrations must not go past the target version"
+        );
+        Migrator { target, migrations }
+    }
+
+    // The 'latest' function creates a migrator up to 'SCHEMA_VERSION'.
+    pub fn latest() -> Self
+    {
+        Self::new(SCHEMA_VERSION, migrations())
+    }
+
+    // The 'version' function reads the schema version of a database, or `None` for an empty database.
+    pub fn version(db: &D) -> Result<Option<u64>, StorageError>
+    {
+        if let Some(version) = SchemaStore::new(db).get_metadata::<SchemaVersionSchema>()?
+        {
+            return Ok(Some(version));
+        }
+        for column in Column::ALL
+        {
+            if db.iter(&column)?.next().is_some()
+            {
+                return Ok(Some(LEGACY_SCHEMA_VERSION));
+            }
+        }
+        Ok(None)
+    }
+
+    // The 'run' function brings a database to the target version, resuming an interrupted migration.
+    pub fn run(&self, db: &D) -> Result<(), StorageError>
+    {
+        let store = SchemaStore::new(db);
+        let version = match Self::version(db)?
+        {
+            Some(version) => version,
+            None => return store.put_metadata::<SchemaVersionSchema>(&self.target),
+        };
+        if version > self.target
+        {
+            return Err(StorageError::IncompatibleSchemaVersion { found: version, supported: self.target });
+        }
+        for migration in self.migrations.iter().filter(|migration| migration.version() > version)
+        {
+            self.apply(db, migration.as_ref())
+                .map_err(|error| StorageError::Migration { version: migration.version(), error: Box::new(error) })?;
+        }
+        // The migrations may stop short of the target, and a legacy database has no version recorded yet.
+        if store.get_metadata::<SchemaVersionSchema>()? != Some(self.target)
+        {
+            store.put_metadata::<SchemaVersionSchema>(&self.target)?;
+        }
+        Ok(())
+    }
+
+    // The 'apply' function runs the steps of a migration from the cursor it stopped at.
+    fn apply(&self, db: &D, migration: &dyn Migration<D>) -> Result<(), StorageError>
+    {
+        let store = SchemaStore::new(db);
+        let mut cursor = store.get_metadata::<MigrationCursorSchema>()?;
+        loop
+        {
+            let mut batch = WriteBatch::new();
+            let progress = migration.step(db, cursor.as_deref(), &mut batch)?;
+            let mut batch = SchemaBatch::from(batch);
+            match &progress
+            {
+                Progress::Continue(next) => batch.put_metadata::<MigrationCursorSchema>(next)?,
+                Progress::Done => {
+                    batch.delete_metadata::<MigrationCursorSchema>();
+                    batch.put_metadata::<SchemaVersionSchema>(&migration.version())?;
+                },
+            }
+            store.write(batch)?;
+            match progress
+            {
+                Progress::Continue(next) => cursor = Some(next),
+                Progress::Done => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::{db::{backend_tests, memory::MemoryDB}, schema::{KeyCodec, MetadataKey}};
+
+    fn version<D: KeyValueStore>(db: &D) -> Option<u64>
+    {
+        SchemaStore::new(db).get_metadata::<SchemaVersionSchema>().unwrap()
+    }
+
+    // Prefixes every value with its length, failing on the value "fail"
+    fn prefix_length(_key: &[u8], value: &[u8]) -> Result<Vec<u8>, StorageError>
+    {
+        if value == b"fail"
+        {
+            return Err(StorageError::InvalidValue { expected: 0, found: value.len() });
+        }
+        Ok([&[value.len() as u8], value].concat())
+    }
+
+    fn reencode<D: KeyValueStore>(version: u64) -> Box<dyn Migration<D>>
+    {
+        Box::new(ReencodeColumn {
+            version,
+            description: "Prefix receipts with their length",
+            column: Column::Receipt,
+            reencode: prefix_length,
+            entries_per_step: 2,
+        })
+    }
+
+    // Copies every receipt into the 'TransactionIndex' column, standing for a new column
+    struct FillColumn;
+
+    impl<D: KeyValueStore> Migration<D> for FillColumn
+    {
+        fn version(&self) -> u64
+        {
+            3
+        }
+
+        fn description(&self) -> &str
+        {
+            "Fill a new column"
+        }
+
+        fn step(&self, db: &D, _cursor: Option<&[u8]>, batch: &mut WriteBatch) -> Result<Progress, StorageError>
+        {
+            for entry in db.iter(&Column::Receipt)?
+            {
+                let (key, value) = entry?;
+                batch.put(Column::TransactionIndex, key, value);
+            }
+            Ok(Progress::Done)
+        }
+    }
+
+    fn test_empty_database_is_stamped<D: KeyValueStore>(db: &D)
+    {
+        assert_eq!(Migrator::<D>::version(db).unwrap(), None);
+        Migrator::new(3, vec![reencode(2)]).run(db).unwrap();
+        assert_eq!(version(db), Some(3));
+    }
+
+    fn test_latest_stamps_empty_database<D: KeyValueStore>(db: &D)
+    {
+        Migrator::<D>::latest().run(db).unwrap();
+        assert_eq!(version(db), Some(SCHEMA_VERSION));
+    }
+
+    fn test_unversioned_database_is_legacy<D: KeyValueStore>(db: &D)
+    {
+        db.put(&Column::Receipt, b"a", b"value").unwrap();
+        assert_eq!(Migrator::<D>::version(db).unwrap(), Some(LEGACY_SCHEMA_VERSION));
+        Migrator::<D>::latest().run(db).unwrap();
+        assert_eq!(version(db), Some(SCHEMA_VERSION));
+    }
+
+    fn test_newer_version_is_refused<D: KeyValueStore>(db: &D)
+    {
+        SchemaStore::new(db).put_metadata::<SchemaVersionSchema>(&(SCHEMA_VERSION + 1)).unwrap();
+        let error = Migrator::<D>::latest().run(db).unwrap_err();
+        assert!(matches!(
+            error,
+            StorageError::IncompatibleSchemaVersion { found, supported } if found == SCHEMA_VERSION + 1 && supported == SCHEMA_VERSION
+        ));
+        assert_eq!(version(db), Some(SCHEMA_VERSION + 1));
+    }
+
+    fn test_migrations_apply_in_order<D: KeyValueStore>(db: &D)
+    {
+        SchemaStore::new(db).put_metadata::<SchemaVersionSchema>(&1).unwrap();
+        for (key, value) in [(b"a", &b"x"[..]), (b"b", b"yy"), (b"c", b"zzz"), (b"d", b"")]
+        {
+            db.put(&Column::Receipt, key, value).unwrap();
+        }
+        Migrator::new(4, vec![reencode(2), Box::new(FillColumn)]).run(db).unwrap();
+        assert_eq!(version(db), Some(4));
+        assert_eq!(db.get(&Column::Receipt, b"c").unwrap(), Some(b"\x03zzz".to_vec()));
+        assert_eq!(db.get(&Column::Receipt, b"d").unwrap(), Some(vec![0]));
+        // The second migration sees the values written by the first.
+        assert_eq!(db.get(&Column::TransactionIndex, b"a").unwrap(), Some(b"\x01x".to_vec()));
+        assert_eq!(db.get(&Column::Metadata, &MetadataKey::MigrationCursor.encode_key()).unwrap(), None);
+
+        // Applied migrations are not applied again.
+        Migrator::new(4, vec![reencode(2), Box::new(FillColumn)]).run(db).unwrap();
+        assert_eq!(db.get(&Column::Receipt, b"c").unwrap(), Some(b"\x03zzz".to_vec()));
+    }
+
+    fn test_interrupted_migration_resumes<D: KeyValueStore>(db: &D)
+    {
+        SchemaStore::new(db).put_metadata::<SchemaVersionSchema>(&1).unwrap();
+        for (key, value) in [(b"a", &b"x"[..]), (b"b", b"y"), (b"c", b"fail"), (b"d", b"z")]
+        {
+            db.put(&Column::Receipt, key, value).unwrap();
+        }
+        let error = Migrator::new(2, vec![reencode(2)]).run(db).unwrap_err();
+        assert!(matches!(error, StorageError::Migration { version: 2, .. }));
+        // The first step was written, with the cursor it stopped at.
+        assert_eq!(version(db), Some(1));
+        assert_eq!(db.get(&Column::Receipt, b"b").unwrap(), Some(b"\x01y".to_vec()));
+        assert_eq!(db.get(&Column::Receipt, b"c").unwrap(), Some(b"fail".to_vec()));
+        assert_eq!(
+            SchemaStore::new(db).get_metadata::<MigrationCursorSchema>().unwrap(),
+            Some(b"b".to_vec())
+        );
+
+        db.put(&Column::Receipt, b"c", b"w").unwrap();
+        Migrator::new(2, vec![reencode(2)]).run(db).unwrap();
+        assert_eq!(version(db), Some(2));
+        // Entries migrated before the interruption are not migrated twice.
+        assert_eq!(db.get(&Column::Receipt, b"a").unwrap(), Some(b"\x01x".to_vec()));
+        assert_eq!(db.get(&Column::Receipt, b"c").unwrap(), Some(b"\x01w".to_vec()));
+        assert_eq!(db.get(&Column::Receipt, b"d").unwrap(), Some(b"\x01z".to_vec()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Migrations must be ordered by version")]
+    fn test_migrations_must_be_ordered()
+    {
+        Migrator::<MemoryDB>::new(3, vec![reencode(3), reencode(2)]);
+    }
+
+    backend_tests!(
+        test_empty_database_is_stamped,
+        test_latest_stamps_empty_database,
+        test_unversioned_database_is_legacy,
+        test_newer_version_is_refused,
+        test_migrations_apply_in_order,
+        test_interrupted_migration_resumes,
+    );
+}
@@ -0,0 +1,73 @@
+// Receipt storage, keyed by transaction hash
+use crypto::hash::HashDigest;
+use primvites::receipt::Receipt;
+use crate::{db::key_value::KeyValueStore, error::StorageError, schema::ReceiptSchema, store::{SchemaBatch, SchemaStore}};
+
+pub struct ReceiptStore<'a, D: KeyValueStore>
+{
+    store: SchemaStore<'a, D>,
+}
+
+impl<'a, D: KeyValueStore> ReceiptStore<'a, D>
+{
+    // The 'new' function creates a receipt store over the given database.
+    pub fn new(db: &'a D) -> Self
+    {
+        ReceiptStore { store: SchemaStore::new(db) }
+    }
+
+    // The 'put_receipts' function stores the receipts of a block atomically.
+    pub fn put_receipts(&self, receipts: &[Receipt]) -> Result<(), StorageError>
+    {
+        let mut batch = SchemaBatch::new();
+        for receipt in receipts
+        {
+            batch.put::<ReceiptSchema>(receipt.transaction_hash(), receipt)?;
+        }
+        self.store.write(batch)
+    }
+
+    // The 'get_receipt' function reads the receipt of a transaction.
+    pub fn get_receipt(&self, transaction_hash: &HashDigest) -> Result<Option<Receipt>, StorageError>
+    {
+        self.store.get::<ReceiptSchema>(transaction_hash)
+    }
+
+    // The 'delete_receipt' function removes the receipt of a transaction.
+    pub fn delete_receipt(&self, transaction_hash: &HashDigest) -> Result<(), StorageError>
+    {
+        self.store.delete::<ReceiptSchema>(transaction_hash)
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use core_utils::gas::Gas;
+    use primvites::{Address, receipt::Log, transaction::ExecutionStatus};
+    use crate::db::backend_tests;
+
+    fn receipt(byte: u8, status: ExecutionStatus) -> Receipt
+    {
+        let log = Log::new(Address::from([byte; 20]), vec![HashDigest::from([byte; HashDigest::LENGTH])], vec![byte]);
+        Receipt::new(HashDigest::from([byte; HashDigest::LENGTH]), status, Gas::new(21_000), Gas::new(21_000 * byte as u64), vec![log])
+    }
+
+    fn test_put_get_delete_receipts<D: KeyValueStore>(db: &D)
+    {
+        let receipts = ReceiptStore::new(db);
+        let (first, second) = (receipt(1, ExecutionStatus::Success), receipt(2, ExecutionStatus::Failure));
+        assert_eq!(receipts.get_receipt(first.transaction_hash()).unwrap(), None);
+
+        receipts.put_receipts(&[first.clone(), second.clone()]).unwrap();
+        assert_eq!(receipts.get_receipt(first.transaction_hash()).unwrap(), Some(first.clone()));
+        assert_eq!(receipts.get_receipt(second.transaction_hash()).unwrap(), Some(second.clone()));
+
+        receipts.delete_receipt(first.transaction_hash()).unwrap();
+        assert_eq!(receipts.get_receipt(first.transaction_hash()).unwrap(), None);
+        assert_eq!(receipts.get_receipt(second.transaction_hash()).unwrap(), Some(second));
+    }
+
+    backend_tests!(test_put_get_delete_receipts);
+}
@@ -0,0 +1,390 @@
+//! # Schema
+//!
+//! This module binds every column of the database to the type of its keys and values, so that
+//! stores read and write typed data instead of hand-rolled bytes.
+//!
+//! A `Schema` names a column, a `KeyCodec` key type and a `ValueCodec` value type. Keys are encoded
+//! so that their byte order follows their natural order: hashes as their bytes, and numbers in big
+//! endian. Range iteration over a column therefore visits keys in order. Values use the canonical
+//! codec of the primitives, except for hashes, difficulties and transaction locations, which are
+//! stored as fixed-length bytes.
+//!
+//! The `Metadata` column holds single values about the database as a whole, such as the canonical
+//! head or the schema version, each under its own `MetadataKey`. Since these values have different
+//! types, the column is not a `Schema`: every value has a `MetadataSchema` instead, which fixes its
+//! key and its type, and the column cannot be iterated.
+
+use crypto::hash::HashDigest;
+use primvites::{
+    BlockNumber,
+    U256,
+    block::BlockBody,
+    block_header::BlockHeader,
+    codec::Codec,
+    receipt::Receipt,
+    transaction::SignedTransaction,
+};
+use crate::{column::Column, error::StorageError};
+
+// 'KeyCodec' converts a key to and from bytes preserving its order.
+pub trait KeyCodec: Sized
+{
+    // The 'encode_key' function returns the bytes of the key
+    fn encode_key(&self) -> Vec<u8>;
+
+    // The 'decode_key' function reads a key from its bytes
+    fn decode_key(bytes: &[u8]) -> Result<Self, StorageError>;
+}
+
+// 'ValueCodec' converts a value to and from bytes.
+pub trait ValueCodec: Sized
+{
+    // The 'encode_value' function returns the bytes of the value
+    fn encode_value(&self) -> Result<Vec<u8>, StorageError>;
+
+    // The 'decode_value' function reads a value from its bytes
+    fn decode_value(bytes: &[u8]) -> Result<Self, StorageError>;
+}
+
+// 'Schema' binds a column to the type of its keys and values.
+pub trait Schema
+{
+    // The column the data is stored in
+    const COLUMN: Column;
+
+    type Key: KeyCodec;
+    type Value: ValueCodec;
+}
+
+// 'MetadataSchema' binds a key of the 'Metadata' column to the type of its value.
+pub trait MetadataSchema
+{
+    // The key the value is stored under
+    const KEY: MetadataKey;
+
+    type Value: ValueCodec;
+}
+
+// The 'fixed_key' function checks that a key has the length its type encodes to.
+fn fixed_key<const LENGTH: usize>(bytes: &[u8]) -> Result<[u8; LENGTH], StorageError>
+{
+    <[u8; LENGTH]>::try_from(bytes).map_err(|_| StorageError::InvalidKey { expected: LENGTH, found: bytes.len() })
+}
+
+// The 'fixed_value' function checks that a value has the length its type encodes to.
+fn fixed_value<const LENGTH: usize>(bytes: &[u8]) -> Result<[u8; LENGTH], StorageError>
+{
+    <[u8; LENGTH]>::try_from(bytes).map_err(|_| StorageError::InvalidValue { expected: LENGTH, found: bytes.len() })
+}
+
+impl KeyCodec for HashDigest
+{
+    fn encode_key(&self) -> Vec<u8>
+    {
+        self.to_byte().to_vec()
+    }
+
+    fn decode_key(bytes: &[u8]) -> Result<Self, StorageError>
+    {
+        Ok(HashDigest::from(fixed_key::<{ HashDigest::LENGTH }>(bytes)?))
+    }
+}
+
+impl KeyCodec for u64
+{
+    // Big endian, so that keys sort numerically.
+    fn encode_key(&self) -> Vec<u8>
+    {
+        self.to_be_bytes().to_vec()
+    }
+
+    fn decode_key(bytes: &[u8]) -> Result<Self, StorageError>
+    {
+        Ok(u64::from_be_bytes(fixed_key::<8>(bytes)?))
+    }
+}
+
+// The keys of the 'Metadata' column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataKey
+{
+    CanonicalHead,
+    SchemaVersion,
+    MigrationCursor,
+}
+
+impl MetadataKey
+{
+    // The 'name' function returns the name the key is stored under
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            MetadataKey::CanonicalHead => "CanonicalHead",
+            MetadataKey::SchemaVersion => "SchemaVersion",
+            MetadataKey::MigrationCursor => "MigrationCursor",
+        }
+    }
+}
+
+impl KeyCodec for MetadataKey
+{
+    fn encode_key(&self) -> Vec<u8>
+    {
+        self.name().as_bytes().to_vec()
+    }
+
+    fn decode_key(bytes: &[u8]) -> Result<Self, StorageError>
+    {
+        match bytes
+        {
+            b"CanonicalHead" => Ok(MetadataKey::CanonicalHead),
+            b"SchemaVersion" => Ok(MetadataKey::SchemaVersion),
+            b"MigrationCursor" => Ok(MetadataKey::MigrationCursor),
+            _ => Err(StorageError::UnknownMetadataKey(String::from_utf8_lossy(bytes).into_owned())),
+        }
+    }
+}
+
+// The block including a transaction and the position of the transaction in its body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionLocation
+{
+    pub block_hash: HashDigest,
+    pub index: usize,
+}
+
+impl ValueCodec for TransactionLocation
+{
+    fn encode_value(&self) -> Result<Vec<u8>, StorageError>
+    {
+        let mut bytes = self.block_hash.to_byte().to_vec();
+        bytes.extend_from_slice(&(self.index as u64).to_be_bytes());
+        Ok(bytes)
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, StorageError>
+    {
+        let bytes = fixed_value::<{ HashDigest::LENGTH + 8 }>(bytes)?;
+        let (block_hash, index) = bytes.split_at(HashDigest::LENGTH);
+        Ok(TransactionLocation {
+            block_hash: HashDigest::from(block_hash.to_vec()),
+            index: u64::from_be_bytes(index.try_into().expect("Eight bytes")) as usize,
+        })
+    }
+}
+
+impl ValueCodec for HashDigest
+{
+    fn encode_value(&self) -> Result<Vec<u8>, StorageError>
+    {
+        Ok(self.to_byte().to_vec())
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, StorageError>
+    {
+        Ok(HashDigest::from(fixed_value::<{ HashDigest::LENGTH }>(bytes)?))
+    }
+}
+
+impl ValueCodec for u64
+{
+    fn encode_value(&self) -> Result<Vec<u8>, StorageError>
+    {
+        Ok(self.to_be_bytes().to_vec())
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, StorageError>
+    {
+        Ok(u64::from_be_bytes(fixed_value::<8>(bytes)?))
+    }
+}
+
+// Raw bytes, stored as they are
+impl ValueCodec for Vec<u8>
+{
+    fn encode_value(&self) -> Result<Vec<u8>, StorageError>
+    {
+        Ok(self.clone())
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, StorageError>
+    {
+        Ok(bytes.to_vec())
+    }
+}
+
+impl ValueCodec for U256
+{
+    fn encode_value(&self) -> Result<Vec<u8>, StorageError>
+    {
+        let mut bytes = [0; 32];
+        self.to_big_endian(&mut bytes);
+        Ok(bytes.to_vec())
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, StorageError>
+    {
+        Ok(U256::from_big_endian(&fixed_value::<32>(bytes)?))
+    }
+}
+
+// Implements 'ValueCodec' with the canonical codec of the primitives.
+macro_rules! codec_value {
+    ($($value:ty),* $(,)?) => {
+        $(
+            impl ValueCodec for $value
+            {
+                fn encode_value(&self) -> Result<Vec<u8>, StorageError>
+                {
+                    Ok(self.encode()?)
+                }
+
+                fn decode_value(bytes: &[u8]) -> Result<Self, StorageError>
+                {
+                    Ok(<$value>::decode(bytes)?)
+                }
+            }
+        )*
+    };
+}
+
+codec_value!(BlockHeader, BlockBody, SignedTransaction, Receipt);
+
+// Block headers, keyed by block hash
+pub struct HeaderSchema;
+
+impl Schema for HeaderSchema
+{
+    const COLUMN: Column = Column::BlockHeader;
+    type Key = HashDigest;
+    type Value = BlockHeader;
+}
+
+// Block bodies, keyed by block hash
+pub struct BodySchema;
+
+impl Schema for BodySchema
+{
+    const COLUMN: Column = Column::BlockBody;
+    type Key = HashDigest;
+    type Value = BlockBody;
+}
+
+// Signed transactions, keyed by transaction hash
+pub struct TransactionSchema;
+
+impl Schema for TransactionSchema
+{
+    const COLUMN: Column = Column::Transaction;
+    type Key = HashDigest;
+    type Value = SignedTransaction;
+}
+
+// Transaction receipts, keyed by transaction hash
+pub struct ReceiptSchema;
+
+impl Schema for ReceiptSchema
+{
+    const COLUMN: Column = Column::Receipt;
+    type Key = HashDigest;
+    type Value = Receipt;
+}
+
+// The hash of the canonical block at every number
+pub struct CanonicalHashSchema;
+
+impl Schema for CanonicalHashSchema
+{
+    const COLUMN: Column = Column::CanonicalHash;
+    type Key = BlockNumber;
+    type Value = HashDigest;
+}
+
+// The location of every canonical transaction, keyed by transaction hash
+pub struct TransactionIndexSchema;
+
+impl Schema for TransactionIndexSchema
+{
+    const COLUMN: Column = Column::TransactionIndex;
+    type Key = HashDigest;
+    type Value = TransactionLocation;
+}
+
+// The total difficulty of the chain ending at every block, keyed by block hash
+pub struct TotalDifficultySchema;
+
+impl Schema for TotalDifficultySchema
+{
+    const COLUMN: Column = Column::TotalDifficulty;
+    type Key = HashDigest;
+    type Value = U256;
+}
+
+// The hash of the canonical head
+pub struct CanonicalHeadSchema;
+
+impl MetadataSchema for CanonicalHeadSchema
+{
+    const KEY: MetadataKey = MetadataKey::CanonicalHead;
+    type Value = HashDigest;
+}
+
+// The version of the layout the database is written with
+pub struct SchemaVersionSchema;
+
+impl MetadataSchema for SchemaVersionSchema
+{
+    const KEY: MetadataKey = MetadataKey::SchemaVersion;
+    type Value = u64;
+}
+
+// The position an interrupted migration stopped at
+pub struct MigrationCursorSchema;
+
+impl MetadataSchema for MigrationCursorSchema
+{
+    const KEY: MetadataKey = MetadataKey::MigrationCursor;
+    type Value = Vec<u8>;
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_number_keys_sort_numerically()
+    {
+        let numbers = [0u64, 1, 255, 256, 65_536, u64::MAX];
+        let keys: Vec<Vec<u8>> = numbers.iter().map(KeyCodec::encode_key).collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+        assert_eq!(u64::decode_key(&256u64.encode_key()).unwrap(), 256);
+    }
+
+    #[test]
+    fn test_key_length_is_checked()
+    {
+        let hash = HashDigest::from([4; HashDigest::LENGTH]);
+        assert_eq!(HashDigest::decode_key(&hash.encode_key()).unwrap(), hash);
+        assert!(matches!(HashDigest::decode_key(&[1, 2]), Err(StorageError::InvalidKey { expected: 32, found: 2 })));
+        assert!(matches!(u64::decode_key(&[0; 9]), Err(StorageError::InvalidKey { expected: 8, found: 9 })));
+        assert!(matches!(MetadataKey::decode_key(b"Head"), Err(StorageError::UnknownMetadataKey(_))));
+        for key in [MetadataKey::CanonicalHead, MetadataKey::SchemaVersion, MetadataKey::MigrationCursor]
+        {
+            assert_eq!(MetadataKey::decode_key(&key.encode_key()).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn test_fixed_length_values()
+    {
+        let location = TransactionLocation { block_hash: HashDigest::from([2; HashDigest::LENGTH]), index: 300 };
+        assert_eq!(TransactionLocation::decode_value(&location.encode_value().unwrap()).unwrap(), location);
+        let difficulty = U256::from(123_456_789u64) << 100;
+        assert_eq!(U256::decode_value(&difficulty.encode_value().unwrap()).unwrap(), difficulty);
+        assert!(matches!(U256::decode_value(&[1; 31]), Err(StorageError::InvalidValue { expected: 32, found: 31 })));
+    }
+}
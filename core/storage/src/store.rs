@@ -0,0 +1,419 @@
+//! # Store
+//!
+//! This module defines `SchemaStore`, typed access to the columns of the database, and
+//! `SchemaBatch`, which collects typed writes to several columns and applies them atomically.
+//!
+//! Every function takes the `Schema` of the column it reads or writes, which fixes the type of its
+//! keys and values. Iterators decode every entry and stop at the first error they meet. Values of
+//! the `Metadata` column are read and written one at a time through their `MetadataSchema`.
+
+use std::{marker::PhantomData, ops::{Bound, RangeBounds}};
+use crate::{
+    db::{key_value::{KeyValueIterator, KeyValueStore}, write_batch::WriteBatch},
+    error::StorageError,
+    column::Column,
+    schema::{KeyCodec, MetadataSchema, Schema, ValueCodec},
+};
+
+// Typed writes to several columns, applied atomically by 'SchemaStore::write'
+#[derive(Debug, Clone, Default)]
+pub struct SchemaBatch
+{
+    batch: WriteBatch,
+}
+
+impl SchemaBatch
+{
+    // The 'new' function creates an empty batch.
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    // The 'put' function records a value to write for a key.
+    pub fn put<S: Schema>(&mut self, key: &S::Key, value: &S::Value) -> Result<(), StorageError>
+    {
+        self.batch.put(S::COLUMN, key.encode_key(), value.encode_value()?);
+        Ok(())
+    }
+
+    // The 'delete' function records the deletion of a key.
+    pub fn delete<S: Schema>(&mut self, key: &S::Key)
+    {
+        self.batch.delete(S::COLUMN, key.encode_key());
+    }
+
+    // The 'put_metadata' function records a metadata value to write.
+    pub fn put_metadata<M: MetadataSchema>(&mut self, value: &M::Value) -> Result<(), StorageError>
+    {
+        self.batch.put(Column::Metadata, M::KEY.encode_key(), value.encode_value()?);
+        Ok(())
+    }
+
+    // The 'delete_metadata' function records the deletion of a metadata value.
+    pub fn delete_metadata<M: MetadataSchema>(&mut self)
+    {
+        self.batch.delete(Column::Metadata, M::KEY.encode_key());
+    }
+
+    // The 'len' function returns the number of writes in the batch.
+    pub fn len(&self) -> usize
+    {
+        self.batch.len()
+    }
+
+    // The 'is_empty' function checks if the batch has no writes.
+    pub fn is_empty(&self) -> bool
+    {
+        self.batch.is_empty()
+    }
+
+    // The 'into_inner' function returns the untyped writes of the batch.
+    pub fn into_inner(self) -> WriteBatch
+    {
+        self.batch
+    }
+}
+
+impl From<WriteBatch> for SchemaBatch
+{
+    fn from(batch: WriteBatch) -> Self
+    {
+        SchemaBatch { batch }
+    }
+}
+
+// Typed access to the columns of a database
+pub struct SchemaStore<'a, D: KeyValueStore>
+{
+    db: &'a D,
+}
+
+impl<'a, D: KeyValueStore> Clone for SchemaStore<'a, D>
+{
+    fn clone(&self) -> Self
+    {
+        *self
+    }
+}
+
+impl<'a, D: KeyValueStore> Copy for SchemaStore<'a, D> {}
+
+impl<'a, D: KeyValueStore> SchemaStore<'a, D>
+{
+    // The 'new' function creates a typed store over the given database.
+    pub fn new(db: &'a D) -> Self
+    {
+        SchemaStore { db }
+    }
+
+    // The 'get' function reads the value stored for a key.
+    pub fn get<S: Schema>(&self, key: &S::Key) -> Result<Option<S::Value>, StorageError>
+    {
+        self.db.get(&S::COLUMN, &key.encode_key())?.map(|bytes| S::Value::decode_value(&bytes)).transpose()
+    }
+
+    // The 'put' function writes a value for a key.
+    pub fn put<S: Schema>(&self, key: &S::Key, value: &S::Value) -> Result<(), StorageError>
+    {
+        self.db.put(&S::COLUMN, &key.encode_key(), &value.encode_value()?)
+    }
+
+    // The 'delete' function removes the value stored for a key.
+    pub fn delete<S: Schema>(&self, key: &S::Key) -> Result<(), StorageError>
+    {
+        self.db.delete(&S::COLUMN, &key.encode_key())
+    }
+
+    // The 'get_metadata' function reads a metadata value.
+    pub fn get_metadata<M: MetadataSchema>(&self) -> Result<Option<M::Value>, StorageError>
+    {
+        self.db.get(&Column::Metadata, &M::KEY.encode_key())?.map(|bytes| M::Value::decode_value(&bytes)).transpose()
+    }
+
+    // The 'put_metadata' function writes a metadata value.
+    pub fn put_metadata<M: MetadataSchema>(&self, value: &M::Value) -> Result<(), StorageError>
+    {
+        self.db.put(&Column::Metadata, &M::KEY.encode_key(), &value.encode_value()?)
+    }
+
+    // The 'multi_get' function reads the values stored for several keys, in the order of the keys.
+    pub fn multi_get<S: Schema>(&self, keys: &[S::Key]) -> Result<Vec<Option<S::Value>>, StorageError>
+    {
+        let keys: Vec<Vec<u8>> = keys.iter().map(KeyCodec::encode_key).collect();
+        self.db
+            .multi_get(&S::COLUMN, &keys)?
+            .into_iter()
+            .map(|value| value.map(|bytes| S::Value::decode_value(&bytes)).transpose())
+            .collect()
+    }
+
+    // The 'iter' function iterates over every entry of a column in key order.
+    pub fn iter<S: Schema>(&self) -> Result<SchemaIterator<'a, S>, StorageError>
+    {
+        self.range::<S, _>(..)
+    }
+
+    // The 'iter_prefix' function iterates in key order over the entries whose key starts with the
+    // encoding of `prefix`.
+    pub fn iter_prefix<S: Schema, P: KeyCodec>(&self, prefix: &P) -> Result<SchemaIterator<'a, S>, StorageError>
+    {
+        let prefix = prefix.encode_key();
+        let end = prefix_end(&prefix);
+        Ok(SchemaIterator::new(self.db.iter_from(&S::COLUMN, &prefix)?, end))
+    }
+
+    // The 'range' function iterates in key order over the entries whose key is within `range`.
+    pub fn range<S: Schema, R: RangeBounds<S::Key>>(&self, range: R) -> Result<SchemaIterator<'a, S>, StorageError>
+    {
+        let start = match range.start_bound()
+        {
+            Bound::Included(key) => key.encode_key(),
+            // The smallest key after an excluded start is the start followed by a zero byte.
+            Bound::Excluded(key) => { let mut key = key.encode_key(); key.push(0); key },
+            Bound::Unbounded => Vec::new(),
+        };
+        let end = range.end_bound().map(KeyCodec::encode_key);
+        Ok(SchemaIterator::new(self.db.iter_from(&S::COLUMN, &start)?, end))
+    }
+
+    // The 'write' function applies every write of a batch atomically.
+    pub fn write(&self, batch: SchemaBatch) -> Result<(), StorageError>
+    {
+        self.db.write_batch(batch.into_inner())
+    }
+}
+
+// The 'prefix_end' function returns the bound below every key starting with `prefix`.
+fn prefix_end(prefix: &[u8]) -> Bound<Vec<u8>>
+{
+    // The first key after the prefix range increments the last byte that is not 0xFF.
+    match prefix.iter().rposition(|byte| *byte != u8::MAX)
+    {
+        Some(position) => {
+            let mut end = prefix[..=position].to_vec();
+            end[position] += 1;
+            Bound::Excluded(end)
+        },
+        None => Bound::Unbounded,
+    }
+}
+
+// Iterates over the decoded entries of a column, up to an end bound
+pub struct SchemaIterator<'a, S: Schema>
+{
+    inner: KeyValueIterator<'a>,
+    end: Bound<Vec<u8>>,
+    done: bool,
+    _schema: PhantomData<S>,
+}
+
+impl<'a, S: Schema> SchemaIterator<'a, S>
+{
+    fn new(inner: KeyValueIterator<'a>, end: Bound<Vec<u8>>) -> Self
+    {
+        SchemaIterator { inner, end, done: false, _schema: PhantomData }
+    }
+
+    // The 'decode' function decodes an entry, unless its key is past the end bound
+    fn decode(&self, key: &[u8], value: &[u8]) -> Option<<Self as Iterator>::Item>
+    {
+        let within = match &self.end
+        {
+            Bound::Included(end) => key <= end.as_slice(),
+            Bound::Excluded(end) => key < end.as_slice(),
+            Bound::Unbounded => true,
+        };
+        if !within
+        {
+            return None;
+        }
+        Some(S::Key::decode_key(key).and_then(|key| Ok((key, S::Value::decode_value(value)?))))
+    }
+}
+
+impl<'a, S: Schema> Iterator for SchemaIterator<'a, S>
+{
+    type Item = Result<(S::Key, S::Value), StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        if self.done
+        {
+            return None;
+        }
+        let item = match self.inner.next()?
+        {
+            Ok((key, value)) => self.decode(&key, &value),
+            Err(error) => Some(Err(error)),
+        };
+        self.done = !matches!(item, Some(Ok(_)));
+        item
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crypto::hash::HashDigest;
+    use primvites::U256;
+    use crate::{
+        db::backend_tests,
+        schema::{CanonicalHashSchema, CanonicalHeadSchema, MigrationCursorSchema, SchemaVersionSchema, TotalDifficultySchema},
+    };
+
+    fn hash(byte: u8) -> HashDigest
+    {
+        HashDigest::from([byte; HashDigest::LENGTH])
+    }
+
+    // Stores the hash of every number from 0 to 4
+    fn canonical_hashes<D: KeyValueStore>(db: &D) -> SchemaStore<'_, D>
+    {
+        let store = SchemaStore::new(db);
+        for number in 0..5u64
+        {
+            store.put::<CanonicalHashSchema>(&number, &hash(number as u8)).unwrap();
+        }
+        store
+    }
+
+    fn collect<S: Schema>(iter: SchemaIterator<'_, S>) -> Vec<S::Key>
+    {
+        iter.map(|entry| entry.unwrap().0).collect()
+    }
+
+    fn test_get_put_delete<D: KeyValueStore>(db: &D)
+    {
+        let store = SchemaStore::new(db);
+        assert_eq!(store.get::<TotalDifficultySchema>(&hash(1)).unwrap(), None);
+
+        store.put::<TotalDifficultySchema>(&hash(1), &U256::from(1_000)).unwrap();
+        assert_eq!(store.get::<TotalDifficultySchema>(&hash(1)).unwrap(), Some(U256::from(1_000)));
+        // Values are stored in the column of their schema only.
+        assert_eq!(store.get::<CanonicalHashSchema>(&0).unwrap(), None);
+
+        store.delete::<TotalDifficultySchema>(&hash(1)).unwrap();
+        assert_eq!(store.get::<TotalDifficultySchema>(&hash(1)).unwrap(), None);
+    }
+
+    fn test_multi_get<D: KeyValueStore>(db: &D)
+    {
+        let store = canonical_hashes(db);
+        assert_eq!(
+            store.multi_get::<CanonicalHashSchema>(&[3, 7, 0]).unwrap(),
+            vec![Some(hash(3)), None, Some(hash(0))]
+        );
+        assert!(store.multi_get::<CanonicalHashSchema>(&[]).unwrap().is_empty());
+    }
+
+    fn test_range<D: KeyValueStore>(db: &D)
+    {
+        let store = canonical_hashes(db);
+        assert_eq!(collect(store.iter::<CanonicalHashSchema>().unwrap()), vec![0, 1, 2, 3, 4]);
+        assert_eq!(collect(store.range::<CanonicalHashSchema, _>(1..3).unwrap()), vec![1, 2]);
+        assert_eq!(collect(store.range::<CanonicalHashSchema, _>(1..=3).unwrap()), vec![1, 2, 3]);
+        assert_eq!(collect(store.range::<CanonicalHashSchema, _>(3..).unwrap()), vec![3, 4]);
+        assert_eq!(collect(store.range::<CanonicalHashSchema, _>(..2).unwrap()), vec![0, 1]);
+        assert_eq!(
+            collect(store.range::<CanonicalHashSchema, _>((Bound::Excluded(1), Bound::Included(3))).unwrap()),
+            vec![2, 3]
+        );
+        assert_eq!(
+            collect(store.range::<CanonicalHashSchema, _>((Bound::Excluded(1), Bound::Excluded(3))).unwrap()),
+            vec![2]
+        );
+        assert!(collect(store.range::<CanonicalHashSchema, _>(5..).unwrap()).is_empty());
+
+        let (number, value) = store.range::<CanonicalHashSchema, _>(4..).unwrap().next().unwrap().unwrap();
+        assert_eq!((number, value), (4, hash(4)));
+    }
+
+    fn test_iter_prefix<D: KeyValueStore>(db: &D)
+    {
+        let store = SchemaStore::new(db);
+        let mut inside = [1; HashDigest::LENGTH];
+        inside[8..].fill(2);
+        for key in [hash(0), hash(1), HashDigest::from(inside), hash(2)]
+        {
+            store.put::<TotalDifficultySchema>(&key, &U256::one()).unwrap();
+        }
+
+        // The prefix is the encoding of a number, here the first 8 bytes of the hashes.
+        let prefix = u64::from_be_bytes([1; 8]);
+        assert_eq!(
+            collect(store.iter_prefix::<TotalDifficultySchema, _>(&prefix).unwrap()),
+            vec![hash(1), HashDigest::from(inside)]
+        );
+        assert!(collect(store.iter_prefix::<TotalDifficultySchema, _>(&u64::MAX).unwrap()).is_empty());
+    }
+
+    fn test_metadata<D: KeyValueStore>(db: &D)
+    {
+        let store = SchemaStore::new(db);
+        assert_eq!(store.get_metadata::<SchemaVersionSchema>().unwrap(), None);
+
+        // Values of different types share the column under their own keys.
+        store.put_metadata::<SchemaVersionSchema>(&3).unwrap();
+        store.put_metadata::<CanonicalHeadSchema>(&hash(3)).unwrap();
+        let mut batch = SchemaBatch::new();
+        batch.put_metadata::<MigrationCursorSchema>(&vec![1, 2]).unwrap();
+        batch.put_metadata::<SchemaVersionSchema>(&4).unwrap();
+        store.write(batch).unwrap();
+        assert_eq!(store.get_metadata::<SchemaVersionSchema>().unwrap(), Some(4));
+        assert_eq!(store.get_metadata::<CanonicalHeadSchema>().unwrap(), Some(hash(3)));
+        assert_eq!(store.get_metadata::<MigrationCursorSchema>().unwrap(), Some(vec![1, 2]));
+
+        let mut batch = SchemaBatch::new();
+        batch.delete_metadata::<MigrationCursorSchema>();
+        store.write(batch).unwrap();
+        assert_eq!(store.get_metadata::<MigrationCursorSchema>().unwrap(), None);
+        assert_eq!(store.get_metadata::<SchemaVersionSchema>().unwrap(), Some(4));
+    }
+
+    fn test_batch_is_atomic<D: KeyValueStore>(db: &D)
+    {
+        let store = canonical_hashes(db);
+        let mut batch = SchemaBatch::new();
+        batch.put::<CanonicalHashSchema>(&5, &hash(5)).unwrap();
+        batch.delete::<CanonicalHashSchema>(&0);
+        batch.put::<TotalDifficultySchema>(&hash(5), &U256::from(5)).unwrap();
+        assert_eq!(batch.len(), 3);
+        store.write(batch).unwrap();
+
+        assert_eq!(collect(store.iter::<CanonicalHashSchema>().unwrap()), vec![1, 2, 3, 4, 5]);
+        assert_eq!(store.get::<TotalDifficultySchema>(&hash(5)).unwrap(), Some(U256::from(5)));
+    }
+
+    // Run with the 'CanonicalHash' column only
+    fn test_batch_to_missing_column<D: KeyValueStore>(db: &D)
+    {
+        // A batch writing to a missing column leaves every column unchanged.
+        let store = SchemaStore::new(db);
+        let mut batch = SchemaBatch::new();
+        batch.put::<CanonicalHashSchema>(&0, &hash(0)).unwrap();
+        batch.put::<TotalDifficultySchema>(&hash(0), &U256::one()).unwrap();
+        assert!(store.write(batch).is_err());
+        assert_eq!(store.get::<CanonicalHashSchema>(&0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_prefix_end()
+    {
+        assert_eq!(prefix_end(&[1, 2]), Bound::Excluded(vec![1, 3]));
+        assert_eq!(prefix_end(&[1, 0xFF, 0xFF]), Bound::Excluded(vec![2]));
+        assert_eq!(prefix_end(&[0xFF]), Bound::Unbounded);
+        assert_eq!(prefix_end(&[]), Bound::Unbounded);
+    }
+
+    backend_tests!(
+        test_get_put_delete,
+        test_multi_get,
+        test_range,
+        test_iter_prefix,
+        test_metadata,
+        test_batch_is_atomic,
+        test_batch_to_missing_column: &[crate::column::Column::CanonicalHash],
+    );
+}